@@ -0,0 +1,80 @@
+#![feature(test)]
+
+extern crate computor_v2;
+extern crate test;
+
+use computor_v2::Interpreter;
+use test::Bencher;
+
+/// A session with some variables around, which every call used to copy
+fn session() -> Interpreter {
+    let mut calc = Interpreter::new();
+
+    for (n, name) in ["a", "b", "c", "d", "k", "m", "r", "t", "u", "v"].iter().enumerate() {
+        calc.set_var(name, n as f64).unwrap();
+    }
+    calc.eval("f(x) = x^2 / 2 + 3 * x - 1").unwrap();
+
+    calc
+}
+
+#[bench]
+fn iterative_sum(b: &mut Bencher) {
+    let mut calc = session();
+
+    b.iter(|| calc.eval("sum(f, 1, 1000)").unwrap());
+}
+
+/// Baseline for `iterative_sum`, the same sum with the tree walker
+#[bench]
+fn tree_walker_sum(b: &mut Bencher) {
+    let mut calc = session();
+    calc.walk_functions();
+
+    b.iter(|| calc.eval("sum(f, 1, 1000)").unwrap());
+}
+
+#[bench]
+fn iterative_integrate(b: &mut Bencher) {
+    let mut calc = session();
+    calc.eval("g(x) = sin(x) * exp(-x) + sqrt(x)").unwrap();
+
+    b.iter(|| calc.eval("integrate(g, 0, 10)").unwrap());
+}
+
+#[bench]
+fn iterative_plot(b: &mut Bencher) {
+    let mut calc = session();
+
+    b.iter(|| calc.eval("plot(f, -10, 10)").unwrap());
+}
+
+#[bench]
+fn recursive_calls(b: &mut Bencher) {
+    let mut calc = session();
+    calc.eval("p(x) = f(x) + f(x + 1)").unwrap();
+    calc.eval("q(x) = p(x) * p(x - 1)").unwrap();
+    calc.eval("w(x) = q(x) - q(x / 2)").unwrap();
+
+    b.iter(|| calc.eval("sum(w, 1, 100)").unwrap());
+}
+
+#[bench]
+fn recursive_sums(b: &mut Bencher) {
+    let mut calc = session();
+    calc.eval("s(n) = sum(f, 1, n)").unwrap();
+
+    b.iter(|| calc.eval("sum(s, 1, 40)").unwrap());
+}
+
+/// Baseline for `recursive_calls`, the same calls with the tree walker
+#[bench]
+fn tree_walker_calls(b: &mut Bencher) {
+    let mut calc = session();
+    calc.walk_functions();
+    calc.eval("p(x) = f(x) + f(x + 1)").unwrap();
+    calc.eval("q(x) = p(x) * p(x - 1)").unwrap();
+    calc.eval("w(x) = q(x) - q(x / 2)").unwrap();
+
+    b.iter(|| calc.eval("sum(w, 1, 100)").unwrap());
+}
@@ -0,0 +1,25 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate computor_v2;
+
+use computor_v2::{Interpreter, Limits};
+use std::time::Duration;
+
+// Every line of the input, as typed into the REPL one after another
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let mut calc = Interpreter::new();
+        calc.set_limits(Limits {
+            max_steps: Some(100_000),
+            max_matrix_size: Some(10_000),
+            max_digits: Some(10_000),
+            timeout: Some(Duration::from_millis(500)),
+            max_context_size: Some(100),
+        });
+
+        for line in text.lines() {
+            let _ = calc.eval(line);
+        }
+    }
+});
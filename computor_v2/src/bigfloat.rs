@@ -0,0 +1,650 @@
+use crate::limits::Deadline;
+use crate::ExprError;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+use std::cmp::max;
+use std::fmt;
+
+/// Extra digits carried through series and iterations, cut off in the result
+const GUARD: usize = 10;
+
+/// Integer digits of the biggest argument `sin`, `cos` and `tan` take, reducing it
+/// needs pi to as many digits more
+const MAX_REDUCED_DIGITS: usize = 10_000;
+
+/// Decimal float `mantissa * 10^exponent`, rounded to `precision` significant digits
+#[derive(Clone, Debug)]
+pub struct BigFloat {
+    mantissa: BigInt,
+    exponent: i64,
+    precision: usize,
+}
+
+impl BigFloat {
+    fn new(mantissa: BigInt, exponent: i64, precision: usize) -> BigFloat {
+        let mut x = BigFloat {
+            mantissa,
+            exponent,
+            precision,
+        };
+
+        let n = digits(&x.mantissa);
+        if n > precision {
+            x.mantissa = div_round(&x.mantissa, &pow10(n - precision));
+            x.exponent += (n - precision) as i64;
+        }
+
+        if x.mantissa.is_zero() {
+            x.exponent = 0;
+        } else {
+            while (&x.mantissa % 10u32).is_zero() {
+                x.mantissa /= 10u32;
+                x.exponent += 1;
+            }
+        }
+
+        x
+    }
+
+    /// Parses a decimal literal like `3.1415` without losing any digit
+    pub fn parse(s: &str) -> BigFloat {
+        let (int, frac) = match s.find('.') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, ""),
+        };
+        let mantissa: BigInt = format!("{}{}", int, frac).parse().unwrap();
+
+        BigFloat::new(
+            mantissa,
+            -(frac.len() as i64),
+            max(int.len() + frac.len(), 1),
+        )
+    }
+
+    pub fn from_integer(x: BigInt, precision: usize) -> BigFloat {
+        BigFloat::new(x, 0, precision)
+    }
+
+    pub fn from_rational(x: &BigRational, precision: usize) -> BigFloat {
+        let shift = precision + GUARD + digits(x.denom());
+        let mantissa = div_round(&(x.numer() * pow10(shift)), x.denom());
+
+        BigFloat::new(mantissa, -(shift as i64), precision)
+    }
+
+    pub fn pi(precision: usize, deadline: Deadline) -> Result<BigFloat, ExprError> {
+        let q = precision + GUARD;
+
+        Ok(BigFloat::from_fixed(pi_fixed(q, deadline)?, q, precision))
+    }
+
+    pub fn e(precision: usize, deadline: Deadline) -> Result<BigFloat, ExprError> {
+        let q = precision + GUARD;
+
+        Ok(BigFloat::from_fixed(exp_fixed(&pow10(q), q, deadline)?, q, precision))
+    }
+
+    pub fn precision(&self) -> usize {
+        self.precision
+    }
+
+    /// Significant digits and the power of ten of the first one, `(d.ddd, exp)`
+    pub fn digits(&self) -> (String, i32) {
+        let digits = self.mantissa.abs().to_string();
+        let exp = self.exponent + digits.len() as i64 - 1;
+
+        (digits, exp as i32)
+    }
+
+    pub fn with_precision(&self, precision: usize) -> BigFloat {
+        BigFloat::new(self.mantissa.clone(), self.exponent, precision)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mantissa.is_negative()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.exponent >= 0
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.mantissa, self.exponent)
+            .parse()
+            .unwrap_or(std::f64::NAN)
+    }
+
+    /// Exact value, every decimal float is a rational number
+    pub fn to_rational(&self) -> BigRational {
+        let scale = pow10(self.exponent.abs() as usize);
+
+        if self.exponent >= 0 {
+            BigRational::from_integer(&self.mantissa * scale)
+        } else {
+            BigRational::new(self.mantissa.clone(), scale)
+        }
+    }
+
+    /// Integral value, `None` if the number is not an integer or too big for `i32`
+    pub fn to_i32(&self) -> Option<i32> {
+        if !self.is_integer() || self.exponent > 9 {
+            return None;
+        }
+
+        (&self.mantissa * pow10(self.exponent as usize)).to_i32()
+    }
+
+    pub fn neg(&self) -> BigFloat {
+        BigFloat {
+            mantissa: -&self.mantissa,
+            exponent: self.exponent,
+            precision: self.precision,
+        }
+    }
+
+    pub fn add(&self, other: &BigFloat) -> BigFloat {
+        let precision = max(self.precision, other.precision);
+
+        if self.is_zero() {
+            return other.with_precision(precision);
+        }
+        if other.is_zero() {
+            return self.with_precision(precision);
+        }
+
+        // Don't align exponents that are too far apart, the smaller one can't matter
+        let top_a = self.exponent + digits(&self.mantissa) as i64;
+        let top_b = other.exponent + digits(&other.mantissa) as i64;
+        if top_a - top_b > precision as i64 + 2 {
+            return self.with_precision(precision);
+        }
+        if top_b - top_a > precision as i64 + 2 {
+            return other.with_precision(precision);
+        }
+
+        let (a, b, exponent) = align(self, other);
+
+        BigFloat::new(a + b, exponent, precision)
+    }
+
+    pub fn sub(&self, other: &BigFloat) -> BigFloat {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigFloat) -> BigFloat {
+        BigFloat::new(
+            &self.mantissa * &other.mantissa,
+            self.exponent + other.exponent,
+            max(self.precision, other.precision),
+        )
+    }
+
+    /// `other` must not be zero
+    pub fn div(&self, other: &BigFloat) -> BigFloat {
+        let precision = max(self.precision, other.precision);
+        let shift = max(
+            (precision + GUARD + digits(&other.mantissa)) as i64 - digits(&self.mantissa) as i64,
+            0,
+        );
+        let mantissa = div_round(&(&self.mantissa * pow10(shift as usize)), &other.mantissa);
+
+        BigFloat::new(
+            mantissa,
+            self.exponent - other.exponent - shift,
+            precision,
+        )
+    }
+
+    /// Remainder with the sign of `self`, like `%` on `f64`; `other` must not be zero
+    pub fn rem(&self, other: &BigFloat) -> BigFloat {
+        let (a, b, exponent) = align(self, other);
+
+        BigFloat::new(a % b, exponent, max(self.precision, other.precision))
+    }
+
+    pub fn powi(&self, n: i32) -> BigFloat {
+        let working = self.precision + GUARD + digits(&BigInt::from(n));
+        let mut base = self.with_precision(working);
+        let mut res = BigFloat::from_integer(BigInt::one(), working);
+        let mut k = (n as i64).abs();
+
+        while k > 0 {
+            if k & 1 == 1 {
+                res = res.mul(&base);
+            }
+            base = base.mul(&base);
+            k >>= 1;
+        }
+
+        if n < 0 {
+            res = BigFloat::from_integer(BigInt::one(), working).div(&res);
+        }
+
+        res.with_precision(self.precision)
+    }
+
+    /// `None` for a negative base with a non-integer exponent or an overflow
+    pub fn pow(&self, other: &BigFloat, deadline: Deadline) -> Result<Option<BigFloat>, ExprError> {
+        match other.to_i32() {
+            Some(n) => Ok(Some(self.powi(n))),
+            None if self.is_zero() && !other.is_negative() => Ok(Some(self.clone())),
+            None => {
+                let working = self.with_precision(self.precision + GUARD);
+                let ln = match working.ln(deadline)? {
+                    Some(ln) => ln,
+                    None => return Ok(None),
+                };
+                let res = ln.mul(other).exp(deadline)?;
+
+                Ok(res.map(|x| x.with_precision(self.precision)))
+            }
+        }
+    }
+
+    pub fn sqrt(&self) -> Option<BigFloat> {
+        if self.is_negative() {
+            return None;
+        }
+        if self.is_zero() {
+            return Some(self.clone());
+        }
+
+        // Integer square root of a mantissa with enough digits and an even exponent
+        let q = self.precision + GUARD;
+        let mut shift = max(2 * q as i64 - digits(&self.mantissa) as i64, 0);
+        if (self.exponent - shift) % 2 != 0 {
+            shift += 1;
+        }
+        let root = (&self.mantissa * pow10(shift as usize)).sqrt();
+
+        Some(BigFloat::new(
+            root,
+            (self.exponent - shift) / 2,
+            self.precision,
+        ))
+    }
+
+    /// `None` when the result would be absurdly big
+    pub fn exp(&self, deadline: Deadline) -> Result<Option<BigFloat>, ExprError> {
+        let int_digits = self.int_digits();
+        if int_digits > 18 {
+            return Ok(None);
+        }
+
+        // exp(x) = exp(x - n ln 10) * 10^n keeps the fixed point part small
+        let q = self.precision + GUARD + int_digits;
+        let x = self.to_fixed(q);
+        let ln10 = ln_fixed(&(pow10(q) * 10u32), q, deadline)?;
+        let n = div_round(&x, &ln10);
+        let r = div_round(&(x - &n * &ln10), &pow10(int_digits));
+
+        let q = self.precision + GUARD;
+        let mut res = BigFloat::from_fixed(exp_fixed(&r, q, deadline)?, q, self.precision);
+        res.exponent += match n.to_i64() {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+
+        Ok(Some(res))
+    }
+
+    /// `None` outside of the domain
+    pub fn ln(&self, deadline: Deadline) -> Result<Option<BigFloat>, ExprError> {
+        if self.is_negative() || self.is_zero() {
+            return Ok(None);
+        }
+
+        // self = y * 10^k with y in [1, 10)
+        let n = digits(&self.mantissa);
+        let k = self.exponent + n as i64 - 1;
+        let y = BigFloat {
+            mantissa: self.mantissa.clone(),
+            exponent: 1 - n as i64,
+            precision: self.precision,
+        };
+
+        let q = self.precision + GUARD + digits(&BigInt::from(k));
+        let ln10 = ln_fixed(&(pow10(q) * 10u32), q, deadline)?;
+        let res = ln_fixed(&y.to_fixed(q), q, deadline)? + ln10 * k;
+
+        Ok(Some(BigFloat::from_fixed(res, q, self.precision)))
+    }
+
+    /// Whether `sin`, `cos` and `tan` take `self`, `false` past `10^10000`
+    pub fn is_reducible(&self) -> bool {
+        self.int_digits() <= MAX_REDUCED_DIGITS
+    }
+
+    /// `None` if `self` is not reducible
+    pub fn sin(&self, deadline: Deadline) -> Result<Option<BigFloat>, ExprError> {
+        let (r, q) = match self.reduced(deadline)? {
+            Some(reduced) => reduced,
+            None => return Ok(None),
+        };
+
+        Ok(Some(BigFloat::from_fixed(sin_fixed(&r, q, deadline)?, q, self.precision)))
+    }
+
+    /// `None` if `self` is not reducible
+    pub fn cos(&self, deadline: Deadline) -> Result<Option<BigFloat>, ExprError> {
+        let (r, q) = match self.reduced(deadline)? {
+            Some(reduced) => reduced,
+            None => return Ok(None),
+        };
+
+        Ok(Some(BigFloat::from_fixed(cos_fixed(&r, q, deadline)?, q, self.precision)))
+    }
+
+    /// `None` where the cosine is zero or if `self` is not reducible
+    pub fn tan(&self, deadline: Deadline) -> Result<Option<BigFloat>, ExprError> {
+        let (r, q) = match self.reduced(deadline)? {
+            Some(reduced) => reduced,
+            None => return Ok(None),
+        };
+        let cos = cos_fixed(&r, q, deadline)?;
+
+        if cos.is_zero() {
+            return Ok(None);
+        }
+
+        let tan = div_round(&(sin_fixed(&r, q, deadline)? * pow10(q)), &cos);
+
+        Ok(Some(BigFloat::from_fixed(tan, q, self.precision)))
+    }
+
+    pub fn atan(&self, deadline: Deadline) -> Result<BigFloat, ExprError> {
+        let q = self.precision + GUARD;
+        let res = atan_fixed(&self.to_fixed(q), q, deadline)?;
+
+        Ok(BigFloat::from_fixed(res, q, self.precision))
+    }
+
+    /// Digits before the decimal point
+    fn int_digits(&self) -> usize {
+        max(self.exponent + digits(&self.mantissa) as i64, 0) as usize
+    }
+
+    /// Fixed point integer `self * 10^q`
+    fn to_fixed(&self, q: usize) -> BigInt {
+        let shift = self.exponent + q as i64;
+
+        if shift >= 0 {
+            &self.mantissa * pow10(shift as usize)
+        } else {
+            div_round(&self.mantissa, &pow10((-shift) as usize))
+        }
+    }
+
+    fn from_fixed(x: BigInt, q: usize, precision: usize) -> BigFloat {
+        BigFloat::new(x, -(q as i64), precision)
+    }
+
+    /// `self` reduced to [-pi, pi] as a fixed point number, with its scale
+    fn reduced(&self, deadline: Deadline) -> Result<Option<(BigInt, usize)>, ExprError> {
+        if !self.is_reducible() {
+            return Ok(None);
+        }
+
+        let int_digits = self.int_digits();
+        let q = self.precision + GUARD;
+        let x = self.to_fixed(q + int_digits);
+        let two_pi = pi_fixed(q + int_digits, deadline)? * 2u32;
+        let k = div_round(&x, &two_pi);
+        let r = div_round(&(x - k * two_pi), &pow10(int_digits));
+
+        Ok(Some((r, q)))
+    }
+}
+
+impl fmt::Display for BigFloat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let sign = if self.is_negative() { "-" } else { "" };
+        let digits = self.mantissa.abs().to_string();
+        let n = digits.len() as i64;
+        // Position of the decimal point, counted from the first digit
+        let point = self.exponent + n;
+
+        if point > max(self.precision as i64, n) || point < -5 {
+            let (first, rest) = digits.split_at(1);
+            let rest = if rest.is_empty() {
+                String::new()
+            } else {
+                format!(".{}", rest)
+            };
+
+            write!(f, "{}{}{}e{}", sign, first, rest, point - 1)
+        } else if point <= 0 {
+            write!(f, "{}0.{}{}", sign, "0".repeat((-point) as usize), digits)
+        } else if point >= n {
+            write!(f, "{}{}{}", sign, digits, "0".repeat((point - n) as usize))
+        } else {
+            let (int, frac) = digits.split_at(point as usize);
+
+            write!(f, "{}{}.{}", sign, int, frac)
+        }
+    }
+}
+
+fn pow10(n: usize) -> BigInt {
+    BigInt::from(10u32).pow(n as u32)
+}
+
+fn digits(x: &BigInt) -> usize {
+    if x.is_zero() {
+        0
+    } else {
+        x.abs().to_string().len()
+    }
+}
+
+/// Division rounding half away from zero
+fn div_round(a: &BigInt, b: &BigInt) -> BigInt {
+    let (q, r) = a.div_rem(b);
+
+    if r.abs() * 2u32 >= b.abs() {
+        if a.is_negative() == b.is_negative() {
+            q + 1u32
+        } else {
+            q - 1u32
+        }
+    } else {
+        q
+    }
+}
+
+/// Both mantissas brought to the smaller of the two exponents
+fn align(a: &BigFloat, b: &BigFloat) -> (BigInt, BigInt, i64) {
+    let exponent = std::cmp::min(a.exponent, b.exponent);
+
+    (
+        &a.mantissa * pow10((a.exponent - exponent) as usize),
+        &b.mantissa * pow10((b.exponent - exponent) as usize),
+        exponent,
+    )
+}
+
+fn fixed_from_f64(x: f64, q: usize) -> BigInt {
+    let x = BigInt::from_f64((x * 1e15).round()).unwrap_or_else(BigInt::zero);
+
+    if q >= 15 {
+        x * pow10(q - 15)
+    } else {
+        div_round(&x, &pow10(15 - q))
+    }
+}
+
+// Everything below works on fixed point integers `x * 10^q`, series check
+// the deadline once a term
+
+/// Meant for |x| of a few units at most
+fn exp_fixed(x: &BigInt, q: usize, deadline: Deadline) -> Result<BigInt, ExprError> {
+    // Taylor series on x / 2^8, then squared back 8 times
+    const HALVINGS: u32 = 8;
+    const EXTRA: usize = 4;
+
+    let one = pow10(q + EXTRA);
+    let y = div_round(&(x * pow10(EXTRA)), &BigInt::from(1u32 << HALVINGS));
+
+    let mut sum = one.clone();
+    let mut term = one.clone();
+    let mut k = 1u32;
+    loop {
+        deadline.check()?;
+        term = div_round(&(&term * &y), &(&one * k));
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+        k += 1;
+    }
+
+    for _ in 0..HALVINGS {
+        sum = div_round(&(&sum * &sum), &one);
+    }
+
+    Ok(div_round(&sum, &pow10(EXTRA)))
+}
+
+/// Newton iterations on exp, meant for x in [1, 10]
+fn ln_fixed(x: &BigInt, q: usize, deadline: Deadline) -> Result<BigInt, ExprError> {
+    let one = pow10(q);
+    let guess = BigFloat::from_fixed(x.clone(), q, 17).to_f64().ln();
+    let mut y = fixed_from_f64(guess, q);
+
+    for _ in 0..64 {
+        let e = exp_fixed(&y, q, deadline)?;
+        let delta = div_round(&((x - &e) * 2u32 * &one), &(x + &e));
+
+        y += &delta;
+
+        if delta.abs() < BigInt::from(100u32) {
+            break;
+        }
+    }
+
+    Ok(y)
+}
+
+/// atan(1 / n)
+fn atan_inv_fixed(n: u32, q: usize, deadline: Deadline) -> Result<BigInt, ExprError> {
+    let mut term = pow10(q) / n;
+    let mut sum = term.clone();
+    let mut k = 1u32;
+
+    loop {
+        deadline.check()?;
+        term /= n * n;
+        let t = &term / (2 * k + 1);
+        if t.is_zero() {
+            break;
+        }
+        if k % 2 == 1 {
+            sum -= t;
+        } else {
+            sum += t;
+        }
+        k += 1;
+    }
+
+    Ok(sum)
+}
+
+/// Machin's formula: pi = 16 atan(1/5) - 4 atan(1/239)
+fn pi_fixed(q: usize, deadline: Deadline) -> Result<BigInt, ExprError> {
+    let q2 = q + 5;
+    let pi = atan_inv_fixed(5, q2, deadline)? * 16u32 - atan_inv_fixed(239, q2, deadline)? * 4u32;
+
+    Ok(div_round(&pi, &pow10(5)))
+}
+
+fn sin_fixed(x: &BigInt, q: usize, deadline: Deadline) -> Result<BigInt, ExprError> {
+    let one = pow10(q);
+    let x2 = div_round(&(x * x), &one);
+    let mut term = x.clone();
+    let mut sum = x.clone();
+    let mut k = 1u32;
+
+    loop {
+        deadline.check()?;
+        term = -div_round(&(&term * &x2), &(&one * (2 * k) * (2 * k + 1)));
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+        k += 1;
+    }
+
+    Ok(sum)
+}
+
+fn cos_fixed(x: &BigInt, q: usize, deadline: Deadline) -> Result<BigInt, ExprError> {
+    let one = pow10(q);
+    let x2 = div_round(&(x * x), &one);
+    let mut term = one.clone();
+    let mut sum = one.clone();
+    let mut k = 1u32;
+
+    loop {
+        deadline.check()?;
+        term = -div_round(&(&term * &x2), &(&one * (2 * k - 1) * (2 * k)));
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+        k += 1;
+    }
+
+    Ok(sum)
+}
+
+fn atan_fixed(x: &BigInt, q: usize, deadline: Deadline) -> Result<BigInt, ExprError> {
+    let one = pow10(q);
+
+    // atan(x) = +-pi/2 - atan(1/x)
+    if x.abs() > one {
+        let half_pi = pi_fixed(q, deadline)? / 2u32;
+        let inv = atan_fixed(&div_round(&(&one * &one), x), q, deadline)?;
+
+        return Ok(if x.is_negative() {
+            -half_pi - inv
+        } else {
+            half_pi - inv
+        });
+    }
+
+    // atan(x) = 2 atan(x / (1 + sqrt(1 + x^2))), twice, so the series converges fast
+    let mut y = x.clone();
+    for _ in 0..2 {
+        let root = (&one * &one + &y * &y).sqrt();
+        y = div_round(&(&y * &one), &(&one + root));
+    }
+
+    let y2 = div_round(&(&y * &y), &one);
+    let mut term = y.clone();
+    let mut sum = y;
+    let mut k = 1u32;
+
+    loop {
+        deadline.check()?;
+        term = -div_round(&(&term * &y2), &one);
+        let t = &term / (2 * k + 1);
+        if t.is_zero() {
+            break;
+        }
+        sum += t;
+        k += 1;
+    }
+
+    Ok(sum * 4u32)
+}
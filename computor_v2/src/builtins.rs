@@ -0,0 +1,636 @@
+use crate::bigfloat::BigFloat;
+use crate::compile::Program;
+use crate::plot::{Curve, Kind, Plot};
+use crate::{calculus, limits, linalg, matrix, symbolic};
+use crate::{rational, to_f64, Context, Deadline, Expr, ExprError};
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Constants that exist without being defined, looked up after user variables
+pub fn constant(name: &str, context: &Context) -> Result<Option<Expr>, ExprError> {
+    let deadline = context.deadline();
+
+    Ok(match (name, context.settings.precision) {
+        ("pi", Some(precision)) => Some(Expr::BigFloat(BigFloat::pi(precision, deadline)?)),
+        ("pi", None) => Some(Expr::Real(std::f64::consts::PI)),
+        ("e", Some(precision)) => Some(Expr::BigFloat(BigFloat::e(precision, deadline)?)),
+        ("e", None) => Some(Expr::Real(std::f64::consts::E)),
+        _ => None,
+    })
+}
+
+/// Functions that exist without being defined, looked up after user ones
+pub fn call(
+    name: &str,
+    args: Vec<Expr>,
+    context: &mut Context,
+    level: usize,
+) -> Result<Expr, ExprError> {
+    match name {
+        "sqrt" | "exp" | "ln" | "sin" | "cos" | "tan" | "atan" => {
+            math(name, one(name, args)?, context)
+        }
+        "abs" => match args.as_slice() {
+            [Expr::Integer(x)] => Ok(Expr::Integer(x.abs())),
+            [Expr::Rational(x)] => Ok(Expr::Rational(x.abs())),
+            [Expr::BigFloat(x)] if x.is_negative() => Ok(Expr::BigFloat(x.neg())),
+            [Expr::BigFloat(x)] => Ok(Expr::BigFloat(x.clone())),
+            [Expr::Real(x)] => Ok(Expr::Real(x.abs())),
+            [Expr::Complex(x, y)] => Ok(Expr::Real(x.hypot(*y))),
+            [_] => Err(ExprError::CalcError {
+                err: "abs works only on numbers".into(),
+            }),
+            _ => Err(ExprError::BadArgsCount {
+                func: name.to_string(),
+            }),
+        },
+        "choose" => {
+            let args = integers(name, args, 2)?;
+
+            choose(&args[0], &args[1], context)
+        }
+        "expand" | "factor" | "polydiv" | "compose" => algebra(name, args, context.deadline()),
+        "gcd" if args.iter().any(|x| matches!(x, Expr::Lambda(..))) => {
+            algebra(name, args, context.deadline())
+        }
+        "gcd" => {
+            let args = integers(name, args, 2)?;
+
+            Ok(Expr::Integer(args[0].gcd(&args[1])))
+        }
+        "lcm" => {
+            let args = integers(name, args, 2)?;
+
+            Ok(Expr::Integer(args[0].lcm(&args[1])))
+        }
+        "dot" | "cross" | "norm" | "normalize" | "row" | "col" | "vec" => vector(name, args),
+        "eig" | "lu" | "qr" | "svd" | "chol" | "transpose" => match one(name, args)? {
+            Expr::Matrix(m) => decompose(name, &m, context.deadline()),
+            _ => Err(ExprError::CalcError {
+                err: format!("{} works only on matrices", name),
+            }),
+        },
+        "zeros" | "ones" | "eye" | "diag" | "range" | "reshape" | "hcat" | "vcat" => {
+            matrix::call(name, args, &context.limits)
+        }
+        "integrate" | "nderiv" | "limit" => numeric(name, args, context, level),
+        "sum" | "prod" => series(name, args, context, level),
+        "linsolve" => {
+            let (a, b) = two(name, args)?;
+
+            a.solve(b, context)
+        }
+        "isprime" => {
+            let args = integers(name, args, 1)?;
+
+            Ok(Expr::Integer(BigInt::from(is_prime(&args[0], context.deadline())? as u32)))
+        }
+        _ => Err(ExprError::UndefinedVariable {
+            name: name.to_string(),
+        }),
+    }
+}
+
+/// Irrational functions, exact input goes through `BigFloat` when a precision is set
+fn math(name: &str, x: Expr, context: &Context) -> Result<Expr, ExprError> {
+    let domain = || ExprError::CalcError {
+        err: format!("{} is not defined there", name),
+    };
+
+    let x = match x {
+        Expr::Integer(x) => BigRational::from_integer(x),
+        Expr::Rational(x) => x,
+        Expr::Real(x) => {
+            return match name {
+                "sqrt" if x < 0.0 => Ok(Expr::Complex(0.0, (-x).sqrt())),
+                "sqrt" => Ok(Expr::Real(x.sqrt())),
+                "exp" => Ok(Expr::Real(x.exp())),
+                "ln" if x <= 0.0 => Err(domain()),
+                "ln" => Ok(Expr::Real(x.ln())),
+                "sin" => Ok(Expr::Real(x.sin())),
+                "cos" => Ok(Expr::Real(x.cos())),
+                "tan" => Ok(Expr::Real(x.tan())),
+                _ => Ok(Expr::Real(x.atan())),
+            }
+        }
+        Expr::BigFloat(x) => {
+            let deadline = context.deadline();
+            let res = match name {
+                "sin" | "cos" | "tan" if !x.is_reducible() => {
+                    return Err(ExprError::CalcError {
+                        err: format!("{} argument is too big", name),
+                    });
+                }
+                "sqrt" if x.is_negative() => {
+                    return Ok(Expr::Complex(0.0, (-x.to_f64()).sqrt()));
+                }
+                "sqrt" => x.sqrt(),
+                "exp" => x.exp(deadline)?,
+                "ln" => x.ln(deadline)?,
+                "sin" => x.sin(deadline)?,
+                "cos" => x.cos(deadline)?,
+                "tan" => x.tan(deadline)?,
+                _ => Some(x.atan(deadline)?),
+            };
+
+            return res.map(Expr::BigFloat).ok_or_else(domain);
+        }
+        _ => {
+            return Err(ExprError::CalcError {
+                err: format!("{} works only on real numbers", name),
+            })
+        }
+    };
+
+    // Square roots of perfect squares stay exact
+    if name == "sqrt" && !x.is_negative() {
+        let (numer, denom) = (x.numer().sqrt(), x.denom().sqrt());
+
+        if &(&numer * &numer) == x.numer() && &(&denom * &denom) == x.denom() {
+            return Ok(rational(BigRational::new(numer, denom)));
+        }
+    }
+
+    match context.settings.precision {
+        Some(precision) => {
+            let x = BigFloat::from_rational(&x, precision);
+
+            math(name, Expr::BigFloat(x), context)
+        }
+        None => math(name, Expr::Real(to_f64(&x)), context),
+    }
+}
+
+/// Polynomial algebra on one argument functions, results are functions too
+fn algebra(name: &str, args: Vec<Expr>, deadline: Deadline) -> Result<Expr, ExprError> {
+    let mut polys = vec![];
+
+    for arg in &args {
+        polys.push(symbolic::function_poly(name, arg)?);
+    }
+
+    let lambda = |x: &str, body: Expr| Expr::Lambda(vec![x.to_string()], box body);
+
+    match (name, polys.as_slice()) {
+        ("expand", [(x, p)]) => Ok(lambda(x, symbolic::from_poly(p, x))),
+        ("factor", [(x, p)]) => Ok(lambda(x, symbolic::from_factors(&p.factor(deadline)?, x))),
+        ("polydiv", [(x, p), (_, q)]) => {
+            let (quot, rem) = p.div_rem(q).ok_or(ExprError::DivisionByZero)?;
+
+            Ok(Expr::Tuple(vec![
+                lambda(x, symbolic::from_poly(&quot, x)),
+                lambda(x, symbolic::from_poly(&rem, x)),
+            ]))
+        }
+        ("gcd", [(x, p), (_, q)]) => Ok(lambda(x, symbolic::from_poly(&p.gcd(q), x))),
+        // f(g(x)) is a function of g's argument
+        ("compose", [(_, p), (x, q)]) => Ok(lambda(x, symbolic::from_poly(&p.compose(q), x))),
+        _ => Err(ExprError::BadArgsCount {
+            func: name.to_string(),
+        }),
+    }
+}
+
+/// Builtins that get their arguments unevaluated
+pub const SPECIAL: [&str; 4] = ["antideriv", "plot", "parametric", "polar"];
+
+pub fn special(
+    name: &str,
+    args: Vec<Expr>,
+    context: &mut Context,
+    level: usize,
+) -> Result<Expr, ExprError> {
+    match name {
+        "antideriv" => antideriv(args, context, level),
+        _ => plot(name, args, context, level),
+    }
+}
+
+/// `plot(f, g, ..., xmin, xmax)`, `parametric(fx, fy, ..., tmin, tmax)` and
+/// `polar(r, ..., tmin, tmax)`, curves are labeled with the names they are given by
+fn plot(
+    name: &str,
+    args: Vec<Expr>,
+    context: &mut Context,
+    level: usize,
+) -> Result<Expr, ExprError> {
+    if args.len() < 3 {
+        return Err(ExprError::BadArgsCount {
+            func: name.to_string(),
+        });
+    }
+
+    let mut args = args;
+    let mut range = vec![];
+
+    for x in args.split_off(args.len() - 2) {
+        range.push(matrix::real(name, &x.run(context, level)?)?);
+    }
+
+    let (from, to) = (range[0], range[1]);
+
+    if from >= to || !from.is_finite() || !to.is_finite() {
+        return Err(ExprError::CalcError {
+            err: format!("{} needs a finite range with the smaller end first", name),
+        });
+    }
+
+    let mut funcs = vec![];
+
+    for arg in args {
+        let label = match arg {
+            Expr::Var(ref name) => name.clone(),
+            ref arg => arg.formatted(&context.settings).to_string(),
+        };
+
+        match arg.run(context, level)? {
+            f @ Expr::Lambda(..) => {
+                let program = Program::new(&f, &label, context)?;
+                funcs.push((label, program));
+            }
+            _ => {
+                return Err(ExprError::CalcError {
+                    err: format!("{} takes functions, {} is not one", name, label),
+                })
+            }
+        }
+    }
+
+    let eval = |f: &mut Program, x: f64| {
+        let y = f.call(vec![Expr::Real(x)], level).ok()?;
+
+        matrix::real(name, &y).ok()
+    };
+
+    let (kind, curves) = match name {
+        "parametric" if funcs.len() % 2 == 1 => {
+            return Err(ExprError::CalcError {
+                err: "parametric takes functions in pairs, one for x and one for y".into(),
+            })
+        }
+        "parametric" => (
+            Kind::Parametric,
+            funcs
+                .chunks_mut(2)
+                .map(|pair| {
+                    let label = format!("({}, {})", pair[0].0, pair[1].0);
+                    let (fx, fy) = pair.split_at_mut(1);
+                    let mut f = |t| Some((eval(&mut fx[0].1, t)?, eval(&mut fy[0].1, t)?));
+
+                    Curve::trace(label, &mut f, from, to)
+                })
+                .collect(),
+        ),
+        "polar" => (
+            Kind::Polar,
+            funcs
+                .iter_mut()
+                .map(|(label, f)| {
+                    let mut point = |t: f64| eval(f, t).map(|r| (r * t.cos(), r * t.sin()));
+
+                    Curve::trace(label.clone(), &mut point, from, to)
+                })
+                .collect(),
+        ),
+        _ => (
+            Kind::Function,
+            funcs
+                .iter_mut()
+                .map(|(label, f)| Curve::sample(label.clone(), &mut |x| eval(f, x), from, to))
+                .collect(),
+        ),
+    };
+
+    Ok(Expr::Plot(Plot::new(kind, curves, (from, to))))
+}
+
+/// `antideriv(f, x)` gives a new function, `x` may be left out when `f` has one argument
+fn antideriv(args: Vec<Expr>, context: &mut Context, level: usize) -> Result<Expr, ExprError> {
+    let mut args = args.into_iter();
+
+    let (f, x) = match (args.next(), args.next(), args.next()) {
+        (Some(f), x, None) => (f.run(context, level)?, x),
+        _ => {
+            return Err(ExprError::BadArgsCount {
+                func: "antideriv".into(),
+            })
+        }
+    };
+
+    let (names, body) = match f {
+        Expr::Lambda(names, body) => (names, body),
+        _ => {
+            return Err(ExprError::CalcError {
+                err: "antideriv takes a user function first".into(),
+            })
+        }
+    };
+
+    let x = match x {
+        Some(Expr::Var(x)) => x,
+        None if names.len() == 1 => names[0].clone(),
+        _ => {
+            return Err(ExprError::CalcError {
+                err: "antideriv takes the name of the variable second".into(),
+            })
+        }
+    };
+
+    if !names.contains(&x) {
+        return Err(ExprError::CalcError {
+            err: format!("{} is not an argument of the function", x),
+        });
+    }
+
+    let body = symbolic::antideriv(&body, &x)?;
+
+    Ok(Expr::Lambda(names, box body))
+}
+
+/// Numeric calculus on a one argument user function, gives `(value, error estimate)`
+fn numeric(
+    name: &str,
+    args: Vec<Expr>,
+    context: &Context,
+    level: usize,
+) -> Result<Expr, ExprError> {
+    let mut points = vec![];
+
+    for arg in args.iter().skip(1) {
+        points.push(matrix::real(name, arg)?);
+    }
+
+    let f = match args.first() {
+        Some(f @ Expr::Lambda(..)) => f,
+        _ => {
+            return Err(ExprError::CalcError {
+                err: format!("{} takes a user function first", name),
+            })
+        }
+    };
+    let mut f = Program::new(f, name, context)?;
+    let mut f = |x: f64| matrix::real(name, &f.call(vec![Expr::Real(x)], level)?);
+
+    let (value, error) = match (name, points.as_slice()) {
+        ("integrate", &[a, b]) => calculus::integrate(&mut f, a, b)?,
+        ("nderiv", &[x]) => calculus::derivative(&mut f, x)?,
+        ("limit", &[x]) => calculus::limit(&mut f, x)?,
+        _ => {
+            return Err(ExprError::BadArgsCount {
+                func: name.to_string(),
+            })
+        }
+    };
+
+    Ok(Expr::Tuple(vec![Expr::Real(value), Expr::Real(error)]))
+}
+
+/// `sum(f, n0, n1)` and `prod(f, n0, n1)` over whole `n` from `n0` to `n1`, exact when `f` is
+fn series(
+    name: &str,
+    args: Vec<Expr>,
+    context: &mut Context,
+    level: usize,
+) -> Result<Expr, ExprError> {
+    let (f, from, to) = match args.as_slice() {
+        [f @ Expr::Lambda(..), from, to] => (f, matrix::position(from)?, matrix::position(to)?),
+        [_, _, _] => {
+            return Err(ExprError::CalcError {
+                err: format!("{} takes a user function first", name),
+            })
+        }
+        _ => {
+            return Err(ExprError::BadArgsCount {
+                func: name.to_string(),
+            })
+        }
+    };
+
+    let mut f = Program::new(f, name, context)?;
+    let mut res = Expr::Integer(BigInt::from((name == "prod") as u32));
+
+    for n in from..=to {
+        let term = f.call(vec![Expr::Integer(BigInt::from(n))], level)?;
+
+        res = match name {
+            "sum" => res.add(term, context)?,
+            _ => res.mul(term, context)?,
+        };
+        context.limits.check(&res)?;
+    }
+
+    Ok(res)
+}
+
+fn vector(name: &str, args: Vec<Expr>) -> Result<Expr, ExprError> {
+    let vectors: Vec<Vec<f64>> = match name {
+        "vec" => return one(name, args)?.into_vector(),
+        _ => args
+            .into_iter()
+            .map(|arg| match arg {
+                Expr::Vector(x) => Ok(x),
+                _ => Err(ExprError::CalcError {
+                    err: format!("{} works only on vectors", name),
+                }),
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    match (name, vectors.as_slice()) {
+        ("dot", [x, y]) if x.len() == y.len() => {
+            Ok(Expr::Real(x.iter().zip(y).map(|(x, y)| x * y).sum()))
+        }
+        ("cross", [x, y]) if x.len() == 3 && y.len() == 3 => Ok(Expr::Vector(vec![
+            x[1] * y[2] - x[2] * y[1],
+            x[2] * y[0] - x[0] * y[2],
+            x[0] * y[1] - x[1] * y[0],
+        ])),
+        ("dot", [_, _]) => Err(ExprError::CalcError {
+            err: "vectors have different sizes".into(),
+        }),
+        ("cross", [_, _]) => Err(ExprError::CalcError {
+            err: "cross works only on 3D vectors".into(),
+        }),
+        ("norm", [x]) => Ok(Expr::Real(norm(x))),
+        ("normalize", [x]) if norm(x) == 0.0 => Err(ExprError::DivisionByZero),
+        ("normalize", [x]) => Ok(Expr::Vector(x.iter().map(|v| v / norm(x)).collect())),
+        ("row", [x]) => Ok(Expr::Matrix(vec![x.clone()])),
+        ("col", [x]) => Ok(Expr::Matrix(x.iter().map(|&v| vec![v]).collect())),
+        _ => Err(ExprError::BadArgsCount {
+            func: name.to_string(),
+        }),
+    }
+}
+
+/// Several results come back as a tuple of matrices
+fn decompose(name: &str, m: &[Vec<f64>], deadline: Deadline) -> Result<Expr, ExprError> {
+    let tuple = |items: Vec<linalg::Matrix>| {
+        Expr::Tuple(items.into_iter().map(Expr::Matrix).collect())
+    };
+
+    match name {
+        "lu" => linalg::lu(m).map(|(l, u, p)| tuple(vec![l, u, p])),
+        "qr" => linalg::qr(m).map(|(q, r)| tuple(vec![q, r])),
+        "svd" => linalg::svd(m, deadline).map(|(u, s, v)| tuple(vec![u, s, v])),
+        "chol" => linalg::chol(m).map(Expr::Matrix),
+        "transpose" => Ok(Expr::Matrix(linalg::transpose(m))),
+        _ => {
+            let (values, vectors) = linalg::eig(m, deadline)?;
+
+            // Real eigenvalues are handier as a vector
+            let values = if values.iter().all(|x| x.1 == 0.0) {
+                Expr::Vector(values.iter().map(|x| x.0).collect())
+            } else {
+                Expr::Tuple(
+                    values
+                        .iter()
+                        .map(|&(re, im)| match im {
+                            im if im == 0.0 => Expr::Real(re),
+                            im => Expr::Complex(re, im),
+                        })
+                        .collect(),
+                )
+            };
+
+            Ok(Expr::Tuple(vec![values, Expr::Matrix(vectors)]))
+        }
+    }
+}
+
+fn norm(x: &[f64]) -> f64 {
+    x.iter().map(|v| v * v).sum::<f64>().sqrt()
+}
+
+/// The only argument of a one argument function
+fn one(func: &str, args: Vec<Expr>) -> Result<Expr, ExprError> {
+    if args.len() != 1 {
+        return Err(ExprError::BadArgsCount {
+            func: func.to_string(),
+        });
+    }
+
+    Ok(args.into_iter().next().unwrap())
+}
+
+fn two(func: &str, args: Vec<Expr>) -> Result<(Expr, Expr), ExprError> {
+    if args.len() != 2 {
+        return Err(ExprError::BadArgsCount {
+            func: func.to_string(),
+        });
+    }
+
+    let mut args = args.into_iter();
+
+    Ok((args.next().unwrap(), args.next().unwrap()))
+}
+
+fn integers(func: &str, args: Vec<Expr>, count: usize) -> Result<Vec<BigInt>, ExprError> {
+    if args.len() != count {
+        return Err(ExprError::BadArgsCount {
+            func: func.to_string(),
+        });
+    }
+
+    args.into_iter()
+        .map(|arg| match arg {
+            Expr::Integer(x) => Ok(x),
+            _ => Err(ExprError::CalcError {
+                err: format!("{} works only on integers", func),
+            }),
+        })
+        .collect()
+}
+
+fn choose(n: &BigInt, k: &BigInt, context: &Context) -> Result<Expr, ExprError> {
+    if n.is_negative() {
+        return Err(ExprError::CalcError {
+            err: "choose works only on natural numbers".into(),
+        });
+    }
+
+    if k.is_negative() || k > n {
+        return Ok(Expr::Integer(BigInt::zero()));
+    }
+
+    let k = std::cmp::min(k.clone(), n - k);
+    let k = match k.to_u64() {
+        Some(k) => k,
+        None => {
+            return Err(ExprError::CalcError {
+                err: "choose arguments are too big".into(),
+            })
+        }
+    };
+
+    if k > 0 {
+        // At most k * log10(n / k) + (n - k) * log10(n / (n - k)) digits, the
+        // second term being below k * log10(e)
+        let k_f64 = k as f64;
+        let log10_n = match n.to_f64() {
+            Some(n) if n.is_finite() => n.log10(),
+            _ => n.bits() as f64 * std::f64::consts::LOG10_2,
+        };
+        let rest = match (n - k).to_f64() {
+            Some(m) if m.is_finite() => m * (k_f64 / m).ln_1p() * std::f64::consts::LOG10_E,
+            _ => k_f64 * std::f64::consts::LOG10_E,
+        };
+        context.limits.check_digits(k_f64 * (log10_n - k_f64.log10()) + rest)?;
+    }
+
+    // Every partial product is itself a binomial coefficient, so division is exact
+    let deadline = context.deadline();
+    let mut res = BigInt::one();
+    for i in 0..k {
+        deadline.check()?;
+        res = res * (n - i) / (i + 1);
+    }
+
+    Ok(Expr::Integer(res))
+}
+
+/// Miller-Rabin, deterministic below 3.3 * 10^24 and a probable prime test above
+fn is_prime(n: &BigInt, deadline: Deadline) -> Result<bool, ExprError> {
+    const BASES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+    if n < &BigInt::from(2) {
+        return Ok(false);
+    }
+
+    for &p in BASES.iter() {
+        if n == &BigInt::from(p) {
+            return Ok(true);
+        }
+        if (n % p).is_zero() {
+            return Ok(false);
+        }
+    }
+
+    let n_1 = n - 1u32;
+    let s = n_1.trailing_zeros().unwrap_or(0);
+    let d = &n_1 >> s;
+
+    'witness: for &a in BASES.iter() {
+        deadline.check()?;
+        let mut x = limits::modpow(&BigInt::from(a), &d, n, deadline)?;
+
+        if x.is_one() || x == n_1 {
+            continue;
+        }
+
+        for _ in 1..s {
+            deadline.check()?;
+            x = &x * &x % n;
+
+            if x == n_1 {
+                continue 'witness;
+            }
+        }
+
+        return Ok(false);
+    }
+
+    Ok(true)
+}
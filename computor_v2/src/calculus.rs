@@ -0,0 +1,206 @@
+use crate::ExprError;
+
+/// Gauss-Kronrod 15 point nodes on [0, 1], the 7 point Gauss rule uses every other one
+const XGK: [f64; 8] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+    0.0,
+];
+const WGK: [f64; 8] = [
+    0.022_935_322_010_529_22,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_2,
+    0.140_653_259_715_525_9,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_8,
+];
+const WG: [f64; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+
+const MAX_INTERVALS: usize = 1000;
+
+type Function<'a> = dyn FnMut(f64) -> Result<f64, ExprError> + 'a;
+
+/// Adaptive Gauss-Kronrod, splits the interval with the worst error until the
+/// sum of error estimates is small enough. Returns the value and the estimate
+pub fn integrate(f: &mut Function, a: f64, b: f64) -> Result<(f64, f64), ExprError> {
+    if a == b {
+        return Ok((0.0, 0.0));
+    }
+
+    let mut intervals = vec![(a, b, kronrod(f, a, b)?)];
+
+    loop {
+        let value: f64 = intervals.iter().map(|x| (x.2).0).sum();
+        let error: f64 = intervals.iter().map(|x| (x.2).1).sum();
+
+        if error <= f64::max(1e-12, 1e-10 * value.abs()) {
+            return Ok((value, error));
+        }
+
+        if intervals.len() >= MAX_INTERVALS || !error.is_finite() {
+            return Err(no_convergence("integrate"));
+        }
+
+        let worst = (0..intervals.len())
+            .max_by(|&i, &j| {
+                let (x, y) = ((intervals[i].2).1, (intervals[j].2).1);
+                x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(0);
+        let (from, to, _) = intervals.swap_remove(worst);
+        let middle = (from + to) / 2.0;
+
+        intervals.push((from, middle, kronrod(f, from, middle)?));
+        intervals.push((middle, to, kronrod(f, middle, to)?));
+    }
+}
+
+/// 15 point rule, the difference with the embedded 7 point one is the error estimate
+fn kronrod(f: &mut Function, a: f64, b: f64) -> Result<(f64, f64), ExprError> {
+    let center = (a + b) / 2.0;
+    let half = (b - a) / 2.0;
+    let fc = f(center)?;
+    let mut kronrod = fc * WGK[7];
+    let mut gauss = fc * WG[3];
+
+    for j in 0..7 {
+        let x = half * XGK[j];
+        let sum = f(center - x)? + f(center + x)?;
+
+        kronrod += WGK[j] * sum;
+        if j % 2 == 1 {
+            gauss += WG[j / 2] * sum;
+        }
+    }
+
+    Ok((kronrod * half, ((kronrod - gauss) * half).abs()))
+}
+
+/// Ridders' method: central differences with shrinking steps, extrapolated to a zero step
+pub fn derivative(f: &mut Function, x: f64) -> Result<(f64, f64), ExprError> {
+    const SHRINK: f64 = 1.4;
+    const STEPS: usize = 10;
+
+    let mut h = 0.1 * x.abs().max(1.0);
+    let mut table = vec![vec![0.0; STEPS]; STEPS];
+    let mut best = (0.0, std::f64::INFINITY);
+    let mut scale = 0.0f64;
+    let mut difference = |f: &mut Function, h: f64| -> Result<f64, ExprError> {
+        let (right, left) = (f(x + h)?, f(x - h)?);
+        scale = scale.max(right.abs()).max(left.abs());
+
+        Ok((right - left) / (2.0 * h))
+    };
+
+    table[0][0] = difference(f, h)?;
+
+    for i in 1..STEPS {
+        h /= SHRINK;
+        table[0][i] = difference(f, h)?;
+
+        if extrapolate(&mut table, i, SHRINK * SHRINK, &mut best) {
+            break;
+        }
+    }
+
+    // Differences can't be more precise than the rounding of the values at the smallest step
+    let rounding = 4.0 * std::f64::EPSILON * scale / h;
+
+    accept("nderiv", (best.0, best.1.max(rounding)))
+}
+
+/// Values at `x0 + h`, `x0 + h/2`, ... from both sides, extrapolated to a zero step
+pub fn limit(f: &mut Function, x0: f64) -> Result<(f64, f64), ExprError> {
+    let left = side(f, x0, -1.0);
+    let right = side(f, x0, 1.0);
+
+    match (left, right) {
+        (Some(left), Some(right)) => {
+            let (left, right) = (accept("limit", left)?, accept("limit", right)?);
+            let gap = (left.0 - right.0).abs();
+
+            if gap > 1e-6 * left.0.abs().max(1.0) {
+                return Err(ExprError::CalcError {
+                    err: "limit is different from the left and from the right".into(),
+                });
+            }
+
+            Ok(((left.0 + right.0) / 2.0, left.1.max(right.1).max(gap)))
+        }
+        (Some(side), None) | (None, Some(side)) => accept("limit", side),
+        (None, None) => Err(no_convergence("limit")),
+    }
+}
+
+/// `None` when `f` can't be evaluated on this side
+fn side(f: &mut Function, x0: f64, direction: f64) -> Option<(f64, f64)> {
+    const STEPS: usize = 16;
+
+    let mut h = 0.1 * x0.abs().max(1.0);
+    let mut table = vec![vec![0.0; STEPS]; STEPS];
+    let mut best = (0.0, std::f64::INFINITY);
+
+    table[0][0] = f(x0 + direction * h).ok()?;
+
+    for i in 1..STEPS {
+        h /= 2.0;
+        table[0][i] = f(x0 + direction * h).ok()?;
+
+        if extrapolate(&mut table, i, 2.0, &mut best) {
+            break;
+        }
+    }
+
+    let rounding = 4.0 * std::f64::EPSILON * best.0.abs();
+
+    Some((best.0, best.1.max(rounding)))
+}
+
+/// Fills column `i` of a Neville table whose step shrinks by `sqrt(ratio)` (or
+/// `ratio` for one-sided values), keeping the entry with the smallest error in
+/// `best`. True once the error starts to grow again
+fn extrapolate(table: &mut [Vec<f64>], i: usize, ratio: f64, best: &mut (f64, f64)) -> bool {
+    let mut factor = ratio;
+
+    for j in 1..=i {
+        table[j][i] = (table[j - 1][i] * factor - table[j - 1][i - 1]) / (factor - 1.0);
+        factor *= ratio;
+
+        let error = f64::max(
+            (table[j][i] - table[j - 1][i]).abs(),
+            (table[j][i] - table[j - 1][i - 1]).abs(),
+        );
+
+        if error <= best.1 {
+            *best = (table[j][i], error);
+        }
+    }
+
+    (table[i][i] - table[i - 1][i - 1]).abs() >= 2.0 * best.1
+}
+
+fn accept(func: &str, (value, error): (f64, f64)) -> Result<(f64, f64), ExprError> {
+    if value.is_finite() && error <= 1e-6 * value.abs().max(1.0) {
+        Ok((value, error))
+    } else {
+        Err(no_convergence(func))
+    }
+}
+
+fn no_convergence(func: &str) -> ExprError {
+    ExprError::NoConvergence {
+        func: func.to_string(),
+    }
+}
@@ -0,0 +1,525 @@
+use crate::{builtins, matrix, native};
+use crate::{Context, Expr, ExprError, NativeFunction, Slice};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Instruction of the stack machine, taking its operands off the top of the
+/// stack and pushing its result. `level` is how deep in the function body its
+/// node is, the body itself being 1
+#[derive(Debug)]
+enum Op {
+    /// Literal or variable known when compiling
+    Const(Expr),
+    /// Argument of the running function
+    Arg(usize),
+    /// Variable that may be an argument of one of the calling functions, calls
+    /// see the arguments of their callers like with the tree walker. `unit` if
+    /// the name may also be a unit
+    Lookup { name: String, unit: bool },
+    Neg,
+    Factorial,
+    Add,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    MatrixMul,
+    Solve,
+    /// `x[...]`, taking the positions each axis was given
+    Index(Vec<Axis>),
+    /// User function of the program
+    Call { func: usize, args: usize, level: usize },
+    Native { func: Arc<dyn NativeFunction>, args: usize },
+    Builtin { name: String, args: usize, level: usize },
+    /// Left to the tree walker, for assignments, conversions and calls by
+    /// argument name
+    Walk { expr: Expr, level: usize },
+}
+
+/// Axis of `x[...]`, a `Span` says which of its ends were given
+#[derive(Debug)]
+enum Axis {
+    At,
+    Span(bool, bool),
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    args: Vec<String>,
+    code: Vec<Op>,
+    /// Deepest node of the body
+    depth: usize,
+}
+
+/// Running function, its arguments start at `base` of the argument stack
+#[derive(Clone, Copy)]
+struct Frame {
+    func: usize,
+    pc: usize,
+    base: usize,
+    /// Level of the call
+    level: usize,
+}
+
+/// A user function compiled with the ones it calls, with variables resolved
+/// to argument slots or constants. Made once for a `sum` or an `integrate`,
+/// it is called without cloning the context or the body each time
+#[derive(Debug)]
+pub(crate) struct Program {
+    funcs: Vec<Function>,
+    context: Context,
+}
+
+impl Program {
+    /// Compiles the `Lambda` `f`, called `name` in errors
+    pub(crate) fn new(f: &Expr, name: &str, context: &Context) -> Result<Program, ExprError> {
+        let (args, body) = match f {
+            Expr::Lambda(args, body) => (args, body),
+            _ => {
+                return Err(ExprError::CalcError {
+                    err: format!("{} is not a function", name),
+                })
+            }
+        };
+
+        let mut dynamic = args.iter().cloned().collect();
+        gather(context, body, &mut dynamic, &mut HashSet::new());
+
+        // Its own cache, the one of `context` may end up holding the program
+        let mut context = Context {
+            programs: Arc::default(),
+            ..context.clone()
+        };
+        let mut compiler = Compiler {
+            context: &mut context,
+            dynamic,
+            indices: HashMap::new(),
+            funcs: vec![],
+        };
+        compiler.function(name, args, body);
+
+        Ok(Program {
+            funcs: compiler.funcs,
+            context,
+        })
+    }
+
+    /// Runs the function on `values` from a call at `level`
+    pub(crate) fn call(&mut self, values: Vec<Expr>, level: usize) -> Result<Expr, ExprError> {
+        let Program { funcs, context } = self;
+
+        if values.len() != funcs[0].args.len() {
+            return Err(ExprError::BadArgsCount {
+                func: funcs[0].name.clone(),
+            });
+        }
+        if level + funcs[0].depth > 100 {
+            return Err(ExprError::RecursiveRecursion);
+        }
+
+        let mut frames = vec![Frame {
+            func: 0,
+            pc: 0,
+            base: 0,
+            level,
+        }];
+        let mut locals = values;
+        let mut stack = vec![];
+
+        while let Some(&frame) = frames.last() {
+            let op = match funcs[frame.func].code.get(frame.pc) {
+                Some(op) => op,
+                None => {
+                    locals.truncate(frame.base);
+                    frames.pop();
+                    continue;
+                }
+            };
+            let top = frames.len() - 1;
+            frames[top].pc += 1;
+            context.step()?;
+
+            let res = match op {
+                Op::Const(x) => x.clone(),
+                Op::Arg(i) => locals[frame.base + i].clone(),
+                Op::Lookup { name, unit } => {
+                    let arg = frames.iter().rev().find_map(|frame| {
+                        let i = funcs[frame.func].args.iter().position(|arg| arg == name)?;
+
+                        Some(locals[frame.base + i].clone())
+                    });
+
+                    match arg {
+                        Some(x) => x,
+                        None if *unit => context.lookup_unit(name)?,
+                        None => context.lookup(name)?,
+                    }
+                }
+                Op::Neg => pop(&mut stack)?.neg(context)?,
+                Op::Factorial => pop(&mut stack)?.factorial(context)?,
+                Op::Add => {
+                    let y = pop(&mut stack)?;
+                    pop(&mut stack)?.add(y, context)?
+                }
+                Op::Mul => {
+                    let y = pop(&mut stack)?;
+                    pop(&mut stack)?.mul(y, context)?
+                }
+                Op::Div => {
+                    let y = pop(&mut stack)?;
+                    pop(&mut stack)?.div(y, context)?
+                }
+                Op::Rem => {
+                    let y = pop(&mut stack)?;
+                    pop(&mut stack)?.rem(y, context)?
+                }
+                Op::Pow => {
+                    let y = pop(&mut stack)?;
+                    pop(&mut stack)?.pow(y, context)?
+                }
+                Op::MatrixMul => {
+                    let y = pop(&mut stack)?;
+                    pop(&mut stack)?.mmul(y, context)?
+                }
+                Op::Solve => {
+                    let y = pop(&mut stack)?;
+                    pop(&mut stack)?.solve(y, context)?
+                }
+                Op::Index(axes) => {
+                    let count = axes.iter().map(|axis| match axis {
+                        Axis::At => 1,
+                        Axis::Span(from, to) => *from as usize + *to as usize,
+                    });
+                    let mut values = take(&mut stack, count.sum())?.into_iter();
+                    let x = pop(&mut stack)?;
+                    let mut res = vec![];
+
+                    for axis in axes {
+                        res.push(match *axis {
+                            Axis::At => matrix::Axis::At(position(&mut values)?),
+                            Axis::Span(from, to) => {
+                                let from = if from { Some(position(&mut values)?) } else { None };
+                                let to = if to { Some(position(&mut values)?) } else { None };
+
+                                matrix::Axis::Span(from, to)
+                            }
+                        });
+                    }
+
+                    matrix::index(x, res)?
+                }
+                Op::Call { func, args, level } => {
+                    let callee = &funcs[*func];
+
+                    if *args != callee.args.len() {
+                        return Err(ExprError::BadArgsCount {
+                            func: callee.name.clone(),
+                        });
+                    }
+
+                    let level = frame.level + level;
+
+                    if level + callee.depth > 100 {
+                        return Err(ExprError::RecursiveRecursion);
+                    }
+
+                    let values = take(&mut stack, *args)?;
+                    let base = locals.len();
+                    locals.extend(values);
+                    frames.push(Frame {
+                        func: *func,
+                        pc: 0,
+                        base,
+                        level,
+                    });
+
+                    continue;
+                }
+                Op::Native { func, args } => native::call(&**func, &take(&mut stack, *args)?)?,
+                Op::Builtin { name, args, level } => {
+                    let level = frame.level + level;
+                    let values = take(&mut stack, *args)?;
+
+                    // User functions given to builtins see the arguments too
+                    if values.iter().any(|x| matches!(x, Expr::Lambda(..))) {
+                        let mut scope = scope(funcs, context, &frames, &locals);
+                        builtins::call(name, values, &mut scope, level)?
+                    } else {
+                        builtins::call(name, values, context, level)?
+                    }
+                }
+                Op::Walk { expr, level } => {
+                    let level = frame.level + level - 1;
+                    let mut scope = scope(funcs, context, &frames, &locals);
+
+                    expr.clone().run(&mut scope, level)?
+                }
+            };
+
+            context.limits.check(&res)?;
+            stack.push(res);
+        }
+
+        pop(&mut stack)
+    }
+}
+
+/// The context the tree walker would have: arguments of every running
+/// function over the variables, the latest calls winning
+fn scope(funcs: &[Function], context: &Context, frames: &[Frame], locals: &[Expr]) -> Context {
+    let mut scope = context.clone();
+
+    for frame in frames {
+        for (i, name) in funcs[frame.func].args.iter().enumerate() {
+            scope.insert(name.clone(), locals[frame.base + i].clone());
+        }
+    }
+
+    scope
+}
+
+fn pop(stack: &mut Vec<Expr>) -> Result<Expr, ExprError> {
+    stack.pop().ok_or_else(underflow)
+}
+
+/// The top `n` values, in the order they were pushed
+fn take(stack: &mut Vec<Expr>, n: usize) -> Result<Vec<Expr>, ExprError> {
+    match stack.len().checked_sub(n) {
+        Some(at) => Ok(stack.split_off(at)),
+        None => Err(underflow()),
+    }
+}
+
+/// Next of the positions given to `x[...]`
+fn position(values: &mut impl Iterator<Item = Expr>) -> Result<i64, ExprError> {
+    matrix::position(&values.next().ok_or_else(underflow)?)
+}
+
+fn underflow() -> ExprError {
+    ExprError::CalcError {
+        err: "compiled function ran out of values".into(),
+    }
+}
+
+/// Adds the argument names of the functions `e` may call to `dynamic`
+fn gather(
+    context: &Context,
+    e: &Expr,
+    dynamic: &mut HashSet<String>,
+    seen: &mut HashSet<String>,
+) {
+    match e {
+        Expr::Call(name, args) => {
+            if seen.insert(name.clone()) {
+                if let Some(Expr::Lambda(names, body)) = context.get(name) {
+                    dynamic.extend(names.iter().cloned());
+                    gather(context, body, dynamic, seen);
+                }
+            }
+            args.iter().for_each(|x| gather(context, x, dynamic, seen));
+        }
+        Expr::Index(x, slices) => {
+            gather(context, x, dynamic, seen);
+
+            for slice in slices {
+                match slice {
+                    Slice::At(x) => gather(context, x, dynamic, seen),
+                    Slice::Range(from, to) => {
+                        from.iter().chain(to).for_each(|x| gather(context, x, dynamic, seen))
+                    }
+                }
+            }
+        }
+        Expr::Neg(x) | Expr::Factorial(x) | Expr::AssignVar(_, x) => {
+            gather(context, x, dynamic, seen)
+        }
+        Expr::Add(x, y)
+        | Expr::Mul(x, y)
+        | Expr::Div(x, y)
+        | Expr::Rem(x, y)
+        | Expr::Pow(x, y)
+        | Expr::MatrixMul(x, y)
+        | Expr::Solve(x, y)
+        | Expr::Convert(x, y)
+        | Expr::AssignFunc(x, y) => {
+            gather(context, x, dynamic, seen);
+            gather(context, y, dynamic, seen);
+        }
+        _ => {}
+    }
+}
+
+struct Compiler<'a> {
+    context: &'a mut Context,
+    /// Arguments of every function that may be called, a variable with one
+    /// of these names is only known at run time
+    dynamic: HashSet<String>,
+    /// Functions of the context compiled so far
+    indices: HashMap<String, usize>,
+    funcs: Vec<Function>,
+}
+
+impl<'a> Compiler<'a> {
+    /// Compiles a function, gives its index
+    fn function(&mut self, name: &str, args: &[String], body: &Expr) -> usize {
+        let func = self.funcs.len();
+        self.funcs.push(Function {
+            name: name.to_string(),
+            args: args.to_vec(),
+            code: vec![],
+            depth: 0,
+        });
+
+        let mut code = vec![];
+        let depth = if self.context.walk {
+            code.push(Op::Walk {
+                expr: body.clone(),
+                level: 1,
+            });
+            1
+        } else {
+            self.expr(body, args, 1, &mut code)
+        };
+        self.funcs[func].code = code;
+        self.funcs[func].depth = depth;
+
+        func
+    }
+
+    /// Compiles `e` at `level` of a body taking `args`, gives its depth
+    fn expr(&mut self, e: &Expr, args: &[String], level: usize, code: &mut Vec<Op>) -> usize {
+        let mut depth = level;
+        let mut sub = |this: &mut Self, x: &Expr, code: &mut Vec<Op>| {
+            depth = depth.max(this.expr(x, args, level + 1, code));
+        };
+
+        match e {
+            Expr::Var(name) | Expr::Unit(name) => {
+                let unit = matches!(e, Expr::Unit(_));
+                let lookup = || Op::Lookup {
+                    name: name.clone(),
+                    unit,
+                };
+                let value = if unit {
+                    self.context.lookup_unit(name)
+                } else {
+                    self.context.lookup(name)
+                };
+
+                code.push(match args.iter().position(|arg| arg == name) {
+                    Some(i) => Op::Arg(i),
+                    None if self.dynamic.contains(name) => lookup(),
+                    None => value.map(Op::Const).unwrap_or_else(|_| lookup()),
+                })
+            }
+            Expr::Neg(x) | Expr::Factorial(x) => {
+                sub(self, x, code);
+                code.push(match e {
+                    Expr::Neg(_) => Op::Neg,
+                    _ => Op::Factorial,
+                });
+            }
+            Expr::Add(x, y)
+            | Expr::Mul(x, y)
+            | Expr::Div(x, y)
+            | Expr::Rem(x, y)
+            | Expr::Pow(x, y)
+            | Expr::MatrixMul(x, y)
+            | Expr::Solve(x, y) => {
+                sub(self, x, code);
+                sub(self, y, code);
+                code.push(match e {
+                    Expr::Add(..) => Op::Add,
+                    Expr::Mul(..) => Op::Mul,
+                    Expr::Div(..) => Op::Div,
+                    Expr::Rem(..) => Op::Rem,
+                    Expr::Pow(..) => Op::Pow,
+                    Expr::MatrixMul(..) => Op::MatrixMul,
+                    _ => Op::Solve,
+                });
+            }
+            Expr::Index(x, slices) => {
+                sub(self, x, code);
+
+                let mut axes = vec![];
+
+                for slice in slices {
+                    match slice {
+                        Slice::At(x) => {
+                            sub(self, x, code);
+                            axes.push(Axis::At);
+                        }
+                        Slice::Range(from, to) => {
+                            for x in from.iter().chain(to) {
+                                sub(self, x, code);
+                            }
+                            axes.push(Axis::Span(from.is_some(), to.is_some()));
+                        }
+                    }
+                }
+
+                code.push(Op::Index(axes));
+            }
+            Expr::Call(name, values) if self.is_static(name) => {
+                for x in values {
+                    sub(self, x, code);
+                }
+
+                let args = values.len();
+                let lambda = match self.context.get(name) {
+                    Some(Expr::Lambda(names, body)) => Some((names.clone(), body.clone())),
+                    _ => None,
+                };
+
+                code.push(match (lambda, self.context.native(name)) {
+                    (Some((names, body)), _) => Op::Call {
+                        func: match self.indices.get(name) {
+                            Some(&func) => func,
+                            None => {
+                                self.indices.insert(name.clone(), self.funcs.len());
+                                self.function(name, &names, &body)
+                            }
+                        },
+                        args,
+                        level,
+                    },
+                    (_, Some(func)) => Op::Native {
+                        func: func.clone(),
+                        args,
+                    },
+                    _ => Op::Builtin {
+                        name: name.clone(),
+                        args,
+                        level,
+                    },
+                });
+            }
+            Expr::Call(..) | Expr::Convert(..) | Expr::AssignVar(..) | Expr::AssignFunc(..) => {
+                code.push(Op::Walk {
+                    expr: e.clone(),
+                    level,
+                })
+            }
+            _ => code.push(match e.clone().node(&mut *self.context, level) {
+                Ok(x) if self.context.limits.check(&x).is_ok() => Op::Const(x),
+                _ => Op::Walk {
+                    expr: e.clone(),
+                    level,
+                },
+            }),
+        }
+
+        depth
+    }
+
+    /// Whether the call to `name` can be resolved now, not being to an
+    /// argument or a builtin that takes its arguments unevaluated
+    fn is_static(&self, name: &str) -> bool {
+        !self.dynamic.contains(name)
+            && !(builtins::SPECIAL.contains(&name)
+                && self.context.get(name).is_none()
+                && self.context.native(name).is_none())
+    }
+}
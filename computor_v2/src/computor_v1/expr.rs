@@ -25,7 +25,7 @@ impl Expr {
             Mul(mut x, mut vec) => {
                 let mut new = vec![];
 
-                if let Number(a) = vec[0] {
+                if let Some(&Number(a)) = vec.first() {
                     x *= a;
                     vec.remove(0);
                 }
@@ -66,7 +66,6 @@ impl Expr {
             Neg(box Variable(_)) => self,
             Neg(box Neg(x)) => x.simplify(),
             Neg(box Add(mut vec)) => Add(vec.drain(..).map(|x| Neg(box x)).collect()).simplify(),
-            Neg(box Equation(..)) => unreachable!(),
             Neg(x) => Neg(box x.simplify()),
 
             Add(vec) => {
@@ -155,13 +154,13 @@ impl Expr {
         }
     }
 
+    /// `l = r` as `l - r = 0`, anything else as `self = 0`
     pub fn move_to_left(self) -> Expr {
         use Expr::*;
 
-        if let Equation(l, r) = self {
-            Equation(box Add(vec![*l, Neg(r)]), box Number(0.0))
-        } else {
-            panic!("Attempt to move_to_left() on non-equation");
+        match self {
+            Equation(l, r) => Equation(box Add(vec![*l, Neg(r)]), box Number(0.0)),
+            _ => Equation(box self, box Number(0.0)),
         }
     }
 }
@@ -181,6 +180,7 @@ impl ToString for Expr {
             Neg(x @ box Pow(_, _)) => "-".to_string() + &x.to_string(),
             Neg(x) => "-".to_string() + "(" + &x.to_string() + ")",
 
+            Add(vec) if vec.is_empty() => "0".to_string(),
             Add(vec) => {
                 let mut res = vec[0].to_string();
 
@@ -198,6 +198,7 @@ impl ToString for Expr {
                 res
             }
 
+            Mul(x, vec) if vec.is_empty() => x.to_string(),
             Mul(x, vec) => {
                 let mut res = "".to_string();
 
@@ -3,17 +3,13 @@ pub mod parser {
 }
 
 mod expr;
+mod poly;
 
 use expr::Expr;
-
-#[derive(Debug)]
-pub struct Poly {
-    pub list: Vec<(u32, f64)>,
-}
+pub use poly::{Factors, Poly};
 
 impl Poly {
     pub fn from_expr(expr: &Expr) -> Result<Poly, ()> {
-        use std::collections::HashMap;
         use Expr::*;
 
         if let Equation(box Add(vec), box Number(_)) = expr {
@@ -27,59 +23,52 @@ impl Poly {
                     Neg(box Mul(x, mul)) => match mul.as_slice() {
                         [Variable(_)] => list.push((1, -(*x))),
                         [Pow(box Expr::Variable(_), box Number(n))] => {
-                            list.push((*n as u32, -(*x)))
+                            list.push((power(*n)?, -(*x)))
                         }
                         _ => return Err(()),
                     },
                     Mul(x, mul) => match mul.as_slice() {
                         [Variable(_)] => list.push((1, *x)),
-                        [Pow(box Expr::Variable(_), box Number(n))] => list.push((*n as u32, *x)),
+                        [Pow(box Expr::Variable(_), box Number(n))] => list.push((power(*n)?, *x)),
                         _ => return Err(()),
                     },
-                    Pow(box Variable(_), box Number(n)) => list.push((*n as u32, 1.0)),
-                    Pow(box Neg(box Variable(_)), box Number(n)) => list.push((*n as u32, -1.0)),
-                    Neg(box Pow(box Variable(_), box Number(n))) => list.push((*n as u32, -1.0)),
+                    Pow(box Variable(_), box Number(n)) => list.push((power(*n)?, 1.0)),
+                    Pow(box Neg(box Variable(_)), box Number(n)) => list.push((power(*n)?, -1.0)),
+                    Neg(box Pow(box Variable(_), box Number(n))) => list.push((power(*n)?, -1.0)),
                     _ => return Err(()),
                 }
             }
 
-            let mut map: HashMap<u32, f64> = HashMap::new();
-            for (a, b) in list {
-                if map.contains_key(&a) {
-                    *map.get_mut(&a).unwrap() += b;
-                } else {
-                    map.insert(a, b);
-                }
-            }
-            let mut list: Vec<(u32, f64)> = map.drain().collect();
-            list.sort_by(|(a, _), (b, _)| b.cmp(a));
-
-            Ok(Poly { list })
+            Ok(Poly::from_terms(&list))
         } else if let Equation(box Mul(n, vec), box Number(_)) = expr {
             match vec.as_slice() {
-                [Variable(_)] => Ok(Poly {
-                    list: vec![(1, *n)],
-                }),
-                [Pow(box Variable(_), box Number(x))] => Ok(Poly {
-                    list: vec![(*x as u32, *n)],
-                }),
+                [Variable(_)] => Ok(Poly::from_terms(&[(1, *n)])),
+                [Pow(box Variable(_), box Number(x))] => Ok(Poly::from_terms(&[(power(*x)?, *n)])),
 
                 _ => Err(()),
             }
         } else if let Equation(box Variable(_), box Number(_)) = expr {
-            Ok(Poly {
-                list: vec![(1, 0.0)],
-            })
+            Ok(Poly::x())
         } else if let Equation(box Neg(box Variable(_)), box Number(_)) = expr {
-            Ok(Poly {
-                list: vec![(1, 0.0)],
-            })
+            Ok(Poly::x())
         } else {
             Err(())
         }
     }
 }
 
+/// Highest power of `x` a polynomial equation may have
+const MAX_DEGREE: f64 = 1000.0;
+
+/// `n` as the power of a polynomial term, if it is a whole number small enough
+fn power(n: f64) -> Result<u32, ()> {
+    if (0.0..=MAX_DEGREE).contains(&n) && n.fract() == 0.0 {
+        Ok(n as u32)
+    } else {
+        Err(())
+    }
+}
+
 fn solve_quad(a: f64, b: f64, c: f64) {
     let discriminant = b * b - 4. * a * c;
     println!("Discriminant is {}", discriminant);
@@ -121,7 +110,8 @@ pub fn computor_v1(equation: String) {
             expr = expr.flatten();
             expr = expr.simplify();
             println!("==> {}", expr.to_string());
-            if let Ok(Poly { list }) = Poly::from_expr(&expr) {
+            if let Ok(poly) = Poly::from_expr(&expr) {
+                let list = poly.terms();
                 println!("Polynomial: {:?}", list);
                 match list.as_slice() {
                     [(1, _)] => println!("x = 0"),
@@ -0,0 +1,484 @@
+use crate::{Deadline, ExprError};
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Polynomial in one variable with exact coefficients, lowest power first
+#[derive(Clone, Debug, PartialEq)]
+pub struct Poly {
+    coefs: Vec<BigRational>,
+}
+
+/// `constant * exact factors * (x - root) factors`
+#[derive(Debug)]
+pub struct Factors {
+    pub constant: BigRational,
+    /// Linear factors of the rational roots, primitive with integer coefficients
+    pub exact: Vec<(Poly, u32)>,
+    /// `(re, im)` roots of what is left after the exact factors, irrational or
+    /// complex, even when they pair up into a quadratic with rational coefficients
+    pub numeric: Vec<((f64, f64), u32)>,
+}
+
+/// Trial division stops here, bigger numbers skip the search for rational roots
+const MAX_DIVISOR_SEARCH: u64 = 1_000_000;
+
+impl Poly {
+    pub fn new(mut coefs: Vec<BigRational>) -> Poly {
+        while coefs.last().map_or(false, |c| c.is_zero()) {
+            coefs.pop();
+        }
+
+        Poly { coefs }
+    }
+
+    pub fn zero() -> Poly {
+        Poly { coefs: vec![] }
+    }
+
+    pub fn constant(c: BigRational) -> Poly {
+        Poly::new(vec![c])
+    }
+
+    /// The polynomial `x`
+    pub fn x() -> Poly {
+        Poly::new(vec![BigRational::zero(), BigRational::one()])
+    }
+
+    /// From `(power, coefficient)` pairs, same powers add up
+    pub fn from_terms(terms: &[(u32, f64)]) -> Poly {
+        let mut coefs = vec![];
+
+        for &(n, c) in terms {
+            let n = n as usize;
+
+            if coefs.len() <= n {
+                coefs.resize(n + 1, BigRational::zero());
+            }
+            coefs[n] += BigRational::from_float(c).unwrap_or_else(BigRational::zero);
+        }
+
+        Poly::new(coefs)
+    }
+
+    /// Non-zero `(power, coefficient)` pairs, highest power first
+    pub fn terms(&self) -> Vec<(u32, f64)> {
+        self.coefs
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, c)| !c.is_zero())
+            .map(|(n, c)| (n as u32, crate::to_f64(c)))
+            .collect()
+    }
+
+    pub fn coefs(&self) -> &[BigRational] {
+        &self.coefs
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefs.is_empty()
+    }
+
+    /// Degree, 0 for the zero polynomial too
+    pub fn degree(&self) -> usize {
+        self.coefs.len().saturating_sub(1)
+    }
+
+    pub fn leading(&self) -> BigRational {
+        self.coefs.last().cloned().unwrap_or_else(BigRational::zero)
+    }
+
+    pub fn neg(&self) -> Poly {
+        Poly::new(self.coefs.iter().map(|c| -c).collect())
+    }
+
+    pub fn add(&self, other: &Poly) -> Poly {
+        let zero = BigRational::zero();
+        let len = self.coefs.len().max(other.coefs.len());
+
+        Poly::new(
+            (0..len)
+                .map(|i| self.coefs.get(i).unwrap_or(&zero) + other.coefs.get(i).unwrap_or(&zero))
+                .collect(),
+        )
+    }
+
+    pub fn sub(&self, other: &Poly) -> Poly {
+        self.add(&other.neg())
+    }
+
+    pub fn scale(&self, c: &BigRational) -> Poly {
+        Poly::new(self.coefs.iter().map(|x| x * c).collect())
+    }
+
+    pub fn mul(&self, other: &Poly) -> Poly {
+        if self.is_zero() || other.is_zero() {
+            return Poly::zero();
+        }
+
+        let mut coefs = vec![BigRational::zero(); self.coefs.len() + other.coefs.len() - 1];
+
+        for (i, a) in self.coefs.iter().enumerate() {
+            for (j, b) in other.coefs.iter().enumerate() {
+                coefs[i + j] += a * b;
+            }
+        }
+
+        Poly::new(coefs)
+    }
+
+    pub fn pow(&self, n: u32) -> Poly {
+        (0..n).fold(Poly::constant(BigRational::one()), |acc, _| acc.mul(self))
+    }
+
+    /// Long division, `None` when dividing by zero
+    pub fn div_rem(&self, divisor: &Poly) -> Option<(Poly, Poly)> {
+        if divisor.is_zero() {
+            return None;
+        }
+
+        let n = divisor.degree();
+        let lead = divisor.leading();
+        let mut rem = self.coefs.clone();
+        let mut quot = vec![BigRational::zero(); self.coefs.len().saturating_sub(n)];
+
+        for i in (0..quot.len()).rev() {
+            let q = &rem[i + n] / &lead;
+
+            for (j, d) in divisor.coefs.iter().enumerate() {
+                rem[i + j] -= &q * d;
+            }
+            quot[i] = q;
+        }
+
+        Some((Poly::new(quot), Poly::new(rem)))
+    }
+
+    /// Monic greatest common divisor, zero only if both are zero
+    pub fn gcd(&self, other: &Poly) -> Poly {
+        let (mut a, mut b) = (self.clone(), other.clone());
+
+        while !b.is_zero() {
+            let rem = match a.div_rem(&b) {
+                Some((_, rem)) => rem,
+                None => break,
+            };
+
+            a = b;
+            b = rem;
+        }
+
+        a.monic()
+    }
+
+    pub fn monic(&self) -> Poly {
+        match self.coefs.last() {
+            Some(lead) => self.scale(&lead.recip()),
+            None => Poly::zero(),
+        }
+    }
+
+    pub fn eval(&self, x: &BigRational) -> BigRational {
+        self.coefs
+            .iter()
+            .rev()
+            .fold(BigRational::zero(), |acc, c| acc * x + c)
+    }
+
+    /// `self(inner(x))`
+    pub fn compose(&self, inner: &Poly) -> Poly {
+        self.coefs
+            .iter()
+            .rev()
+            .fold(Poly::zero(), |acc, c| acc.mul(inner).add(&Poly::constant(c.clone())))
+    }
+
+    pub fn derivative(&self) -> Poly {
+        Poly::new(
+            self.coefs
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(n, c)| c * BigRational::from_integer(BigInt::from(n)))
+                .collect(),
+        )
+    }
+
+    /// Antiderivative that is zero at zero
+    pub fn integral(&self) -> Poly {
+        let mut coefs = vec![BigRational::zero()];
+
+        coefs.extend(
+            self.coefs
+                .iter()
+                .enumerate()
+                .map(|(n, c)| c / BigRational::from_integer(BigInt::from(n + 1))),
+        );
+
+        Poly::new(coefs)
+    }
+
+    /// Integer coefficients without a common factor, positive leading one,
+    /// and the constant that gives `self` back
+    pub fn primitive(&self) -> (BigRational, Poly) {
+        if self.is_zero() {
+            return (BigRational::zero(), Poly::zero());
+        }
+
+        let denom = self
+            .coefs
+            .iter()
+            .fold(BigInt::one(), |acc, c| acc.lcm(c.denom()));
+        let mut numer = self
+            .coefs
+            .iter()
+            .fold(BigInt::zero(), |acc, c| acc.gcd(&(c * &denom).to_integer()));
+
+        if self.leading().is_negative() {
+            numer = -numer;
+        }
+
+        let content = BigRational::new(numer, denom);
+
+        (content.clone(), self.scale(&content.recip()))
+    }
+
+    /// Rational roots come out as exact linear factors, the rest as numeric roots
+    pub fn factor(&self, deadline: Deadline) -> Result<Factors, ExprError> {
+        let mut exact = vec![];
+        let mut numeric = vec![];
+
+        for (part, n) in self.square_free() {
+            let (_, mut rest) = part.primitive();
+
+            for root in rest.rational_roots(deadline)? {
+                let (_, linear) = Poly::new(vec![-root, BigRational::one()]).primitive();
+
+                rest = quotient(&rest, &linear);
+                exact.push((linear, n));
+            }
+
+            match rest.degree() {
+                0 => {}
+                1 => exact.push((rest, n)),
+                _ => numeric.extend(rest.roots().into_iter().map(|root| (root, n))),
+            }
+        }
+
+        // `x - root` factors are monic, the rest of the leading coefficient is the constant
+        let constant = exact
+            .iter()
+            .fold(self.leading(), |acc, (p, n)| acc / p.leading().pow(*n as i32));
+
+        Ok(Factors {
+            constant,
+            exact,
+            numeric,
+        })
+    }
+
+    /// Yun's algorithm: `self` is the product of the parts to their powers,
+    /// up to a constant, and each part has simple roots
+    fn square_free(&self) -> Vec<(Poly, u32)> {
+        let mut res = vec![];
+
+        if self.degree() == 0 {
+            return res;
+        }
+
+        let a = self.monic();
+        let mut c = a.gcd(&a.derivative());
+        let mut w = quotient(&a, &c);
+        let mut n = 1;
+
+        while w.degree() > 0 {
+            let y = w.gcd(&c);
+            let z = quotient(&w, &y);
+
+            if z.degree() > 0 {
+                res.push((z, n));
+            }
+
+            n += 1;
+            c = quotient(&c, &y);
+            w = y;
+        }
+
+        res
+    }
+
+    /// `p/q` with `p` dividing the constant term and `q` the leading one
+    fn rational_roots(&self, deadline: Deadline) -> Result<Vec<BigRational>, ExprError> {
+        let mut res = vec![];
+
+        if self.degree() == 0 {
+            return Ok(res);
+        }
+
+        if self.coefs[0].is_zero() {
+            res.push(BigRational::zero());
+        }
+
+        let (_, p) = self.primitive();
+        let zeros = p.coefs.iter().take_while(|c| c.is_zero()).count();
+        let last = p.coefs[zeros].to_integer();
+        let first = p.leading().to_integer();
+
+        let (numers, denoms) = match (divisors(&last, deadline)?, divisors(&first, deadline)?) {
+            (Some(numers), Some(denoms)) => (numers, denoms),
+            _ => return Ok(res),
+        };
+
+        for numer in &numers {
+            for denom in &denoms {
+                deadline.check()?;
+
+                for &sign in &[1, -1] {
+                    let x = BigRational::new(numer * sign, denom.clone());
+
+                    if !res.contains(&x) && p.eval(&x).is_zero() {
+                        res.push(x);
+                    }
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Every complex root at once with the Aberth-Ehrlich iteration, real ones
+    /// have a zero imaginary part. Meant for polynomials without multiple roots
+    pub fn roots(&self) -> Vec<(f64, f64)> {
+        let n = self.degree();
+
+        if n == 0 {
+            return vec![];
+        }
+
+        let lead = self.leading();
+        let coefs: Vec<f64> = self.coefs.iter().map(|c| crate::to_f64(&(c / &lead))).collect();
+
+        // Fujiwara's bound, every root is within it
+        let radius = (0..n)
+            .map(|i| {
+                let c = coefs[i].abs() / if i == 0 { 2.0 } else { 1.0 };
+                c.powf(1.0 / (n - i) as f64)
+            })
+            .fold(0.0, f64::max)
+            * 2.0;
+        let radius = if radius > 0.0 { radius } else { 1.0 };
+
+        let mut z: Vec<Complex> = (0..n)
+            .map(|k| {
+                let angle = 2.0 * std::f64::consts::PI * k as f64 / n as f64 + 0.4;
+                (radius * angle.cos(), radius * angle.sin())
+            })
+            .collect();
+
+        for _ in 0..500 {
+            let mut step: f64 = 0.0;
+
+            for k in 0..n {
+                let (p, dp) = horner(&coefs, z[k]);
+
+                if p == (0.0, 0.0) {
+                    continue;
+                }
+
+                let ratio = div(p, dp);
+                let sum = (0..n)
+                    .filter(|&j| j != k)
+                    .fold((0.0, 0.0), |acc, j| add(acc, div((1.0, 0.0), sub(z[k], z[j]))));
+                let w = div(ratio, sub((1.0, 0.0), mul(ratio, sum)));
+
+                if w.0.is_finite() && w.1.is_finite() {
+                    z[k] = sub(z[k], w);
+                    step = step.max(w.0.hypot(w.1) / z[k].0.hypot(z[k].1).max(1.0));
+                }
+            }
+
+            if step < 1e-15 {
+                break;
+            }
+        }
+
+        for root in &mut z {
+            if root.1.abs() <= 1e-10 * root.0.hypot(root.1).max(1.0) {
+                root.1 = 0.0;
+            }
+        }
+
+        z.sort_by(|a, b| {
+            (a.0, a.1)
+                .partial_cmp(&(b.0, b.1))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        z
+    }
+}
+
+/// Exact division, the remainder is known to be zero
+fn quotient(a: &Poly, b: &Poly) -> Poly {
+    a.div_rem(b).map_or_else(Poly::zero, |(quot, _)| quot)
+}
+
+/// Positive divisors of a non-zero number, `None` if it is too big to search
+fn divisors(n: &BigInt, deadline: Deadline) -> Result<Option<Vec<BigInt>>, ExprError> {
+    let n = match n.abs().to_u64() {
+        Some(n) if n > 0 && n <= MAX_DIVISOR_SEARCH * MAX_DIVISOR_SEARCH => n,
+        _ => return Ok(None),
+    };
+
+    let mut small = vec![];
+    let mut large = vec![];
+    let mut i = 1;
+
+    while i * i <= n {
+        // The clock is slower than a division, look at it now and then
+        if i & 0xfff == 0 {
+            deadline.check()?;
+        }
+        if n % i == 0 {
+            small.push(BigInt::from(i));
+            if i * i != n {
+                large.push(BigInt::from(n / i));
+            }
+        }
+        i += 1;
+    }
+
+    small.extend(large.into_iter().rev());
+
+    Ok(Some(small))
+}
+
+type Complex = (f64, f64);
+
+fn add(a: Complex, b: Complex) -> Complex {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: Complex, b: Complex) -> Complex {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn mul(a: Complex, b: Complex) -> Complex {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+fn div(a: Complex, b: Complex) -> Complex {
+    let d = b.0 * b.0 + b.1 * b.1;
+
+    ((a.0 * b.0 + a.1 * b.1) / d, (a.1 * b.0 - a.0 * b.1) / d)
+}
+
+/// Value and derivative at `z`
+fn horner(coefs: &[f64], z: Complex) -> (Complex, Complex) {
+    coefs.iter().rev().fold(((0.0, 0.0), (0.0, 0.0)), |(p, dp), &c| {
+        (add(mul(p, z), (c, 0.0)), add(mul(dp, z), p))
+    })
+}
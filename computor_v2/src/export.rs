@@ -0,0 +1,571 @@
+use crate::plot::{self, Kind, Plot};
+use crate::ExprError;
+
+use std::fmt::Write;
+use std::fs;
+
+const COLORS: [(u8, u8, u8); 6] = [
+    (31, 119, 180),
+    (214, 39, 40),
+    (44, 160, 44),
+    (255, 127, 14),
+    (148, 103, 189),
+    (140, 86, 75),
+];
+const GRID: (u8, u8, u8) = (221, 221, 221);
+const AXES: (u8, u8, u8) = (68, 68, 68);
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+
+/// Writes an SVG or a PNG picture, whichever the extension of `path` asks for
+pub fn save(plot: &Plot, path: &str) -> Result<(), ExprError> {
+    let data = match path.to_lowercase() {
+        ref x if x.ends_with(".svg") => svg(plot).into_bytes(),
+        ref x if x.ends_with(".png") => png(plot),
+        _ => {
+            return Err(ExprError::CalcError {
+                err: "plots are written to .svg or .png files".into(),
+            })
+        }
+    };
+
+    fs::write(path, data).map_err(|err| ExprError::WriteError {
+        path: path.to_string(),
+        err: err.to_string(),
+    })
+}
+
+/// Picture size, polar plots are square
+fn size(plot: &Plot) -> (f64, f64) {
+    match plot.kind {
+        Kind::Polar => (600.0, 600.0),
+        _ => (800.0, 500.0),
+    }
+}
+
+/// Where the chart goes in the picture and which values it shows
+struct Frame {
+    x: (f64, f64),
+    y: (f64, f64),
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Frame {
+    fn new(plot: &Plot) -> Frame {
+        let (width, height) = size(plot);
+        let (left, top) = (70.0, 20.0);
+        let (width, height) = (width - left - 20.0, height - top - 50.0);
+        let (mut x, mut y) = (plot.x, plot::y_range(plot));
+
+        // Curves traced in the plane keep their shape: same scale on both axes
+        if plot.kind != Kind::Function {
+            let scale = f64::max((x.1 - x.0) / width, (y.1 - y.0) / height);
+            let (cx, cy) = ((x.0 + x.1) / 2.0, (y.0 + y.1) / 2.0);
+
+            x = (cx - scale * width / 2.0, cx + scale * width / 2.0);
+            y = (cy - scale * height / 2.0, cy + scale * height / 2.0);
+        }
+
+        Frame {
+            x,
+            y,
+            left,
+            top,
+            width,
+            height,
+        }
+    }
+
+    fn point(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        // Far away points only give the direction of a line, keep them sane
+        let limit = 10.0 * (self.width + self.height);
+        let px = self.left + (x - self.x.0) / (self.x.1 - self.x.0) * self.width;
+        let py = self.top + (self.y.1 - y) / (self.y.1 - self.y.0) * self.height;
+
+        (px.max(-limit).min(limit), py.max(-limit).min(limit))
+    }
+
+    /// Pieces of a curve in picture coordinates, split where it has gaps or poles
+    fn strokes(&self, curve: &plot::Curve) -> Vec<Vec<(f64, f64)>> {
+        let mut res = vec![];
+        let mut stroke: Vec<(f64, f64)> = vec![];
+
+        for point in &curve.points {
+            match *point {
+                Some(p) => {
+                    let p = self.point(p);
+
+                    if let Some(last) = stroke.last() {
+                        if (last.1 - p.1).abs() > self.height {
+                            res.push(std::mem::replace(&mut stroke, vec![]));
+                        }
+                    }
+                    stroke.push(p);
+                }
+                None if !stroke.is_empty() => res.push(std::mem::replace(&mut stroke, vec![])),
+                None => {}
+            }
+        }
+
+        if !stroke.is_empty() {
+            res.push(stroke);
+        }
+
+        res
+    }
+
+    fn x_ticks(&self) -> Vec<(f64, String)> {
+        let (values, step) = plot::ticks(self.x.0, self.x.1);
+
+        values
+            .into_iter()
+            .map(|x| (self.point((x, 0.0)).0, plot::tick_label(x, step)))
+            .collect()
+    }
+
+    fn y_ticks(&self) -> Vec<(f64, String)> {
+        let (values, step) = plot::ticks(self.y.0, self.y.1);
+
+        values
+            .into_iter()
+            .map(|y| (self.point((0.0, y)).1, plot::tick_label(y, step)))
+            .collect()
+    }
+
+    /// Pixel of the axis where the other coordinate is zero, if it is in view
+    fn axes(&self) -> (Option<f64>, Option<f64>) {
+        let (x, y) = self.point((0.0, 0.0));
+        let inside = |a: f64, b: f64| a <= 0.0 && 0.0 <= b;
+
+        (
+            if inside(self.x.0, self.x.1) { Some(x) } else { None },
+            if inside(self.y.0, self.y.1) { Some(y) } else { None },
+        )
+    }
+
+    fn right(&self) -> f64 {
+        self.left + self.width
+    }
+
+    fn bottom(&self) -> f64 {
+        self.top + self.height
+    }
+}
+
+fn color((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Vector picture with a grid, axes, tick labels and a legend
+pub fn svg(plot: &Plot) -> String {
+    let (width, height) = size(plot);
+    let frame = Frame::new(plot);
+    let mut res = String::new();
+
+    let _ = writeln!(
+        res,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"12\">",
+        w = width,
+        h = height
+    );
+    let _ = writeln!(res, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>");
+    let _ = writeln!(
+        res,
+        "<defs><clipPath id=\"area\"><rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" \
+         height=\"{:.2}\"/></clipPath></defs>",
+        frame.left, frame.top, frame.width, frame.height
+    );
+
+    let line = |res: &mut String, (x1, y1): (f64, f64), (x2, y2): (f64, f64), c| {
+        let _ = writeln!(
+            res,
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\"/>",
+            x1,
+            y1,
+            x2,
+            y2,
+            color(c)
+        );
+    };
+
+    for (x, label) in frame.x_ticks() {
+        line(&mut res, (x, frame.top), (x, frame.bottom()), GRID);
+        let _ = writeln!(
+            res,
+            "<text x=\"{:.2}\" y=\"{:.2}\" text-anchor=\"middle\">{}</text>",
+            x,
+            frame.bottom() + 18.0,
+            escape(&label)
+        );
+    }
+
+    for (y, label) in frame.y_ticks() {
+        line(&mut res, (frame.left, y), (frame.right(), y), GRID);
+        let _ = writeln!(
+            res,
+            "<text x=\"{:.2}\" y=\"{:.2}\" text-anchor=\"end\">{}</text>",
+            frame.left - 6.0,
+            y + 4.0,
+            escape(&label)
+        );
+    }
+
+    let (x_axis, y_axis) = frame.axes();
+    if let Some(x) = x_axis {
+        line(&mut res, (x, frame.top), (x, frame.bottom()), AXES);
+    }
+    if let Some(y) = y_axis {
+        line(&mut res, (frame.left, y), (frame.right(), y), AXES);
+    }
+
+    let _ = writeln!(
+        res,
+        "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"none\" \
+         stroke=\"{}\"/>",
+        frame.left,
+        frame.top,
+        frame.width,
+        frame.height,
+        color(AXES)
+    );
+
+    let _ = writeln!(res, "<g clip-path=\"url(#area)\" fill=\"none\" stroke-width=\"2\">");
+    for (k, curve) in plot.curves.iter().enumerate() {
+        let mut path = String::new();
+
+        for stroke in frame.strokes(curve) {
+            for (i, (x, y)) in stroke.iter().enumerate() {
+                let _ = write!(path, "{}{:.2} {:.2} ", if i == 0 { "M" } else { "L" }, x, y);
+            }
+        }
+
+        let _ = writeln!(
+            res,
+            "<path d=\"{}\" stroke=\"{}\"/>",
+            path.trim_end(),
+            color(COLORS[k % COLORS.len()])
+        );
+    }
+    let _ = writeln!(res, "</g>");
+
+    // Legend in the top right corner of the chart
+    let longest = plot.curves.iter().map(|c| c.label.chars().count()).max().unwrap_or(0);
+    let (box_width, box_height) = (40.0 + 7.0 * longest as f64, 18.0 * plot.curves.len() as f64);
+    let (x, y) = (frame.right() - box_width - 8.0, frame.top + 8.0);
+
+    let _ = writeln!(
+        res,
+        "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"white\" \
+         fill-opacity=\"0.8\" stroke=\"{}\"/>",
+        x,
+        y,
+        box_width,
+        box_height + 6.0,
+        color(GRID)
+    );
+    for (k, curve) in plot.curves.iter().enumerate() {
+        let row = y + 14.0 + 18.0 * k as f64;
+        let _ = writeln!(
+            res,
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\" \
+             stroke-width=\"2\"/>",
+            x + 6.0,
+            row - 4.0,
+            x + 26.0,
+            row - 4.0,
+            color(COLORS[k % COLORS.len()])
+        );
+        let _ = writeln!(
+            res,
+            "<text x=\"{:.2}\" y=\"{:.2}\">{}</text>",
+            x + 32.0,
+            row,
+            escape(&curve.label)
+        );
+    }
+
+    res.push_str("</svg>\n");
+
+    res
+}
+
+/// RGB pixels with clipping
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    clip: (f64, f64, f64, f64),
+}
+
+#[derive(Clone, Copy)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![255; width * height * 3],
+            clip: (0.0, 0.0, width as f64, height as f64),
+        }
+    }
+
+    fn set(&mut self, x: f64, y: f64, (r, g, b): (u8, u8, u8)) {
+        let (left, top, right, bottom) = self.clip;
+
+        if x >= left && x < right && y >= top && y < bottom {
+            let (x, y) = (x as usize, y as usize);
+
+            if x < self.width && y < self.height {
+                let i = (y * self.width + x) * 3;
+                self.pixels[i..i + 3].copy_from_slice(&[r, g, b]);
+            }
+        }
+    }
+
+    fn rect(&mut self, (x, y): (f64, f64), (width, height): (f64, f64), c: (u8, u8, u8)) {
+        for i in 0..width.round() as usize {
+            for j in 0..height.round() as usize {
+                self.set(x + i as f64, y + j as f64, c);
+            }
+        }
+    }
+
+    /// `thickness` pixels wide, one step per pixel along the longer direction
+    fn line(&mut self, a: (f64, f64), b: (f64, f64), thickness: f64, c: (u8, u8, u8)) {
+        let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil().max(1.0) as usize;
+        let offset = (thickness - 1.0) / 2.0;
+
+        for i in 0..=steps {
+            let t = i as f64 / steps as f64;
+            let (x, y) = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+
+            self.rect((x - offset, y - offset), (thickness, thickness), c);
+        }
+    }
+
+    /// Text in the built-in 3x5 font drawn twice as big, `y` is the baseline
+    fn text(&mut self, (x, y): (f64, f64), text: &str, anchor: Anchor, c: (u8, u8, u8)) {
+        const SCALE: f64 = 2.0;
+        const ADVANCE: f64 = 4.0 * SCALE;
+
+        let width = text.chars().count() as f64 * ADVANCE - SCALE;
+        let x = match anchor {
+            Anchor::Start => x,
+            Anchor::Middle => x - width / 2.0,
+            Anchor::End => x - width,
+        }
+        .round();
+        let top = (y - 5.0 * SCALE).round();
+
+        for (n, ch) in text.chars().enumerate() {
+            let rows = glyph(ch);
+
+            for (j, row) in rows.iter().enumerate() {
+                for i in 0..3 {
+                    if row & (0b100 >> i) != 0 {
+                        let px = x + n as f64 * ADVANCE + i as f64 * SCALE;
+
+                        self.rect((px, top + j as f64 * SCALE), (SCALE, SCALE), c);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of a 3x5 glyph, top first, leftmost pixel in the highest bit
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_lowercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'g' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'h' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'i' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'j' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'k' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'l' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'm' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'n' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'o' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'p' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'r' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        's' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        't' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'u' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'v' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'w' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'x' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '^' => [0b010, 0b101, 0b000, 0b000, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        ' ' => [0; 5],
+        _ => [0b111; 5],
+    }
+}
+
+/// Same picture as `svg`, rasterized
+pub fn png(plot: &Plot) -> Vec<u8> {
+    let (width, height) = size(plot);
+    let frame = Frame::new(plot);
+    let mut canvas = Canvas::new(width as usize, height as usize);
+
+    for (x, label) in frame.x_ticks() {
+        canvas.line((x, frame.top), (x, frame.bottom()), 1.0, GRID);
+        canvas.text((x, frame.bottom() + 18.0), &label, Anchor::Middle, AXES);
+    }
+
+    for (y, label) in frame.y_ticks() {
+        canvas.line((frame.left, y), (frame.right(), y), 1.0, GRID);
+        canvas.text((frame.left - 6.0, y + 5.0), &label, Anchor::End, AXES);
+    }
+
+    let (x_axis, y_axis) = frame.axes();
+    if let Some(x) = x_axis {
+        canvas.line((x, frame.top), (x, frame.bottom()), 1.0, AXES);
+    }
+    if let Some(y) = y_axis {
+        canvas.line((frame.left, y), (frame.right(), y), 1.0, AXES);
+    }
+
+    canvas.clip = (frame.left, frame.top, frame.right(), frame.bottom());
+    for (k, curve) in plot.curves.iter().enumerate() {
+        for stroke in frame.strokes(curve) {
+            for pair in stroke.windows(2) {
+                canvas.line(pair[0], pair[1], 2.0, COLORS[k % COLORS.len()]);
+            }
+            if stroke.len() == 1 {
+                canvas.line(stroke[0], stroke[0], 2.0, COLORS[k % COLORS.len()]);
+            }
+        }
+    }
+    canvas.clip = (0.0, 0.0, width, height);
+
+    let (left, top, right, bottom) = (frame.left, frame.top, frame.right(), frame.bottom());
+    for &(a, b) in &[
+        ((left, top), (right, top)),
+        ((right, top), (right, bottom)),
+        ((right, bottom), (left, bottom)),
+        ((left, bottom), (left, top)),
+    ] {
+        canvas.line(a, b, 1.0, AXES);
+    }
+
+    let longest = plot.curves.iter().map(|c| c.label.chars().count()).max().unwrap_or(0);
+    let (box_width, box_height) = (40.0 + 8.0 * longest as f64, 18.0 * plot.curves.len() as f64);
+    let (x, y) = (right - box_width - 8.0, top + 8.0);
+
+    canvas.rect((x, y), (box_width, box_height + 6.0), WHITE);
+    for (k, curve) in plot.curves.iter().enumerate() {
+        let row = y + 16.0 + 18.0 * k as f64;
+
+        canvas.line((x + 6.0, row - 5.0), (x + 26.0, row - 5.0), 2.0, COLORS[k % COLORS.len()]);
+        canvas.text((x + 32.0, row), &curve.label, Anchor::Start, AXES);
+    }
+
+    encode(canvas.width, canvas.height, &canvas.pixels)
+}
+
+/// 8 bit RGB PNG, the image data is deflated with stored blocks only
+fn encode(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+    for row in pixels.chunks(width * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        let len = block.len() as u16;
+
+        zlib.push((i + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut res = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    chunk(&mut res, b"IHDR", &header);
+    chunk(&mut res, b"IDAT", &zlib);
+    chunk(&mut res, b"IEND", &[]);
+
+    res
+}
+
+fn chunk(res: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    res.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    res.extend_from_slice(kind);
+    res.extend_from_slice(data);
+
+    let crc = crc32(kind.iter().chain(data));
+    res.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32<'a>(data: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        *entry = (0..8).fold(n as u32, |c, _| {
+            if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            }
+        });
+    }
+
+    !data.fold(!0u32, |crc, &byte| table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+
+    (b << 16) | a
+}
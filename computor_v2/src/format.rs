@@ -0,0 +1,469 @@
+use crate::bigfloat::BigFloat;
+use crate::{latex, mathml, plot, unicode, units, Expr, ExprError, Slice};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Signed;
+use std::cmp::max;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Notation {
+    /// Positional for "normal" magnitudes, scientific for very big or small
+    Auto,
+    Fixed,
+    Scientific,
+    Engineering,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatrixLayout {
+    /// Single line, same syntax as the input: `[[1, 2]; [3, 4]]`
+    Inline,
+    /// One row per line, columns aligned
+    Pretty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Parenthesised infix, the same syntax as the input
+    Text,
+    Latex,
+    /// Stacked fractions and raised exponents, several lines high
+    Unicode,
+    Mathml,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Latex => write!(f, "latex"),
+            Format::Unicode => write!(f, "unicode"),
+            Format::Mathml => write!(f, "mathml"),
+        }
+    }
+}
+
+/// How values are computed and printed, changed from the REPL with `:set <name> <value>`
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Keep integers and fractions as exact rationals instead of `f64`
+    pub exact: bool,
+    /// Significant digits of decimal numbers, `None` means plain `f64`
+    pub precision: Option<usize>,
+    pub notation: Notation,
+    /// Significant digits, `None` means as many as needed to be exact
+    pub digits: Option<usize>,
+    pub separator: Option<char>,
+    pub matrix: MatrixLayout,
+    /// Language of the printed results
+    pub format: Format,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            exact: true,
+            precision: None,
+            notation: Notation::Auto,
+            digits: None,
+            separator: None,
+            matrix: MatrixLayout::Inline,
+            format: Format::Text,
+        }
+    }
+}
+
+impl Settings {
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ExprError> {
+        let bad = || ExprError::BadSetting {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+
+        match (name, value) {
+            ("exact", "on") => self.exact = true,
+            ("exact", "off") => self.exact = false,
+            ("precision", "off") => self.precision = None,
+            ("precision", n) => match n.parse() {
+                Ok(n) if n > 0 && n <= 10000 => self.precision = Some(n),
+                _ => return Err(bad()),
+            },
+            ("notation", "auto") => self.notation = Notation::Auto,
+            ("notation", "fixed") => self.notation = Notation::Fixed,
+            ("notation", "sci") | ("notation", "scientific") => {
+                self.notation = Notation::Scientific
+            }
+            ("notation", "eng") | ("notation", "engineering") => {
+                self.notation = Notation::Engineering
+            }
+            ("digits", "auto") => self.digits = None,
+            ("digits", n) => match n.parse() {
+                Ok(n) if n > 0 && n <= 17 => self.digits = Some(n),
+                _ => return Err(bad()),
+            },
+            ("separator", "off") => self.separator = None,
+            ("separator", "on") => self.separator = Some(','),
+            ("separator", c) if c.chars().count() == 1 => self.separator = c.chars().next(),
+            ("matrix", "inline") => self.matrix = MatrixLayout::Inline,
+            ("matrix", "pretty") => self.matrix = MatrixLayout::Pretty,
+            ("format", "text") => self.format = Format::Text,
+            ("format", "latex") => self.format = Format::Latex,
+            ("format", "unicode") => self.format = Format::Unicode,
+            ("format", "mathml") => self.format = Format::Mathml,
+            _ => return Err(bad()),
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let exact = if self.exact { "on" } else { "off" };
+        let precision = match self.precision {
+            Some(n) => n.to_string(),
+            None => "off".to_string(),
+        };
+        let notation = match self.notation {
+            Notation::Auto => "auto",
+            Notation::Fixed => "fixed",
+            Notation::Scientific => "sci",
+            Notation::Engineering => "eng",
+        };
+        let digits = match self.digits {
+            Some(n) => n.to_string(),
+            None => "auto".to_string(),
+        };
+        let separator = match self.separator {
+            Some(c) => c.to_string(),
+            None => "off".to_string(),
+        };
+        let matrix = match self.matrix {
+            MatrixLayout::Inline => "inline",
+            MatrixLayout::Pretty => "pretty",
+        };
+
+        writeln!(f, "exact     {}", exact)?;
+        writeln!(f, "precision {}", precision)?;
+        writeln!(f, "notation  {}", notation)?;
+        writeln!(f, "digits    {}", digits)?;
+        writeln!(f, "separator {}", separator)?;
+        writeln!(f, "matrix    {}", matrix)?;
+        write!(f, "format    {}", self.format)
+    }
+}
+
+pub fn format_real(x: f64, settings: &Settings) -> String {
+    if x.is_nan() {
+        return "NaN".to_string();
+    }
+    if x.is_infinite() {
+        return if x < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    if x == 0.0 {
+        return "0".to_string();
+    }
+
+    // Let std do the rounding, then move the decimal point around ourselves
+    let sci = match settings.digits {
+        Some(n) => format!("{:.*e}", n - 1, x.abs()),
+        None => format!("{:e}", x.abs()),
+    };
+    let (mantissa, exp) = sci.split_at(sci.find('e').unwrap());
+    let exp: i32 = exp[1..].parse().unwrap();
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+
+    place(x < 0.0, &digits, exp, 15, settings)
+}
+
+/// `BigFloat` rounded to `settings.digits`, switching to exponents past its own precision
+pub fn format_bigfloat(x: &BigFloat, settings: &Settings) -> String {
+    if x.is_zero() {
+        return "0".to_string();
+    }
+
+    let x = match settings.digits {
+        Some(n) => x.with_precision(n),
+        None => x.clone(),
+    };
+    let (digits, exp) = x.digits();
+
+    place(x.is_negative(), &digits, exp, max(x.precision(), 15) as i32, settings)
+}
+
+/// Decimal number `d.ddd * 10^exp` with the point placed by `settings.notation`, `Auto`
+/// switches to scientific notation below `1e-5` and from `10^auto_limit`
+fn place(negative: bool, digits: &str, exp: i32, auto_limit: i32, settings: &Settings) -> String {
+    let notation = match settings.notation {
+        Notation::Auto if exp < -5 || exp >= auto_limit => Notation::Scientific,
+        Notation::Auto => Notation::Fixed,
+        notation => notation,
+    };
+
+    let body = match notation {
+        Notation::Scientific => with_exponent(digits, 1, exp, settings),
+        Notation::Engineering => {
+            let shift = exp.rem_euclid(3);
+            with_exponent(digits, shift as usize + 1, exp - shift, settings)
+        }
+        _ if exp >= 0 => {
+            let (int, frac) = split_digits(digits, exp as usize + 1);
+            join(int, frac, settings)
+        }
+        _ => join(
+            "0".to_string(),
+            "0".repeat((-exp - 1) as usize) + digits,
+            settings,
+        ),
+    };
+
+    if negative {
+        format!("-{}", body)
+    } else {
+        body
+    }
+}
+
+pub fn format_integer(x: &BigInt, settings: &Settings) -> String {
+    let digits = join(x.abs().to_string(), String::new(), settings);
+
+    if x.is_negative() {
+        format!("-{}", digits)
+    } else {
+        digits
+    }
+}
+
+pub fn format_rational(x: &BigRational, settings: &Settings) -> String {
+    format!("{}/{}", format_integer(x.numer(), settings), format_integer(x.denom(), settings))
+}
+
+pub fn format_complex(re: f64, im: f64, settings: &Settings) -> String {
+    if im == 0.0 {
+        return format_real(re, settings);
+    }
+
+    let abs = if im.abs() == 1.0 {
+        "i".to_string()
+    } else {
+        format_real(im.abs(), settings) + "i"
+    };
+
+    if re != 0.0 {
+        let sign = if im < 0.0 { "-" } else { "+" };
+
+        format!("{} {} {}", format_real(re, settings), sign, abs)
+    } else if im < 0.0 {
+        format!("-{}", abs)
+    } else {
+        abs
+    }
+}
+
+pub fn format_vector(items: &[f64], settings: &Settings) -> String {
+    let items: Vec<String> = items.iter().map(|x| format_real(*x, settings)).collect();
+
+    format!("[{}]", items.join(", "))
+}
+
+pub fn format_matrix(rows: &[Vec<f64>], settings: &Settings) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|x| format_real(*x, settings)).collect())
+        .collect();
+
+    match settings.matrix {
+        MatrixLayout::Inline => {
+            let rows: Vec<String> = cells
+                .iter()
+                .map(|row| format!("[{}]", row.join(", ")))
+                .collect();
+
+            format!("[{}]", rows.join("; "))
+        }
+        MatrixLayout::Pretty => {
+            if cells.is_empty() {
+                return "[ ]".to_string();
+            }
+
+            let columns = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+            let widths: Vec<usize> = (0..columns)
+                .map(|j| {
+                    cells
+                        .iter()
+                        .filter_map(|row| row.get(j))
+                        .map(|cell| cell.chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            let rows: Vec<String> = cells
+                .iter()
+                .map(|row| {
+                    let row: Vec<String> = row
+                        .iter()
+                        .zip(&widths)
+                        .map(|(cell, width)| format!("{:>1$}", cell, width))
+                        .collect();
+
+                    format!("[ {} ]", row.join("  "))
+                })
+                .collect();
+
+            rows.join("\n")
+        }
+    }
+}
+
+/// Cuts `digits` after `int_len` digits, padding with zeros if it is too short
+fn split_digits(digits: &str, int_len: usize) -> (String, String) {
+    if digits.len() > int_len {
+        (digits[..int_len].to_string(), digits[int_len..].to_string())
+    } else {
+        (digits.to_string() + &"0".repeat(int_len - digits.len()), String::new())
+    }
+}
+
+fn with_exponent(digits: &str, int_len: usize, exp: i32, settings: &Settings) -> String {
+    let (int, frac) = split_digits(digits, int_len);
+
+    format!("{}e{}", join(int, frac, settings), exp)
+}
+
+fn join(int: String, frac: String, settings: &Settings) -> String {
+    let int = match settings.separator {
+        Some(separator) => {
+            let mut grouped = String::new();
+
+            for (i, c) in int.chars().enumerate() {
+                if i > 0 && (int.len() - i) % 3 == 0 {
+                    grouped.push(separator);
+                }
+                grouped.push(c);
+            }
+
+            grouped
+        }
+        None => int,
+    };
+    let frac = frac.trim_end_matches('0');
+
+    if frac.is_empty() {
+        int
+    } else {
+        format!("{}.{}", int, frac)
+    }
+}
+
+/// `Expr` printer that follows the given `Settings`
+pub struct Formatted<'a> {
+    expr: &'a Expr,
+    settings: &'a Settings,
+}
+
+impl Expr {
+    pub fn formatted<'a>(&'a self, settings: &'a Settings) -> Formatted<'a> {
+        Formatted {
+            expr: self,
+            settings,
+        }
+    }
+
+    /// Text of `self` in the format picked with `:format`, plots are always drawn
+    pub fn rendered(&self, settings: &Settings) -> String {
+        match (settings.format, self) {
+            (_, Expr::Plot(_)) | (Format::Text, _) => self.formatted(settings).to_string(),
+            (Format::Latex, _) => latex::latex(self, settings),
+            (Format::Unicode, _) => unicode::unicode(self, settings),
+            (Format::Mathml, _) => mathml::mathml(self, settings),
+        }
+    }
+}
+
+/// `x` as an operand of `*`, `/`, `%`, `^` or `!`, a fraction goes in
+/// parentheses to read back the same
+fn operand(x: &Expr, s: &Settings) -> String {
+    let text = x.formatted(s).to_string();
+
+    match x {
+        Expr::Rational(_) if text.contains('/') => format!("({})", text),
+        _ => text,
+    }
+}
+
+impl<'a> fmt::Display for Formatted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.settings;
+
+        match self.expr {
+            Expr::Real(ref x) => write!(f, "{}", format_real(*x, s)),
+            Expr::Integer(ref x) => write!(f, "{}", format_integer(x, s)),
+            Expr::Rational(ref x) => write!(f, "{}", format_rational(x, s)),
+            Expr::BigFloat(ref x) => write!(f, "{}", format_bigfloat(x, s)),
+            Expr::Complex(ref x, ref y) => write!(f, "{}", format_complex(*x, *y, s)),
+            Expr::Quantity(ref x) => match x.unit {
+                Some((ref name, size)) => write!(f, "{} {}", format_real(x.value / size, s), name),
+                None => write!(f, "{} {}", format_real(x.value, s), units::base_units(&x.dim)),
+            },
+            Expr::Var(ref x) | Expr::Unit(ref x) => write!(f, "{}", x),
+            Expr::Lambda(ref args, ref expr) => {
+                write!(f, "({}) => {}", args.join(", "), expr.formatted(s))
+            }
+            Expr::Call(ref func, ref args) => {
+                let args: Vec<String> = args.iter().map(|x| x.formatted(s).to_string()).collect();
+
+                write!(f, "{}({})", func, args.join(", "))
+            }
+            Expr::Vector(ref x) => write!(f, "{}", format_vector(x, s)),
+            Expr::Plot(ref x) => write!(f, "{}", plot::render(x, plot::terminal())),
+            Expr::Matrix(ref x) => write!(f, "{}", format_matrix(x, s)),
+            Expr::Tuple(ref items) => {
+                let items: Vec<String> = items.iter().map(|x| x.formatted(s).to_string()).collect();
+
+                // Pretty matrices don't fit on one line, put them one after another
+                if items.iter().any(|x| x.contains('\n')) {
+                    write!(f, "{}", items.join("\n\n"))
+                } else {
+                    write!(f, "({})", items.join(", "))
+                }
+            }
+            Expr::Index(ref x, ref slices) => {
+                let end = |x: &Option<Expr>| match x {
+                    Some(x) => x.formatted(s).to_string(),
+                    None => String::new(),
+                };
+                let slices: Vec<String> = slices
+                    .iter()
+                    .map(|slice| match slice {
+                        Slice::At(x) => x.formatted(s).to_string(),
+                        Slice::Range(from, to) => format!("{}:{}", end(from), end(to)),
+                    })
+                    .collect();
+
+                write!(f, "{}[{}]", x.formatted(s), slices.join(", "))
+            }
+            Expr::Neg(ref x) => write!(f, "-{}", x.formatted(s)),
+            Expr::Factorial(ref x) => write!(f, "{}!", operand(x, s)),
+            Expr::Add(ref x, ref y) => write!(f, "({} + {})", x.formatted(s), y.formatted(s)),
+            Expr::Mul(ref x, ref y) => write!(f, "({} * {})", operand(x, s), operand(y, s)),
+            Expr::Div(ref x, ref y) => write!(f, "({} / {})", operand(x, s), operand(y, s)),
+            Expr::Rem(ref x, ref y) => write!(f, "({} % {})", operand(x, s), operand(y, s)),
+            Expr::Pow(ref x, ref y) => write!(f, "({} ^ {})", operand(x, s), operand(y, s)),
+            Expr::MatrixMul(ref x, ref y) => {
+                write!(f, "({} ** {})", x.formatted(s), y.formatted(s))
+            }
+            Expr::Solve(ref x, ref y) => write!(f, "({} \\ {})", x.formatted(s), y.formatted(s)),
+            Expr::Convert(ref x, ref unit) => {
+                write!(f, "({} to {})", x.formatted(s), unit.formatted(s))
+            }
+            Expr::AssignVar(ref name, ref val) => write!(f, "{} = {}", name, val.formatted(s)),
+            Expr::AssignFunc(_, _) => {
+                // write!(f, "{}({}) = {}", name, args.join(", "), body)
+                write!(f, "(function)",)
+            }
+        }
+    }
+}
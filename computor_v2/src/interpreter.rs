@@ -0,0 +1,323 @@
+use crate::native::{self, Arity, Closure, NativeFunction};
+use crate::{int_to_f64, parse, rational, to_f64, Context, Expr, ExprError, Limits};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::convert::TryFrom;
+
+/// Calculator session for programs embedding it, variables and functions
+/// live as long as the interpreter does
+#[derive(Clone, Debug, Default)]
+pub struct Interpreter {
+    context: Context,
+    /// Whether results are kept as `$1`, `$2`... too
+    numbered: bool,
+    results: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::default()
+    }
+
+    /// Runs one line the way the REPL does, `ans` is kept for the next one
+    pub fn eval(&mut self, line: &str) -> Result<Expr, ExprError> {
+        self.eval_expr(parse(line)?)
+    }
+
+    /// `eval` of a line already parsed
+    pub fn eval_expr(&mut self, expr: Expr) -> Result<Expr, ExprError> {
+        // Function bodies are not values, don't keep them as answers
+        let is_func = matches!(expr, Expr::AssignFunc(..));
+        let result = expr.eval(&mut self.context)?;
+
+        if !is_func {
+            if self.numbered {
+                let name = format!("${}", self.results + 1);
+                self.context.insert(name, result.clone());
+                self.results += 1;
+            }
+            self.context.insert("ans".to_string(), result.clone());
+        }
+
+        Ok(result)
+    }
+
+    /// Keeps every result as `$1`, `$2`... from now on, like the REPL
+    pub fn number_results(&mut self) {
+        self.numbered = true;
+    }
+
+    /// Runs user functions with the tree walker instead of compiling them,
+    /// to compare the two. Slower, and deep recursion may run out of stack
+    pub fn walk_functions(&mut self) {
+        self.context.walk = true;
+    }
+
+    /// `eval` with the result turned into a Rust value, `f64`, `Vec<f64>`...
+    pub fn eval_as<T>(&mut self, line: &str) -> Result<T, ExprError>
+    where
+        T: TryFrom<Expr, Error = ExprError>,
+    {
+        T::try_from(self.eval(line)?)
+    }
+
+    /// Matrices need rows of the same, non-zero length
+    pub fn set_var(&mut self, name: &str, value: impl Into<Expr>) -> Result<(), ExprError> {
+        let name = identifier(name)?;
+        let value = value.into();
+
+        if let Expr::Matrix(ref rows) = value {
+            let cols = rows.first().map_or(0, Vec::len);
+
+            if cols == 0 || rows.iter().any(|row| row.len() != cols) {
+                return Err(ExprError::InvalidMatrix);
+            }
+        }
+
+        self.context.define(name, value)
+    }
+
+    pub fn get_var<T>(&self, name: &str) -> Result<T, ExprError>
+    where
+        T: TryFrom<Expr, Error = ExprError>,
+    {
+        let name = identifier(name)?;
+
+        match self.context.get(&name) {
+            Some(value) => T::try_from(value.clone()),
+            None => Err(ExprError::UndefinedVariable { name }),
+        }
+    }
+
+    /// Same as `name(args...) = body` typed in the REPL
+    pub fn define_fn(&mut self, name: &str, args: &[&str], body: &str) -> Result<(), ExprError> {
+        let name = identifier(name)?;
+        let args = args.iter().map(|arg| identifier(arg)).collect::<Result<_, _>>()?;
+        let body = parse(body)?;
+        self.context.start();
+        let body = native::fold(body, &self.context);
+
+        self.context.define(name, Expr::Lambda(args, box body))
+    }
+
+    /// Function implemented in Rust, called with `arity` evaluated arguments
+    pub fn register_native_fn<F>(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: F,
+    ) -> Result<(), ExprError>
+    where
+        F: Fn(&[Expr]) -> Result<Expr, ExprError> + Send + Sync + 'static,
+    {
+        let name = identifier(name)?;
+        let arity = Arity::Exact(arity);
+
+        self.register(Closure { name, arity, func })
+    }
+
+    /// Makes `func` callable from expressions by its name
+    pub fn register(&mut self, func: impl NativeFunction + 'static) -> Result<(), ExprError> {
+        identifier(func.name())?;
+        self.context.register(func);
+
+        Ok(())
+    }
+
+    /// Same as `:set name value` in the REPL
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ExprError> {
+        self.context.settings.set(name, value)
+    }
+
+    /// Limits for the evaluations from now on, for untrusted input
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.context.limits = limits;
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+}
+
+/// `name` as the parser would see it, lowercased
+fn identifier(name: &str) -> Result<String, ExprError> {
+    match parse(name) {
+        Ok(Expr::Var(name)) => Ok(name),
+        _ => Err(ExprError::ParseError {
+            err: format!("{} is not a valid name", name),
+        }),
+    }
+}
+
+impl From<f64> for Expr {
+    fn from(x: f64) -> Expr {
+        Expr::Real(x)
+    }
+}
+
+impl From<i32> for Expr {
+    fn from(x: i32) -> Expr {
+        Expr::Integer(x.into())
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(x: i64) -> Expr {
+        Expr::Integer(x.into())
+    }
+}
+
+impl From<BigInt> for Expr {
+    fn from(x: BigInt) -> Expr {
+        Expr::Integer(x)
+    }
+}
+
+impl From<BigRational> for Expr {
+    fn from(x: BigRational) -> Expr {
+        rational(x)
+    }
+}
+
+/// Complex number from its real and imaginary parts
+impl From<(f64, f64)> for Expr {
+    fn from((re, im): (f64, f64)) -> Expr {
+        Expr::Complex(re, im)
+    }
+}
+
+impl From<Vec<f64>> for Expr {
+    fn from(x: Vec<f64>) -> Expr {
+        Expr::Vector(x)
+    }
+}
+
+/// Matrix from its rows
+impl From<Vec<Vec<f64>>> for Expr {
+    fn from(x: Vec<Vec<f64>>) -> Expr {
+        Expr::Matrix(x)
+    }
+}
+
+fn mismatch(expected: &str, found: &Expr) -> ExprError {
+    ExprError::TypeMismatch {
+        expected: expected.to_string(),
+        found: kind(found).to_string(),
+    }
+}
+
+/// What a value is, for error messages
+fn kind(e: &Expr) -> &'static str {
+    match e {
+        Expr::Real(_) | Expr::BigFloat(_) => "real",
+        Expr::Integer(_) => "integer",
+        Expr::Rational(_) => "rational",
+        Expr::Complex(..) => "complex",
+        Expr::Quantity(_) => "quantity",
+        Expr::Vector(_) => "vector",
+        Expr::Matrix(_) => "matrix",
+        Expr::Tuple(_) => "tuple",
+        Expr::Lambda(..) => "function",
+        Expr::Plot(_) => "plot",
+        _ => "expression",
+    }
+}
+
+/// Any real number, exact ones rounded
+impl TryFrom<Expr> for f64 {
+    type Error = ExprError;
+
+    fn try_from(e: Expr) -> Result<f64, ExprError> {
+        match e {
+            Expr::Real(x) => Ok(x),
+            Expr::Integer(ref x) => Ok(int_to_f64(x)),
+            Expr::Rational(ref x) => Ok(to_f64(x)),
+            Expr::BigFloat(ref x) => Ok(x.to_f64()),
+            _ => Err(mismatch("real", &e)),
+        }
+    }
+}
+
+/// Integers that fit, including whole `Real`s from `:set exact off`
+impl TryFrom<Expr> for i64 {
+    type Error = ExprError;
+
+    fn try_from(e: Expr) -> Result<i64, ExprError> {
+        let res = match e {
+            Expr::Integer(ref x) => x.to_i64(),
+            Expr::Real(x) if x.fract() == 0.0 => i64::from_f64(x),
+            _ => return Err(mismatch("integer", &e)),
+        };
+
+        res.ok_or_else(|| ExprError::CalcError {
+            err: format!("{} does not fit in 64 bits", e),
+        })
+    }
+}
+
+impl TryFrom<Expr> for BigInt {
+    type Error = ExprError;
+
+    fn try_from(e: Expr) -> Result<BigInt, ExprError> {
+        match e {
+            Expr::Integer(x) => Ok(x),
+            _ => Err(mismatch("integer", &e)),
+        }
+    }
+}
+
+impl TryFrom<Expr> for BigRational {
+    type Error = ExprError;
+
+    fn try_from(e: Expr) -> Result<BigRational, ExprError> {
+        match e {
+            Expr::Integer(x) => Ok(BigRational::from_integer(x)),
+            Expr::Rational(x) => Ok(x),
+            _ => Err(mismatch("rational", &e)),
+        }
+    }
+}
+
+/// Real and imaginary parts, real numbers have a zero imaginary part
+impl TryFrom<Expr> for (f64, f64) {
+    type Error = ExprError;
+
+    fn try_from(e: Expr) -> Result<(f64, f64), ExprError> {
+        match e {
+            Expr::Complex(re, im) => Ok((re, im)),
+            e => match f64::try_from(e) {
+                Ok(re) => Ok((re, 0.0)),
+                Err(ExprError::TypeMismatch { found, .. }) => Err(ExprError::TypeMismatch {
+                    expected: "complex".to_string(),
+                    found,
+                }),
+                Err(err) => Err(err),
+            },
+        }
+    }
+}
+
+impl TryFrom<Expr> for Vec<f64> {
+    type Error = ExprError;
+
+    fn try_from(e: Expr) -> Result<Vec<f64>, ExprError> {
+        match e {
+            Expr::Vector(x) => Ok(x),
+            _ => Err(mismatch("vector", &e)),
+        }
+    }
+}
+
+/// Rows of a matrix
+impl TryFrom<Expr> for Vec<Vec<f64>> {
+    type Error = ExprError;
+
+    fn try_from(e: Expr) -> Result<Vec<Vec<f64>>, ExprError> {
+        match e {
+            Expr::Matrix(x) => Ok(x),
+            _ => Err(mismatch("matrix", &e)),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::format::format_real;
+use crate::{export, parse, units, Expr, ExprError, Interpreter, Slice};
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use serde_json::Value;
+
+/// Answer to one `{"eval": "..."}` request line of `--json` mode, `id` is
+/// passed back as is
+pub fn answer(line: &str, calc: &mut Interpreter) -> Value {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return json!({ "error": bad_request(&err.to_string()) }),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+
+    let source = match request.get("eval").and_then(Value::as_str) {
+        Some(source) => source,
+        None => {
+            let err = bad_request("expected {\"eval\": \"<expression>\"}");
+
+            return json!({ "id": id, "error": err });
+        }
+    };
+
+    let expr = match parse(source) {
+        Ok(expr) => expr,
+        Err(err) => return json!({ "id": id, "error": error(&err) }),
+    };
+    let tree = ast(&expr);
+
+    match calc.eval_expr(expr) {
+        Ok(result) => json!({
+            "id": id,
+            "ast": tree,
+            "result": value(&result),
+            "text": result.formatted(&calc.context().settings).to_string(),
+        }),
+        Err(err) => json!({ "id": id, "ast": tree, "error": error(&err) }),
+    }
+}
+
+/// Syntax tree, one object per node with its kind in `type`
+pub fn ast(e: &Expr) -> Value {
+    let binary = |kind: &str, a: &Expr, b: &Expr| {
+        json!({ "type": kind, "left": ast(a), "right": ast(b) })
+    };
+
+    match e {
+        Expr::Var(ref name) => json!({ "type": "var", "name": name }),
+        Expr::Unit(ref name) => json!({ "type": "unit", "name": name }),
+        Expr::Lambda(ref args, ref body) => {
+            json!({ "type": "lambda", "args": args, "body": ast(body) })
+        }
+        Expr::Call(ref name, ref args) => {
+            let args: Vec<Value> = args.iter().map(ast).collect();
+
+            json!({ "type": "call", "name": name, "args": args })
+        }
+        Expr::Tuple(ref items) => {
+            let items: Vec<Value> = items.iter().map(ast).collect();
+
+            json!({ "type": "tuple", "items": items })
+        }
+        Expr::Index(ref x, ref slices) => {
+            let end = |x: &Option<Expr>| x.as_ref().map(ast).unwrap_or(Value::Null);
+            let slices: Vec<Value> = slices
+                .iter()
+                .map(|slice| match slice {
+                    Slice::At(x) => json!({ "at": ast(x) }),
+                    Slice::Range(from, to) => json!({ "from": end(from), "to": end(to) }),
+                })
+                .collect();
+
+            json!({ "type": "index", "expr": ast(x), "slices": slices })
+        }
+        Expr::Neg(ref x) => json!({ "type": "neg", "arg": ast(x) }),
+        Expr::Factorial(ref x) => json!({ "type": "factorial", "arg": ast(x) }),
+        Expr::Add(ref a, ref b) => binary("add", a, b),
+        Expr::Mul(ref a, ref b) => binary("mul", a, b),
+        Expr::Div(ref a, ref b) => binary("div", a, b),
+        Expr::Rem(ref a, ref b) => binary("rem", a, b),
+        Expr::Pow(ref a, ref b) => binary("pow", a, b),
+        Expr::MatrixMul(ref a, ref b) => binary("matmul", a, b),
+        Expr::Solve(ref a, ref b) => binary("solve", a, b),
+        Expr::Convert(ref a, ref b) => binary("convert", a, b),
+        Expr::AssignVar(ref name, ref val) => {
+            json!({ "type": "assign", "name": name, "value": ast(val) })
+        }
+        Expr::AssignFunc(ref head, ref body) => {
+            json!({ "type": "define", "head": ast(head), "body": ast(body) })
+        }
+        _ => value(e),
+    }
+}
+
+/// Computed value with its kind in `type`
+pub fn value(e: &Expr) -> Value {
+    match e {
+        Expr::Real(x) => json!({ "type": "real", "value": real(*x) }),
+        Expr::Integer(ref x) => json!({ "type": "integer", "value": integer(x) }),
+        Expr::Rational(ref x) => json!({
+            "type": "rational",
+            "numer": integer(x.numer()),
+            "denom": integer(x.denom()),
+            "value": real(crate::to_f64(x)),
+        }),
+        // Digits as a string, f64 would lose them
+        Expr::BigFloat(ref x) => json!({ "type": "bigfloat", "value": x.to_string() }),
+        Expr::Complex(re, im) => json!({ "type": "complex", "re": real(*re), "im": real(*im) }),
+        Expr::Quantity(ref x) => {
+            let (value, unit) = match x.unit {
+                Some((ref name, size)) => (x.value / size, name.clone()),
+                None => (x.value, units::base_units(&x.dim)),
+            };
+
+            json!({ "type": "quantity", "value": real(value), "unit": unit })
+        }
+        Expr::Vector(ref x) => {
+            let items: Vec<Value> = x.iter().map(|x| real(*x)).collect();
+
+            json!({ "type": "vector", "value": items })
+        }
+        Expr::Matrix(ref x) => {
+            let rows: Vec<Vec<Value>> =
+                x.iter().map(|row| row.iter().map(|x| real(*x)).collect()).collect();
+
+            json!({ "type": "matrix", "value": rows })
+        }
+        Expr::Tuple(ref items) => {
+            let items: Vec<Value> = items.iter().map(value).collect();
+
+            json!({ "type": "tuple", "value": items })
+        }
+        Expr::Lambda(ref args, ref body) => {
+            json!({ "type": "function", "args": args, "body": ast(body) })
+        }
+        Expr::Plot(ref x) => json!({ "type": "plot", "svg": export::svg(x) }),
+        _ => json!({ "type": "expression", "ast": ast(e) }),
+    }
+}
+
+/// JSON number, or `"inf"`, `"-inf"` and `"NaN"` which JSON has no numbers for
+fn real(x: f64) -> Value {
+    if x.is_finite() {
+        json!(x)
+    } else {
+        json!(format_real(x, &Default::default()))
+    }
+}
+
+/// JSON number when it fits in 64 bits, a string of digits when not
+fn integer(x: &BigInt) -> Value {
+    match x.to_i64() {
+        Some(x) => json!(x),
+        None => json!(x.to_string()),
+    }
+}
+
+fn bad_request(message: &str) -> Value {
+    json!({ "type": "bad_request", "message": message })
+}
+
+/// `ExprError` as an object with the variant in `type` and its fields next to it
+pub fn error(err: &ExprError) -> Value {
+    let mut res = match err {
+        ExprError::ParseError { ref err } => json!({ "type": "parse_error", "err": err }),
+        ExprError::UndefinedVariable { ref name } => {
+            json!({ "type": "undefined_variable", "name": name })
+        }
+        ExprError::DivisionByZero => json!({ "type": "division_by_zero" }),
+        ExprError::InvalidMatrix => json!({ "type": "invalid_matrix" }),
+        ExprError::CalcError { ref err } => json!({ "type": "calc_error", "err": err }),
+        ExprError::BadArgsCount { ref func } => json!({ "type": "bad_args_count", "func": func }),
+        ExprError::RecursiveRecursion => json!({ "type": "recursive_recursion" }),
+        ExprError::BadSetting {
+            ref name,
+            ref value,
+        } => json!({ "type": "bad_setting", "name": name, "value": value }),
+        ExprError::SingularMatrix => json!({ "type": "singular_matrix" }),
+        ExprError::InconsistentSystem => json!({ "type": "inconsistent_system" }),
+        ExprError::IndexOutOfBounds { index, len } => {
+            json!({ "type": "index_out_of_bounds", "index": index, "len": len })
+        }
+        ExprError::NoConvergence { ref func } => json!({ "type": "no_convergence", "func": func }),
+        ExprError::WriteError { ref path, ref err } => {
+            json!({ "type": "write_error", "path": path, "err": err })
+        }
+        ExprError::NoAntiderivative { ref expr } => {
+            json!({ "type": "no_antiderivative", "expr": expr })
+        }
+        ExprError::UnitMismatch {
+            ref left,
+            ref right,
+        } => json!({ "type": "unit_mismatch", "left": left, "right": right }),
+        ExprError::TypeMismatch {
+            ref expected,
+            ref found,
+        } => json!({ "type": "type_mismatch", "expected": expected, "found": found }),
+        ExprError::StepLimit { limit } => json!({ "type": "step_limit", "limit": limit }),
+        ExprError::MatrixTooBig { limit } => json!({ "type": "matrix_too_big", "limit": limit }),
+        ExprError::NumberTooBig { limit } => json!({ "type": "number_too_big", "limit": limit }),
+        ExprError::Timeout { ms } => json!({ "type": "timeout", "ms": ms }),
+        ExprError::ContextFull { limit } => json!({ "type": "context_full", "limit": limit }),
+    };
+
+    res["message"] = json!(err.to_string());
+
+    res
+}
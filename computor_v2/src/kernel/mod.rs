@@ -0,0 +1,389 @@
+pub mod hmac;
+#[cfg(feature = "jupyter")]
+mod wire;
+
+#[cfg(feature = "jupyter")]
+pub use self::wire::run;
+
+use crate::{json, latex, lsp, Expr, ExprError, Interpreter};
+
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+const PROTOCOL_VERSION: &str = "5.3";
+
+/// One Jupyter message, as it is on the wire minus the signature
+#[derive(Clone, Debug)]
+pub struct Message {
+    /// Routing prefix of the sender, replies go back with the same one
+    pub identities: Vec<Vec<u8>>,
+    pub header: Value,
+    pub parent_header: Value,
+    pub metadata: Value,
+    pub content: Value,
+}
+
+/// Socket a message comes in from or goes out to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    Shell,
+    Control,
+    Iopub,
+}
+
+impl Message {
+    pub fn msg_type(&self) -> &str {
+        self.header["msg_type"].as_str().unwrap_or("")
+    }
+}
+
+/// Frames of `msg` signed with `key`, no signature when the key is empty
+pub fn encode(msg: &Message, key: &[u8]) -> Vec<Vec<u8>> {
+    let parts: Vec<Vec<u8>> = [&msg.header, &msg.parent_header, &msg.metadata, &msg.content]
+        .iter()
+        .map(|x| x.to_string().into_bytes())
+        .collect();
+
+    let mut frames = msg.identities.clone();
+    frames.push(DELIMITER.to_vec());
+    frames.push(signature(key, &parts).into_bytes());
+    frames.extend(parts);
+
+    frames
+}
+
+/// `None` for anything malformed or with a wrong signature
+pub fn decode(mut frames: Vec<Vec<u8>>, key: &[u8]) -> Option<Message> {
+    let split = frames.iter().position(|x| x.as_slice() == DELIMITER)?;
+    let rest = frames.split_off(split + 1);
+
+    if rest.len() < 5 || !hmac::same(&rest[0], signature(key, &rest[1..5]).as_bytes()) {
+        return None;
+    }
+
+    frames.pop();
+    let json = |x: &[u8]| serde_json::from_slice::<Value>(x).ok();
+
+    Some(Message {
+        identities: frames,
+        header: json(&rest[1])?,
+        parent_header: json(&rest[2])?,
+        metadata: json(&rest[3])?,
+        content: json(&rest[4])?,
+    })
+}
+
+fn signature(key: &[u8], parts: &[Vec<u8>]) -> String {
+    if key.is_empty() {
+        return String::new();
+    }
+
+    let parts: Vec<&[u8]> = parts.iter().map(|x| x.as_slice()).collect();
+
+    hmac::hex(&hmac::hmac_sha256(key, &parts))
+}
+
+/// Kernel session, all cells share one `Interpreter`
+pub struct Kernel {
+    calc: Interpreter,
+    session: String,
+    /// Number of the last cell run, shown as `In [n]`
+    count: usize,
+    sent: usize,
+    pub shutdown: bool,
+}
+
+impl Default for Kernel {
+    fn default() -> Kernel {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_nanos());
+
+        Kernel {
+            calc: Interpreter::new(),
+            session: format!("{:x}-{:x}", nanos, std::process::id()),
+            count: 0,
+            sent: 0,
+            shutdown: false,
+        }
+    }
+}
+
+impl Kernel {
+    pub fn new() -> Kernel {
+        Kernel::default()
+    }
+
+    /// Everything to send in answer to `msg`, replies and broadcasts, wrapped
+    /// in busy and idle statuses
+    pub fn handle(&mut self, channel: Channel, msg: &Message) -> Vec<(Channel, Message)> {
+        let mut res = vec![self.status(msg, "busy")];
+        let content = &msg.content;
+
+        let (reply_type, reply) = match msg.msg_type() {
+            "kernel_info_request" => ("kernel_info_reply", self.kernel_info()),
+            "execute_request" => {
+                let (broadcasts, reply) = self.execute(msg);
+                res.extend(broadcasts);
+
+                ("execute_reply", reply)
+            }
+            "is_complete_request" => ("is_complete_reply", json!({ "status": "complete" })),
+            "complete_request" => ("complete_reply", self.complete(content)),
+            "inspect_request" => ("inspect_reply", self.inspect(content)),
+            "comm_info_request" => ("comm_info_reply", json!({ "status": "ok", "comms": {} })),
+            "history_request" => ("history_reply", json!({ "status": "ok", "history": [] })),
+            "interrupt_request" => ("interrupt_reply", json!({ "status": "ok" })),
+            "shutdown_request" => {
+                self.shutdown = true;
+
+                (
+                    "shutdown_reply",
+                    json!({ "status": "ok", "restart": content["restart"] }),
+                )
+            }
+            // Comms and unknown requests have no reply
+            _ => {
+                res.push(self.status(msg, "idle"));
+                return res;
+            }
+        };
+
+        let reply = self.message(msg, msg.identities.clone(), reply_type, reply);
+        res.push((channel, reply));
+        res.push(self.status(msg, "idle"));
+
+        res
+    }
+
+    fn kernel_info(&self) -> Value {
+        json!({
+            "status": "ok",
+            "protocol_version": PROTOCOL_VERSION,
+            "implementation": "computor_v2",
+            "implementation_version": env!("CARGO_PKG_VERSION"),
+            "language_info": {
+                "name": "computor",
+                "version": env!("CARGO_PKG_VERSION"),
+                "mimetype": "text/x-computor",
+                "file_extension": ".cv",
+            },
+            "banner": "computor_v2",
+        })
+    }
+
+    fn execute(&mut self, msg: &Message) -> (Vec<(Channel, Message)>, Value) {
+        let code = msg.content["code"].as_str().unwrap_or("");
+        let silent = msg.content["silent"].as_bool().unwrap_or(false);
+
+        if !silent {
+            self.count += 1;
+        }
+
+        let input = json!({ "code": code, "execution_count": self.count });
+        let mut broadcasts = vec![self.broadcast(msg, "execute_input", input)];
+
+        match self.eval(code) {
+            Ok(Some(ref result)) if !silent => {
+                let content = json!({
+                    "execution_count": self.count,
+                    "data": self.data(result),
+                    "metadata": {},
+                });
+                broadcasts.push(self.broadcast(msg, "execute_result", content));
+            }
+            Ok(_) => {}
+            Err(err) => {
+                let error = json!({
+                    "ename": json::error(&err)["type"],
+                    "evalue": err.to_string(),
+                    "traceback": [format!("Error: {}", err)],
+                });
+                broadcasts.push(self.broadcast(msg, "error", error.clone()));
+
+                let mut reply = error;
+                reply["status"] = json!("error");
+                reply["execution_count"] = json!(self.count);
+
+                return (broadcasts, reply);
+            }
+        }
+
+        let reply = json!({
+            "status": "ok",
+            "execution_count": self.count,
+            "user_expressions": {},
+            "payload": [],
+        });
+
+        (broadcasts, reply)
+    }
+
+    /// Runs the lines of a cell one after another, the value of the last one
+    /// is the result of the cell
+    fn eval(&mut self, code: &str) -> Result<Option<Expr>, ExprError> {
+        let mut last = None;
+
+        for line in code.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with(":set") {
+                let words: Vec<&str> = line.split_whitespace().skip(1).collect();
+
+                match words.as_slice() {
+                    [name, value] => self.calc.set(name, value)?,
+                    _ => {
+                        return Err(ExprError::CalcError {
+                            err: "usage is :set <name> <value>".to_string(),
+                        })
+                    }
+                }
+                continue;
+            }
+
+            last = Some(self.calc.eval(line)?);
+        }
+
+        Ok(last)
+    }
+
+    /// Mime bundle of a result: plain text and LaTeX, or a picture for plots
+    fn data(&self, result: &Expr) -> Value {
+        let settings = &self.calc.context().settings;
+        let text = result.formatted(settings).to_string();
+
+        match result {
+            Expr::Plot(ref plot) => {
+                json!({ "text/plain": text, "image/svg+xml": crate::export::svg(plot) })
+            }
+            _ => json!({
+                "text/plain": text,
+                "text/latex": format!("${}$", latex::latex(result, settings)),
+            }),
+        }
+    }
+
+    fn complete(&self, content: &Value) -> Value {
+        let code = content["code"].as_str().unwrap_or("");
+        let cursor = content["cursor_pos"].as_u64().unwrap_or(0) as usize;
+        let (start, end, word) = word(code, cursor);
+
+        let mut matches: Vec<&str> = self
+            .calc
+            .context()
+            .names()
+            .filter(|name| name.starts_with(&word))
+            .collect();
+        matches.sort();
+
+        json!({
+            "status": "ok",
+            "matches": matches,
+            "cursor_start": start,
+            "cursor_end": end,
+            "metadata": {},
+        })
+    }
+
+    fn inspect(&self, content: &Value) -> Value {
+        let code = content["code"].as_str().unwrap_or("");
+        let cursor = content["cursor_pos"].as_u64().unwrap_or(0) as usize;
+        let (_, _, name) = word(code, cursor);
+
+        match self.calc.context().get(&name) {
+            Some(value) => json!({
+                "status": "ok",
+                "found": true,
+                "data": { "text/plain": lsp::describe(&name, value, self.calc.context()) },
+                "metadata": {},
+            }),
+            None => json!({ "status": "ok", "found": false, "data": {}, "metadata": {} }),
+        }
+    }
+
+    fn status(&mut self, parent: &Message, state: &str) -> (Channel, Message) {
+        self.broadcast(parent, "status", json!({ "execution_state": state }))
+    }
+
+    fn broadcast(
+        &mut self,
+        parent: &Message,
+        msg_type: &str,
+        content: Value,
+    ) -> (Channel, Message) {
+        let topic = vec![msg_type.as_bytes().to_vec()];
+
+        (
+            Channel::Iopub,
+            self.message(parent, topic, msg_type, content),
+        )
+    }
+
+    fn message(
+        &mut self,
+        parent: &Message,
+        identities: Vec<Vec<u8>>,
+        msg_type: &str,
+        content: Value,
+    ) -> Message {
+        self.sent += 1;
+
+        Message {
+            identities,
+            header: json!({
+                "msg_id": format!("{}-{}", self.session, self.sent),
+                "session": self.session,
+                "username": "computor_v2",
+                "date": now(),
+                "msg_type": msg_type,
+                "version": PROTOCOL_VERSION,
+            }),
+            parent_header: parent.header.clone(),
+            metadata: json!({}),
+            content,
+        }
+    }
+}
+
+/// Name around `cursor`, counted in chars like Jupyter does: start, end and
+/// the name lowercased like the parser does
+fn word(code: &str, cursor: usize) -> (usize, usize, String) {
+    let at = code.char_indices().nth(cursor).map_or(code.len(), |(i, _)| i);
+    let (start, end) = lsp::word(code, at);
+    let chars = |i: usize| code[..i].chars().count();
+
+    (chars(start), chars(end), code[start..end].to_lowercase())
+}
+
+/// ISO 8601 UTC time for message headers
+fn now() -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let (days, secs) = ((time.as_secs() / 86400) as i64, time.as_secs() % 86400);
+
+    // Days since 1970-01-01 to a date, from Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        time.subsec_micros()
+    )
+}
@@ -0,0 +1,91 @@
+use super::{decode, encode, Channel, Kernel};
+
+use failure::{err_msg, Error};
+use serde_json::Value;
+use std::fs;
+use std::thread;
+
+/// Serves the notebook described by the connection file at `path` until it
+/// asks the kernel to shut down
+pub fn run(path: &str) -> Result<(), Error> {
+    let info: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let key = info["key"].as_str().unwrap_or("").as_bytes().to_vec();
+
+    match info["signature_scheme"].as_str() {
+        None | Some("") | Some("hmac-sha256") => {}
+        Some(scheme) => return Err(err_msg(format!("unsupported signature scheme {}", scheme))),
+    }
+
+    let transport = info["transport"].as_str().unwrap_or("tcp");
+    let ip = info["ip"].as_str().unwrap_or("127.0.0.1");
+    let endpoint = |name: &str| -> Result<String, Error> {
+        let port = info[name]
+            .as_u64()
+            .ok_or_else(|| err_msg(format!("no {} given", name)))?;
+
+        Ok(match transport {
+            "ipc" => format!("ipc://{}-{}", ip, port),
+            _ => format!("{}://{}:{}", transport, ip, port),
+        })
+    };
+
+    let zmq = zmq::Context::new();
+    let socket = |kind, name: &str| -> Result<zmq::Socket, Error> {
+        let socket = zmq.socket(kind)?;
+        socket.bind(&endpoint(name)?)?;
+
+        Ok(socket)
+    };
+    let shell = socket(zmq::ROUTER, "shell_port")?;
+    let control = socket(zmq::ROUTER, "control_port")?;
+    let iopub = socket(zmq::PUB, "iopub_port")?;
+    let heartbeat = socket(zmq::REP, "hb_port")?;
+    // Nothing here reads input, but clients expect the socket to be there
+    let _stdin = socket(zmq::ROUTER, "stdin_port")?;
+
+    // Answered while a cell runs too, or the notebook thinks the kernel died
+    thread::spawn(move || -> Result<(), zmq::Error> {
+        loop {
+            heartbeat.send_multipart(heartbeat.recv_multipart(0)?, 0)?;
+        }
+    });
+
+    let mut kernel = Kernel::new();
+
+    while !kernel.shutdown {
+        let mut items = [
+            shell.as_poll_item(zmq::POLLIN),
+            control.as_poll_item(zmq::POLLIN),
+        ];
+        zmq::poll(&mut items, -1)?;
+        let ready: Vec<bool> = items.iter().map(zmq::PollItem::is_readable).collect();
+
+        for &(n, channel) in &[(0, Channel::Shell), (1, Channel::Control)] {
+            if !ready[n] {
+                continue;
+            }
+
+            let from = if channel == Channel::Shell {
+                &shell
+            } else {
+                &control
+            };
+            let msg = match decode(from.recv_multipart(0)?, &key) {
+                Some(msg) => msg,
+                // Unsigned or garbled, not from our notebook
+                None => continue,
+            };
+
+            for (channel, reply) in kernel.handle(channel, &msg) {
+                let to = match channel {
+                    Channel::Shell => &shell,
+                    Channel::Control => &control,
+                    Channel::Iopub => &iopub,
+                };
+                to.send_multipart(encode(&reply, &key), 0)?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use crate::format::{format_bigfloat, format_integer, format_real, Settings};
+use crate::precedence::{
+    self, Join, ADDEND, ATOM, FACTORS, LOWEST, NEG, NEG_OPERAND, POWER, PRODUCT, SUM,
+};
+use crate::{units, Expr, Slice};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed};
+
+/// Functions LaTeX has an operator for
+const OPERATORS: [&str; 22] = [
+    "arccos", "arcsin", "arctan", "arg", "cos", "cosh", "cot", "coth", "csc", "det", "exp",
+    "gcd", "lg", "ln", "log", "max", "min", "sec", "sin", "sinh", "tan", "tanh",
+];
+
+const GREEK: [&str; 24] = [
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+    "lambda", "mu", "nu", "xi", "pi", "rho", "sigma", "tau", "upsilon", "phi", "chi", "psi",
+    "omega", "varepsilon",
+];
+
+/// Math mode LaTeX, with no more parentheses than precedence needs
+pub fn latex(e: &Expr, settings: &Settings) -> String {
+    node(e, settings).0
+}
+
+fn node(e: &Expr, s: &Settings) -> (String, u8) {
+    let (res, prec) = match e {
+        Expr::Real(ref x) => number(&format_real(*x, s)),
+        Expr::Integer(ref x) => number(&format_integer(x, s)),
+        Expr::BigFloat(ref x) => number(&format_bigfloat(x, s)),
+        Expr::Rational(ref x) if x.denom().is_one() => number(&format_integer(x.numer(), s)),
+        Expr::Rational(ref x) => {
+            let sign = if x.is_negative() { "-" } else { "" };
+            let numer = number(&format_integer(&x.numer().abs(), s)).0;
+            let denom = number(&format_integer(x.denom(), s)).0;
+
+            (format!("{}\\frac{{{}}}{{{}}}", sign, numer, denom), PRODUCT)
+        }
+        Expr::Complex(re, im) => complex(*re, *im, s),
+        Expr::Quantity(ref x) => {
+            let (value, unit) = match x.unit {
+                Some((ref name, size)) => (x.value / size, name.clone()),
+                None => (x.value, units::base_units(&x.dim)),
+            };
+
+            (format!("{}\\,{}", number(&format_real(value, s)).0, unit_name(&unit)), PRODUCT)
+        }
+        Expr::Var(ref name) => (ident(name), ATOM),
+        Expr::Unit(_) => (unit_name(&units::unit_name(e)), ATOM),
+        Expr::Lambda(ref args, ref body) => {
+            let args: Vec<String> = args.iter().map(|x| ident(x)).collect();
+            let args = if args.len() == 1 {
+                args[0].clone()
+            } else {
+                format!("\\left({}\\right)", args.join(", "))
+            };
+
+            (format!("{} \\mapsto {}", args, latex(body, s)), LOWEST)
+        }
+        Expr::Call(ref name, ref args) => (call(name, args, s), ATOM),
+        Expr::Vector(ref x) => (pmatrix(std::slice::from_ref(x), s), ATOM),
+        Expr::Matrix(ref x) => (pmatrix(x, s), ATOM),
+        Expr::Tuple(ref items) => (format!("\\left({}\\right)", list(items, s)), ATOM),
+        Expr::Plot(_) => ("\\text{(plot)}".to_string(), ATOM),
+        Expr::Index(ref x, ref slices) => {
+            let end = |x: &Option<Expr>| match x {
+                Some(x) => latex(x, s),
+                None => String::new(),
+            };
+            let slices: Vec<String> = slices
+                .iter()
+                .map(|slice| match slice {
+                    Slice::At(x) => latex(x, s),
+                    Slice::Range(from, to) => format!("{}{{:}}{}", end(from), end(to)),
+                })
+                .collect();
+
+            (format!("{}_{{{}}}", wrap(x, s, ATOM), slices.join(", ")), ATOM)
+        }
+        Expr::Neg(ref x) => (format!("-{}", wrap(x, s, NEG_OPERAND)), NEG),
+        Expr::Factorial(ref x) => (format!("{}!", wrap(x, s, ATOM)), POWER),
+        Expr::Add(ref x, ref y) => {
+            let left = wrap(x, s, ADDEND);
+            let (right, prec) = node(y, s);
+
+            match precedence::join(prec, right.starts_with('-')) {
+                Join::Minus => (format!("{} - {}", left, &right[1..]), SUM),
+                Join::Parens => (format!("{} + \\left({}\\right)", left, right), SUM),
+                Join::Plus => (format!("{} + {}", left, right), SUM),
+            }
+        }
+        Expr::Mul(ref x, ref y) => {
+            let left = wrap(x, s, FACTORS.0);
+            let right = wrap(y, s, FACTORS.1);
+
+            // 2 x, but 2 \cdot 3 and 2 \cdot \frac{1}{3}
+            let implicit = match **x {
+                Expr::Integer(_) | Expr::Real(_) | Expr::Rational(_) | Expr::BigFloat(_) => {
+                    let number = right.starts_with(|c: char| c.is_ascii_digit());
+
+                    !number && !right.starts_with("\\frac")
+                }
+                _ => false,
+            };
+
+            if implicit {
+                (format!("{} {}", left, right), PRODUCT)
+            } else {
+                (format!("{} \\cdot {}", left, right), PRODUCT)
+            }
+        }
+        Expr::Div(ref x, ref y) => {
+            (format!("\\frac{{{}}}{{{}}}", latex(x, s), latex(y, s)), PRODUCT)
+        }
+        Expr::Rem(ref x, ref y) => {
+            (format!("{} \\bmod {}", wrap(x, s, NEG), wrap(y, s, POWER)), PRODUCT)
+        }
+        Expr::Pow(ref x, box Expr::Rational(ref n))
+            if n.numer().is_one() && *n.denom() == BigInt::from(2) =>
+        {
+            (format!("\\sqrt{{{}}}", latex(x, s)), ATOM)
+        }
+        Expr::Pow(ref x, ref y) => (format!("{}^{{{}}}", wrap(x, s, ATOM), latex(y, s)), POWER),
+        Expr::MatrixMul(ref x, ref y) => {
+            (format!("{} {}", wrap(x, s, NEG), wrap(y, s, PRODUCT)), PRODUCT)
+        }
+        // A \ b solves A x = b
+        Expr::Solve(ref x, ref y) => {
+            (format!("{}^{{-1}} {}", wrap(x, s, ATOM), wrap(y, s, PRODUCT)), PRODUCT)
+        }
+        Expr::Convert(ref x, ref unit) => {
+            (format!("{} \\to {}", latex(x, s), latex(unit, s)), LOWEST)
+        }
+        Expr::AssignVar(ref name, ref val) => {
+            (format!("{} = {}", ident(name), latex(val, s)), LOWEST)
+        }
+        Expr::AssignFunc(ref head, ref body) => {
+            (format!("{} = {}", latex(head, s), latex(body, s)), LOWEST)
+        }
+    };
+
+    let prec = precedence::signed(prec, res.starts_with('-'));
+
+    (res, prec)
+}
+
+/// `e`, in parentheses if it is looser than `prec`
+fn wrap(e: &Expr, s: &Settings, prec: u8) -> String {
+    match node(e, s) {
+        (res, p) if precedence::looser(p, prec) => format!("\\left({}\\right)", res),
+        (res, _) => res,
+    }
+}
+
+fn list(items: &[Expr], s: &Settings) -> String {
+    let items: Vec<String> = items.iter().map(|x| latex(x, s)).collect();
+
+    items.join(", ")
+}
+
+/// Number printed by `format`, with `1.5e-7` turned into `1.5 \times 10^{-7}`
+fn number(x: &str) -> (String, u8) {
+    let x = match x {
+        "inf" => return ("\\infty".to_string(), ATOM),
+        "-inf" => return ("-\\infty".to_string(), NEG),
+        "NaN" => return ("\\mathrm{NaN}".to_string(), ATOM),
+        x => x,
+    };
+
+    let mut res = String::new();
+    for c in x.chars() {
+        match c {
+            ',' => res += "{,}",
+            ' ' => res += "\\,",
+            '_' => res += "\\_",
+            c => res.push(c),
+        }
+    }
+
+    match res.find('e') {
+        Some(pos) => {
+            let (mantissa, exp) = res.split_at(pos);
+
+            (format!("{} \\times 10^{{{}}}", mantissa, &exp[1..]), PRODUCT)
+        }
+        None => (res, ATOM),
+    }
+}
+
+fn complex(re: f64, im: f64, s: &Settings) -> (String, u8) {
+    if im == 0.0 {
+        return number(&format_real(re, s));
+    }
+
+    let (imag, prec) = if im.abs() == 1.0 {
+        ("i".to_string(), ATOM)
+    } else {
+        (number(&format_real(im.abs(), s)).0 + "i", PRODUCT)
+    };
+    let sign = if im < 0.0 { "-" } else { "+" };
+
+    if re != 0.0 {
+        (format!("{} {} {}", number(&format_real(re, s)).0, sign, imag), SUM)
+    } else if im < 0.0 {
+        (format!("-{}", imag), NEG)
+    } else {
+        (imag, prec)
+    }
+}
+
+fn pmatrix(rows: &[Vec<f64>], s: &Settings) -> String {
+    let rows: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let row: Vec<String> = row.iter().map(|x| number(&format_real(*x, s)).0).collect();
+
+            row.join(" & ")
+        })
+        .collect();
+
+    format!("\\begin{{pmatrix}} {} \\end{{pmatrix}}", rows.join(" \\\\ "))
+}
+
+fn call(name: &str, args: &[Expr], s: &Settings) -> String {
+    match (name, args) {
+        ("sqrt", [x]) => return format!("\\sqrt{{{}}}", latex(x, s)),
+        ("abs", [x]) => return format!("\\left|{}\\right|", latex(x, s)),
+        ("floor", [x]) => return format!("\\left\\lfloor {} \\right\\rfloor", latex(x, s)),
+        ("ceil", [x]) => return format!("\\left\\lceil {} \\right\\rceil", latex(x, s)),
+        _ => {}
+    }
+
+    let name = if OPERATORS.contains(&name) {
+        format!("\\{}", name)
+    } else if name.chars().count() == 1 {
+        name.to_string()
+    } else {
+        format!("\\operatorname{{{}}}", escape(name))
+    };
+
+    format!("{}\\left({}\\right)", name, list(args, s))
+}
+
+/// `x`, `\pi` or `\mathrm{speed}`
+fn ident(name: &str) -> String {
+    if GREEK.contains(&name) {
+        format!("\\{}", name)
+    } else if name.chars().count() == 1 && name.chars().all(char::is_alphabetic) {
+        name.to_string()
+    } else {
+        format!("\\mathrm{{{}}}", escape(name))
+    }
+}
+
+/// `kg*m/s^2` as `\mathrm{kg} \cdot \mathrm{m}/\mathrm{s}^{2}`
+fn unit_name(unit: &str) -> String {
+    let product = |unit: &str| {
+        let parts: Vec<String> = unit
+            .split('*')
+            .map(|part| match part.find('^') {
+                Some(pos) => {
+                    format!("\\mathrm{{{}}}^{{{}}}", escape(&part[..pos]), &part[pos + 1..])
+                }
+                None => format!("\\mathrm{{{}}}", escape(part)),
+            })
+            .collect();
+
+        parts.join(" \\cdot ")
+    };
+
+    match unit.find('/') {
+        Some(pos) if unit[pos + 1..].starts_with('(') => {
+            let bottom = unit[pos + 1..].trim_start_matches('(').trim_end_matches(')');
+
+            format!("{}/\\left({}\\right)", product(&unit[..pos]), product(bottom))
+        }
+        Some(pos) => format!("{}/{}", product(&unit[..pos]), product(&unit[pos + 1..])),
+        None => product(unit),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut res = String::new();
+
+    for c in s.chars() {
+        match c {
+            '$' | '_' | '%' | '&' | '#' | '{' | '}' => {
+                res.push('\\');
+                res.push(c);
+            }
+            c => res.push(c),
+        }
+    }
+
+    res
+}
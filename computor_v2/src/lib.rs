@@ -5,36 +5,209 @@ extern crate lalrpop_util;
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_traits;
+#[macro_use]
+extern crate serde_json;
+extern crate terminal_size;
+#[cfg(feature = "jupyter")]
+extern crate zmq;
 
 lalrpop_mod!(pub grammar);
 
+pub mod bigfloat;
+mod builtins;
+mod calculus;
+mod compile;
 pub mod computor_v1;
-
+pub mod export;
+pub mod format;
+mod interpreter;
+pub mod json;
+pub mod kernel;
+pub mod latex;
+pub mod limits;
+pub mod linalg;
+pub mod lsp;
+pub mod mathml;
+mod matrix;
+pub mod native;
+pub mod plot;
+mod precedence;
+pub mod repl;
+mod symbolic;
+pub mod unicode;
+pub mod units;
+
+pub use interpreter::Interpreter;
+pub use limits::{Deadline, Limits};
+pub use native::{Arity, NativeFunction};
+
+use bigfloat::BigFloat;
+use format::Settings;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
 use std::collections::HashMap;
 use std::fmt;
 use std::string::ToString;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use units::Quantity;
+
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    vars: HashMap<String, Expr>,
+    natives: HashMap<String, Arc<dyn NativeFunction>>,
+    pub settings: Settings,
+    pub limits: Limits,
+    /// Steps of the evaluation going on, shared with the copies of the
+    /// context made for function calls
+    steps: Arc<AtomicU64>,
+    deadline: Deadline,
+    /// Functions compiled during the evaluation going on. Variables are
+    /// compiled in, so a context gets an empty cache whenever one changes
+    programs: Arc<Mutex<HashMap<String, compile::Program>>>,
+    /// Whether user functions go to the tree walker instead of being compiled
+    walk: bool,
+}
+
+/// `ans`, `$1`, `$2`... where the interpreter keeps results
+fn is_result(name: &str) -> bool {
+    name == "ans" || name.starts_with('$')
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Expr> {
+        self.vars.get(name)
+    }
+
+    pub fn insert(&mut self, name: String, expr: Expr) {
+        self.vars.insert(name, expr);
+        self.programs = Arc::default();
+    }
+
+    /// Value of a variable or a builtin constant
+    pub(crate) fn lookup(&self, name: &str) -> Result<Expr, ExprError> {
+        match self.get(name) {
+            Some(expr) => Ok(expr.clone()),
+            None => match builtins::constant(name, self)? {
+                Some(expr) => Ok(expr),
+                None => Err(ExprError::UndefinedVariable {
+                    name: name.to_string(),
+                }),
+            },
+        }
+    }
+
+    /// Like `lookup`, falling back to the unit of that name
+    pub(crate) fn lookup_unit(&self, name: &str) -> Result<Expr, ExprError> {
+        self.lookup(name).or_else(|err| units::lookup(name).ok_or(err))
+    }
+
+    pub(crate) fn native(&self, name: &str) -> Option<&Arc<dyn NativeFunction>> {
+        self.natives.get(name)
+    }
+
+    /// Like `insert`, failing when a new name would go over the context size
+    /// limit. Results kept as `ans`, `$1`, `$2`... don't count
+    pub(crate) fn define(&mut self, name: String, expr: Expr) -> Result<(), ExprError> {
+        let full = |limit| self.vars.keys().filter(|name| !is_result(name)).count() >= limit;
 
-pub type Context = HashMap<String, Expr>;
+        match self.limits.max_context_size {
+            Some(limit) if !is_result(&name) && !self.vars.contains_key(&name) && full(limit) => {
+                Err(ExprError::ContextFull { limit })
+            }
+            _ => {
+                self.insert(name, expr);
+                Ok(())
+            }
+        }
+    }
+
+    /// Starts counting the steps and time of a new evaluation
+    fn start(&mut self) {
+        self.steps = Arc::default();
+        self.deadline = Deadline::after(self.limits.timeout);
+        self.programs = Arc::default();
+    }
+
+    /// Deadline of the evaluation going on
+    pub(crate) fn deadline(&self) -> Deadline {
+        self.deadline
+    }
+
+    /// Counts one step, failing once past the step limit or the deadline
+    fn step(&self) -> Result<(), ExprError> {
+        let steps = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if let Some(limit) = self.limits.max_steps {
+            if steps > limit {
+                return Err(ExprError::StepLimit { limit });
+            }
+        }
+
+        self.deadline.check()
+    }
+
+    /// Makes `func` callable by its name, replacing any native function
+    /// registered under it before
+    pub fn register(&mut self, func: impl NativeFunction + 'static) {
+        self.natives.insert(func.name().to_lowercase(), Arc::new(func));
+        self.programs = Arc::default();
+    }
+
+    /// Names of the variables and functions defined so far
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.keys().chain(self.natives.keys()).map(String::as_str)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub enum Expr {
     Real(f64),
+    Integer(BigInt),
+    Rational(BigRational),
+    BigFloat(BigFloat),
     Complex(f64, f64),
+    Quantity(Quantity),
     Var(String),
+    /// Name where a unit is expected, after a number or on the right of `to`
+    Unit(String),
     Lambda(Vec<String>, Box<Expr>),
     Call(String, Vec<Expr>),
+    Vector(Vec<f64>),
     Matrix(Vec<Vec<f64>>),
+    Tuple(Vec<Expr>),
+    Plot(plot::Plot),
+    Index(Box<Expr>, Vec<Slice>),
     Neg(Box<Expr>),
+    Factorial(Box<Expr>),
     Add(Box<Expr>, Box<Expr>),
     Mul(Box<Expr>, Box<Expr>),
     Div(Box<Expr>, Box<Expr>),
     Rem(Box<Expr>, Box<Expr>),
     Pow(Box<Expr>, Box<Expr>),
     MatrixMul(Box<Expr>, Box<Expr>),
+    Solve(Box<Expr>, Box<Expr>),
+    Convert(Box<Expr>, Box<Expr>),
     AssignVar(String, Box<Expr>),
     AssignFunc(Box<Expr>, Box<Expr>),
 }
 
+/// One axis of `m[...]`
+#[derive(Clone, Debug)]
+pub enum Slice {
+    At(Expr),
+    /// `from:to` with `to` excluded, missing ends mean the edges
+    Range(Option<Expr>, Option<Expr>),
+}
+
 #[derive(Fail, Debug)]
 pub enum ExprError {
     #[fail(display = "parse error: {}", err)]
@@ -51,45 +224,132 @@ pub enum ExprError {
     BadArgsCount { func: String },
     #[fail(display = "recursion is too deep :c")]
     RecursiveRecursion,
+    #[fail(display = "unknown setting or bad value: {} {}", name, value)]
+    BadSetting { name: String, value: String },
+    #[fail(display = "matrix is singular, the system has no unique solution")]
+    SingularMatrix,
+    #[fail(display = "system is inconsistent, it has no solution")]
+    InconsistentSystem,
+    #[fail(display = "index {} is out of bounds for size {}", index, len)]
+    IndexOutOfBounds { index: i64, len: usize },
+    #[fail(display = "{} did not converge", func)]
+    NoConvergence { func: String },
+    #[fail(display = "could not write {}: {}", path, err)]
+    WriteError { path: String, err: String },
+    #[fail(display = "cannot integrate symbolically: {}", expr)]
+    NoAntiderivative { expr: String },
+    #[fail(display = "incompatible units: {} and {}", left, right)]
+    UnitMismatch { left: String, right: String },
+    #[fail(display = "expected {}, got {}", expected, found)]
+    TypeMismatch { expected: String, found: String },
+    #[fail(display = "evaluation takes more than {} steps", limit)]
+    StepLimit { limit: u64 },
+    #[fail(display = "matrix would have more than {} items", limit)]
+    MatrixTooBig { limit: usize },
+    #[fail(display = "number would have more than {} digits", limit)]
+    NumberTooBig { limit: usize },
+    #[fail(display = "evaluation takes longer than {} ms", ms)]
+    Timeout { ms: u64 },
+    #[fail(display = "cannot define more than {} variables and functions", limit)]
+    ContextFull { limit: usize },
 }
 
 impl Expr {
-    pub fn run(self, context: &mut Context, level: usize) -> Result<Expr, ExprError> {
+    /// Value of the expression, assignments change `context`
+    pub fn eval(self, context: &mut Context) -> Result<Expr, ExprError> {
+        context.start();
+        self.run(context, 0)
+    }
+
+    /// `eval` from inside another evaluation `level` calls deep
+    pub(crate) fn run(self, context: &mut Context, level: usize) -> Result<Expr, ExprError> {
         let level = level + 1;
 
         if level > 100 {
             return Err(ExprError::RecursiveRecursion);
         }
 
+        context.step()?;
+        let res = self.node(context, level)?;
+        context.limits.check(&res)?;
+
+        Ok(res)
+    }
+
+    /// `run` of the top node, the ones under it go through `run`
+    fn node(self, context: &mut Context, level: usize) -> Result<Expr, ExprError> {
         match self {
             Expr::Real(_) => Ok(self),
-            Expr::Complex(_, _) => Ok(self),
-            Expr::Var(name) => match context.get(&name) {
-                Some(expr) => Ok(expr.clone()),
-                None => Err(ExprError::UndefinedVariable { name: name.clone() }),
+            Expr::Integer(ref x) if !context.settings.exact => Ok(Expr::Real(int_to_f64(x))),
+            Expr::Integer(_) => Ok(self),
+            Expr::Rational(ref x) if !context.settings.exact => Ok(Expr::Real(to_f64(x))),
+            Expr::Rational(_) => Ok(self),
+            Expr::BigFloat(x) => match context.settings.precision {
+                Some(precision) => Ok(Expr::BigFloat(x.with_precision(precision))),
+                None => Ok(Expr::Real(x.to_f64())),
             },
-            Expr::Lambda(_args, _expr) => unimplemented!(),
+            Expr::Complex(_, _) => Ok(self),
+            Expr::Quantity(_) => Ok(self),
+            Expr::Var(name) => context.lookup(&name),
+            Expr::Unit(name) => context.lookup_unit(&name),
+            Expr::Lambda(..) => Ok(self),
+
+            // Builtins that need their arguments as written
+            Expr::Call(ref name, _) if builtins::SPECIAL.contains(&name.as_str())
+                && context.get(name).is_none()
+                && context.native(name).is_none() =>
+            {
+                match self {
+                    Expr::Call(name, args) => builtins::special(&name, args, context, level),
+                    _ => unreachable!(),
+                }
+            }
 
             Expr::Call(name, args) => {
-                if let Some(Expr::Lambda(names, expr)) = context.get(&name) {
-                    if names.len() != args.len() {
-                        return Err(ExprError::BadArgsCount { func: name.clone() });
-                    }
-
-                    let mut context = context.clone();
+                let mut values = vec![];
 
-                    for (arg_name, arg_val) in names.iter().zip(args) {
-                        context.insert(arg_name.to_string(), arg_val);
-                    }
+                for arg in args {
+                    values.push(arg.run(context, level)?);
+                }
 
-                    Ok(expr.clone().run(&mut context, level)?)
-                } else {
-                    Err(ExprError::UndefinedVariable { name: name.clone() })
+                match (context.get(&name), context.native(&name)) {
+                    (Some(f @ Expr::Lambda(..)), _) => f.apply(&name, values, context, level),
+                    (_, Some(func)) => native::call(&**func, &values),
+                    _ => builtins::call(&name, values, context, level),
                 }
             }
 
+            Expr::Vector(_) => Ok(self),
             Expr::Matrix(_) => Ok(self),
+            Expr::Tuple(_) => Ok(self),
+            Expr::Plot(_) => Ok(self),
+            Expr::Index(box x, slices) => {
+                let x = x.run(context, level)?;
+                let mut axes = vec![];
+
+                for slice in slices {
+                    axes.push(match slice {
+                        Slice::At(x) => {
+                            matrix::Axis::At(matrix::position(&x.run(context, level)?)?)
+                        }
+                        Slice::Range(from, to) => {
+                            let mut ends = [None, None];
+
+                            for (end, x) in ends.iter_mut().zip(vec![from, to]) {
+                                if let Some(x) = x {
+                                    *end = Some(matrix::position(&x.run(context, level)?)?);
+                                }
+                            }
+
+                            matrix::Axis::Span(ends[0], ends[1])
+                        }
+                    });
+                }
+
+                matrix::index(x, axes)
+            }
             Expr::Neg(box x) => x.run(context, level)?.neg(context),
+            Expr::Factorial(box x) => x.run(context, level)?.factorial(context),
             Expr::Add(box x, box y) => x.run(context, level)?.add(y.run(context, level)?, context),
             Expr::Mul(box x, box y) => x.run(context, level)?.mul(y.run(context, level)?, context),
             Expr::Div(box x, box y) => x.run(context, level)?.div(y.run(context, level)?, context),
@@ -98,9 +358,17 @@ impl Expr {
             Expr::MatrixMul(box x, box y) => {
                 x.run(context, level)?.mmul(y.run(context, level)?, context)
             }
+            Expr::Solve(box x, box y) => {
+                x.run(context, level)?.solve(y.run(context, level)?, context)
+            }
+            Expr::Convert(box x, box unit) => {
+                let name = units::unit_name(&unit);
+
+                units::convert(x.run(context, level)?, unit.run(context, level)?, name)
+            }
             Expr::AssignVar(name, box expr) => {
                 let expr = expr.run(context, level)?;
-                context.insert(name, expr.clone());
+                context.define(name, expr.clone())?;
 
                 Ok(expr)
             }
@@ -119,7 +387,8 @@ impl Expr {
                     }
 
                     // dbg!((&name, &new_args, &expr));
-                    context.insert(name, Expr::Lambda(new_args, box expr.clone()));
+                    let expr = native::fold(expr, context);
+                    context.define(name, Expr::Lambda(new_args, box expr.clone()))?;
 
                     Ok(expr)
                 } else {
@@ -134,7 +403,12 @@ impl Expr {
     pub fn neg(self, _context: &mut Context) -> Result<Expr, ExprError> {
         match self {
             Expr::Real(x) => Ok(Expr::Real(-x)),
+            Expr::Integer(x) => Ok(Expr::Integer(-x)),
+            Expr::Rational(x) => Ok(Expr::Rational(-x)),
+            Expr::BigFloat(x) => Ok(Expr::BigFloat(x.neg())),
             Expr::Complex(x, y) => Ok(Expr::Complex(-x, -y)),
+            Expr::Quantity(x) => Ok(x.scale(-1.0)),
+            Expr::Vector(x) => Ok(Expr::Vector(x.iter().map(|x| -x).collect())),
             Expr::Matrix(rows) => Ok(Expr::Matrix(
                 rows.iter()
                     .map(|row| row.iter().map(|x| -x).collect())
@@ -146,13 +420,59 @@ impl Expr {
         }
     }
 
+    pub fn factorial(self, context: &mut Context) -> Result<Expr, ExprError> {
+        match self {
+            Expr::Integer(ref n) if !n.is_negative() => match n.to_u64() {
+                Some(n) => {
+                    // Stirling, log10(n!) is about n * log10(n / e)
+                    let n_f64 = n as f64;
+                    context.limits.check_digits(n_f64 * (n_f64 / std::f64::consts::E).log10())?;
+
+                    let deadline = context.deadline();
+                    let mut res = BigInt::one();
+                    for i in 1..=n {
+                        deadline.check()?;
+                        res *= i;
+                    }
+
+                    Ok(Expr::Integer(res))
+                }
+                None => Err(ExprError::CalcError {
+                    err: "factorial argument is too big".into(),
+                }),
+            },
+            _ => Err(ExprError::CalcError {
+                err: "factorial works only on natural numbers".into(),
+            }),
+        }
+    }
+
     pub fn add(self, other: Expr, _context: &mut Context) -> Result<Expr, ExprError> {
-        match (self, other) {
+        match coerce(self, other) {
             (Expr::Real(x), Expr::Real(y)) => Ok(Expr::Real(x + y)),
+            (Expr::Integer(x), Expr::Integer(y)) => Ok(Expr::Integer(x + y)),
+            (Expr::Rational(x), Expr::Rational(y)) => Ok(rational(x + y)),
+            (Expr::BigFloat(x), Expr::BigFloat(y)) => Ok(Expr::BigFloat(x.add(&y))),
             (Expr::Complex(a, b), Expr::Complex(x, y)) => Ok(Expr::Complex(a + x, b + y)),
             (Expr::Real(a), Expr::Complex(x, y)) | (Expr::Complex(x, y), Expr::Real(a)) => {
                 Ok(Expr::Complex(a + x, y))
             }
+            (Expr::Quantity(x), Expr::Quantity(y)) => x.add(y),
+            (Expr::Quantity(x), Expr::Real(_)) | (Expr::Real(_), Expr::Quantity(x)) => {
+                Err(units::mismatch(&x.dim, &[0; 7]))
+            }
+            (Expr::Vector(x), Expr::Vector(y)) => {
+                if x.len() != y.len() {
+                    return Err(ExprError::CalcError {
+                        err: "vectors have different sizes".into(),
+                    });
+                }
+
+                Ok(Expr::Vector(x.iter().zip(&y).map(|(x, y)| x + y).collect()))
+            }
+            (Expr::Real(y), Expr::Vector(x)) | (Expr::Vector(x), Expr::Real(y)) => {
+                Ok(Expr::Vector(x.iter().map(|x| x + y).collect()))
+            }
             (Expr::Real(y), Expr::Matrix(rows)) | (Expr::Matrix(rows), Expr::Real(y)) => {
                 Ok(Expr::Matrix(
                     rows.iter()
@@ -167,14 +487,27 @@ impl Expr {
     }
 
     pub fn mul(self, other: Expr, _context: &mut Context) -> Result<Expr, ExprError> {
-        match (self, other) {
+        match coerce(self, other) {
             (Expr::Real(x), Expr::Real(y)) => Ok(Expr::Real(x * y)),
+            (Expr::Integer(x), Expr::Integer(y)) => Ok(Expr::Integer(x * y)),
+            (Expr::Rational(x), Expr::Rational(y)) => Ok(rational(x * y)),
+            (Expr::BigFloat(x), Expr::BigFloat(y)) => Ok(Expr::BigFloat(x.mul(&y))),
             (Expr::Complex(a, b), Expr::Complex(x, y)) => {
                 Ok(Expr::Complex(a * x - b * y, a * y + b * x))
             }
             (Expr::Real(a), Expr::Complex(x, y)) | (Expr::Complex(x, y), Expr::Real(a)) => {
                 Ok(Expr::Complex(a * x, a * y))
             }
+            (Expr::Quantity(x), Expr::Quantity(y)) => x.mul(y),
+            (Expr::Quantity(x), Expr::Real(y)) | (Expr::Real(y), Expr::Quantity(x)) => {
+                Ok(x.scale(y))
+            }
+            (Expr::Real(y), Expr::Vector(x)) | (Expr::Vector(x), Expr::Real(y)) => {
+                Ok(Expr::Vector(x.iter().map(|x| x * y).collect()))
+            }
+            (Expr::Vector(_), Expr::Vector(_)) => Err(ExprError::CalcError {
+                err: "vectors multiply with dot() or cross()".into(),
+            }),
             (Expr::Real(y), Expr::Matrix(rows)) | (Expr::Matrix(rows), Expr::Real(y)) => {
                 Ok(Expr::Matrix(
                     rows.iter()
@@ -189,21 +522,30 @@ impl Expr {
     }
 
     pub fn div(self, other: Expr, _context: &mut Context) -> Result<Expr, ExprError> {
-        match (self, other) {
+        match coerce(self, other) {
             (_, Expr::Real(y)) if y == 0.0 => Err(ExprError::DivisionByZero),
+            (_, Expr::Integer(ref y)) if y.is_zero() => Err(ExprError::DivisionByZero),
+            (_, Expr::Rational(ref y)) if y.is_zero() => Err(ExprError::DivisionByZero),
+            (_, Expr::BigFloat(ref y)) if y.is_zero() => Err(ExprError::DivisionByZero),
             (Expr::Real(x), Expr::Real(y)) => Ok(Expr::Real(x / y)),
+            (Expr::Integer(x), Expr::Integer(y)) => Ok(rational(BigRational::new(x, y))),
+            (Expr::Rational(x), Expr::Rational(y)) => Ok(rational(x / y)),
+            (Expr::BigFloat(x), Expr::BigFloat(y)) => Ok(Expr::BigFloat(x.div(&y))),
             (Expr::Complex(a, b), Expr::Complex(x, y)) => {
-                let a = (a, b);
-                let b = (x, y);
-                let conj = (b.0, -b.1);
+                // Times the conjugate of the divisor on both sides
+                let bot = x * x + y * y;
 
-                let top = (a.0 * conj.0 - a.1 * conj.1, a.0 * conj.1 + a.1 * conj.0);
-                let bot = (b.0 * conj.0 - b.1 * conj.1, b.0 * conj.1 + b.1 * conj.0);
-
-                assert_eq!(bot.1, 0.0);
+                if bot == 0.0 {
+                    return Err(ExprError::DivisionByZero);
+                }
 
-                Ok(Expr::Complex(top.0 / bot.0, top.1 / bot.0))
+                Ok(Expr::Complex((a * x + b * y) / bot, (b * x - a * y) / bot))
             }
+            (_, Expr::Quantity(ref y)) if y.value == 0.0 => Err(ExprError::DivisionByZero),
+            (Expr::Quantity(x), Expr::Quantity(y)) => x.div(y),
+            (Expr::Quantity(x), Expr::Real(y)) => Ok(x.scale(1.0 / y)),
+            (Expr::Real(x), Expr::Quantity(y)) => Ok(y.recip().scale(x)),
+            (Expr::Vector(x), Expr::Real(y)) => Ok(Expr::Vector(x.iter().map(|x| x / y).collect())),
             (Expr::Matrix(rows), Expr::Real(y)) => Ok(Expr::Matrix(
                 rows.iter()
                     .map(|row| row.iter().map(|x| x / y).collect())
@@ -216,9 +558,17 @@ impl Expr {
     }
 
     pub fn rem(self, other: Expr, _context: &mut Context) -> Result<Expr, ExprError> {
-        match (self, other) {
+        match coerce(self, other) {
             (_, Expr::Real(y)) if y == 0.0 => Err(ExprError::DivisionByZero),
+            (_, Expr::Integer(ref y)) if y.is_zero() => Err(ExprError::DivisionByZero),
+            (_, Expr::Rational(ref y)) if y.is_zero() => Err(ExprError::DivisionByZero),
+            (_, Expr::BigFloat(ref y)) if y.is_zero() => Err(ExprError::DivisionByZero),
             (Expr::Real(x), Expr::Real(y)) => Ok(Expr::Real(x % y)),
+            (Expr::Integer(x), Expr::Integer(y)) => Ok(Expr::Integer(x % y)),
+            (Expr::Rational(x), Expr::Rational(y)) => Ok(rational(x % y)),
+            (Expr::BigFloat(x), Expr::BigFloat(y)) => Ok(Expr::BigFloat(x.rem(&y))),
+            (_, Expr::Quantity(ref y)) if y.value == 0.0 => Err(ExprError::DivisionByZero),
+            (Expr::Quantity(x), Expr::Quantity(y)) => x.rem(y),
             (Expr::Matrix(rows), Expr::Real(y)) => Ok(Expr::Matrix(
                 rows.iter()
                     .map(|row| row.iter().map(|x| x % y).collect())
@@ -230,9 +580,54 @@ impl Expr {
         }
     }
 
-    pub fn pow(self, other: Expr, _context: &mut Context) -> Result<Expr, ExprError> {
-        match (self, other) {
+    pub fn pow(self, other: Expr, context: &mut Context) -> Result<Expr, ExprError> {
+        match coerce(self, other) {
             (Expr::Real(x), Expr::Real(y)) => Ok(Expr::Real(x.powf(y))),
+            (Expr::Integer(x), Expr::Integer(y)) => match y.to_u32() {
+                Some(n) => {
+                    context.limits.check_digits(limits::log10(&x) * f64::from(n))?;
+
+                    Ok(Expr::Integer(limits::pow(&x, n, context.deadline())?))
+                }
+                None => Expr::Rational(BigRational::from_integer(x))
+                    .pow(Expr::Rational(BigRational::from_integer(y)), context),
+            },
+            (Expr::Rational(x), Expr::Rational(y)) => {
+                match (y.is_integer(), y.to_integer().to_i32()) {
+                    (true, Some(n)) if n < 0 && x.is_zero() => Err(ExprError::DivisionByZero),
+                    (true, Some(n)) => {
+                        let log10 = limits::log10(x.numer()).max(limits::log10(x.denom()));
+                        context.limits.check_digits(log10 * f64::from(n).abs())?;
+
+                        let (x, n) = if n < 0 {
+                            (x.recip(), -i64::from(n) as u32)
+                        } else {
+                            (x, n as u32)
+                        };
+                        let deadline = context.deadline();
+                        let numer = limits::pow(x.numer(), n, deadline)?;
+                        let denom = limits::pow(x.denom(), n, deadline)?;
+
+                        Ok(rational(BigRational::new_raw(numer, denom)))
+                    }
+                    // Irrational in general, no way to stay exact
+                    _ => match context.settings.precision {
+                        Some(precision) => Expr::BigFloat(BigFloat::from_rational(&x, precision))
+                            .pow(Expr::BigFloat(BigFloat::from_rational(&y, precision)), context),
+                        None => Ok(Expr::Real(to_f64(&x).powf(to_f64(&y)))),
+                    },
+                }
+            }
+            (Expr::BigFloat(ref x), Expr::BigFloat(ref y)) if x.is_zero() && y.is_negative() => {
+                Err(ExprError::DivisionByZero)
+            }
+            (Expr::BigFloat(x), Expr::BigFloat(y)) => match x.pow(&y, context.deadline())? {
+                Some(res) => Ok(Expr::BigFloat(res)),
+                None => Err(ExprError::CalcError {
+                    err: "pow result is not a real number or too big".into(),
+                }),
+            },
+            (Expr::Quantity(x), Expr::Real(y)) => x.pow(y),
             (Expr::Matrix(rows), Expr::Real(y)) => Ok(Expr::Matrix(
                 rows.iter()
                     .map(|row| row.iter().map(|x| x.powf(y)).collect())
@@ -244,23 +639,37 @@ impl Expr {
         }
     }
 
-    pub fn mmul(self, other: Expr, _context: &mut Context) -> Result<Expr, ExprError> {
+    pub fn mmul(self, other: Expr, context: &mut Context) -> Result<Expr, ExprError> {
         match (self, other) {
+            // A vector is a column on the right and a row on the left
+            (x @ Expr::Matrix(_), Expr::Vector(y)) => {
+                x.mmul(Expr::Matrix(y.into_iter().map(|y| vec![y]).collect()), context)?
+                    .into_vector()
+            }
+            (Expr::Vector(x), y @ Expr::Matrix(_)) => {
+                Expr::Matrix(vec![x]).mmul(y, context)?.into_vector()
+            }
             (Expr::Matrix(m1), Expr::Matrix(m2)) => {
-                if m1[0].len() != m2.len() {
+                // m1 => n * m
+                // m2 => m * p
+
+                let n = m1.len();
+                let m = m1.first().map_or(0, Vec::len);
+                let p = m2.first().map_or(0, Vec::len);
+
+                if n == 0
+                    || m2.is_empty()
+                    || m != m2.len()
+                    || m1.iter().any(|row| row.len() != m)
+                    || m2.iter().any(|row| row.len() != p)
+                {
                     return Err(ExprError::CalcError {
                         err: "matrices have invalid size".into(),
                     });
                 }
+                context.limits.check_size(n.checked_mul(p))?;
 
-                let mut res = vec![vec![0.0; m2[0].len()]; m1.len()];
-
-                // m1 => n * m
-                // m2 => m * p
-
-                let n = m1.len();
-                let m = m1[0].len();
-                let p = m2[0].len();
+                let mut res = vec![vec![0.0; p]; n];
 
                 for i in 0..n {
                     for j in 0..p {
@@ -277,56 +686,163 @@ impl Expr {
     }
 }
 
-impl fmt::Display for Expr {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Expr::Real(ref x) => write!(f, "{}", x),
-            Expr::Complex(ref x, ref y) => {
-                if *y < 0.0 {
-                    write!(f, "{} - {}i", x, -y)
-                } else {
-                    write!(f, "{} + {}i", x, y)
-                }
+impl Expr {
+    /// Runs the `Lambda` bound to `name` on already evaluated arguments,
+    /// compiled once per evaluation
+    pub(crate) fn apply(
+        &self,
+        name: &str,
+        values: Vec<Expr>,
+        context: &Context,
+        level: usize,
+    ) -> Result<Expr, ExprError> {
+        // Taken out while it runs, a recursive call through the tree walker
+        // compiles its own
+        let cached = context.programs.lock().ok().and_then(|mut programs| programs.remove(name));
+        let mut program = match cached {
+            Some(program) => program,
+            None => compile::Program::new(self, name, context)?,
+        };
+        let result = program.call(values, level);
+
+        if let Ok(mut programs) = context.programs.lock() {
+            programs.insert(name.to_string(), program);
+        }
+        result
+    }
+
+    /// `A \ b`, `b` is a vector or a matrix of right sides
+    pub fn solve(self, other: Expr, _context: &mut Context) -> Result<Expr, ExprError> {
+        match (self, other) {
+            (Expr::Matrix(a), Expr::Vector(b)) => {
+                let b: Vec<Vec<f64>> = b.into_iter().map(|b| vec![b]).collect();
+
+                Expr::Matrix(linalg::solve(&a, &b)?).into_vector()
             }
-            Expr::Var(ref x) => write!(f, "{}", x),
-            Expr::Lambda(ref args, ref expr) => write!(f, "({}) => {}", args.join(", "), expr),
-            Expr::Call(ref func, ref _args) => write!(f, "({})({})", func, "..."),
-            Expr::Matrix(ref x) => write!(f, "{:?}", x),
-            Expr::Neg(ref x) => write!(f, "-{}", x),
-            Expr::Add(ref x, ref y) => write!(f, "({} + {})", x, y),
-            Expr::Mul(ref x, ref y) => write!(f, "({} * {})", x, y),
-            Expr::Div(ref x, ref y) => write!(f, "({} / {})", x, y),
-            Expr::Rem(ref x, ref y) => write!(f, "({} % {})", x, y),
-            Expr::Pow(ref x, ref y) => write!(f, "({} ^ {})", x, y),
-            Expr::MatrixMul(ref x, ref y) => write!(f, "({} ** {})", x, y),
-            Expr::AssignVar(ref name, ref val) => write!(f, "{} = {}", name, val),
-            Expr::AssignFunc(_, _) => {
-                // write!(f, "{}({}) = {}", name, args.join(", "), body)
-                write!(f, "(function)",)
+            (Expr::Matrix(a), Expr::Matrix(b)) => Ok(Expr::Matrix(linalg::solve(&a, &b)?)),
+            _ => Err(ExprError::CalcError {
+                err: "solving works only on a matrix and a vector or matrix".into(),
+            }),
+        }
+    }
+
+    /// Row or column matrix as a vector
+    pub fn into_vector(self) -> Result<Expr, ExprError> {
+        match self {
+            Expr::Vector(_) => Ok(self),
+            Expr::Matrix(ref rows) if rows.len() == 1 => Ok(Expr::Vector(rows[0].clone())),
+            Expr::Matrix(ref rows) if rows.iter().all(|row| row.len() == 1) => {
+                Ok(Expr::Vector(rows.iter().map(|row| row[0]).collect()))
             }
+            _ => Err(ExprError::CalcError {
+                err: "only a row or column matrix can become a vector".into(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.formatted(&Settings::default()))
+    }
+}
+
+pub(crate) fn to_f64(x: &BigRational) -> f64 {
+    x.to_f64().unwrap_or(std::f64::NAN)
+}
+
+fn int_to_f64(x: &BigInt) -> f64 {
+    x.to_f64().unwrap_or(std::f64::NAN)
+}
+
+/// Rational result, turned back into `Integer` when the denominator is 1
+pub(crate) fn rational(x: BigRational) -> Expr {
+    if x.is_integer() {
+        Expr::Integer(x.to_integer())
+    } else {
+        Expr::Rational(x)
+    }
+}
+
+fn inexact(x: Expr) -> Expr {
+    match x {
+        Expr::Integer(ref x) => Expr::Real(int_to_f64(x)),
+        Expr::Rational(ref x) => Expr::Real(to_f64(x)),
+        Expr::BigFloat(ref x) => Expr::Real(x.to_f64()),
+        x => x,
+    }
+}
+
+/// Exact numbers stay exact only among themselves, next to a `BigFloat` they
+/// take its precision, mixed with anything else they turn into `Real`
+fn coerce(x: Expr, y: Expr) -> (Expr, Expr) {
+    match (x, y) {
+        (x @ Expr::BigFloat(_), y @ Expr::BigFloat(_)) => (x, y),
+        (Expr::BigFloat(x), Expr::Integer(y)) => {
+            let y = BigFloat::from_integer(y, x.precision());
+            (Expr::BigFloat(x), Expr::BigFloat(y))
+        }
+        (Expr::Integer(x), Expr::BigFloat(y)) => {
+            let x = BigFloat::from_integer(x, y.precision());
+            (Expr::BigFloat(x), Expr::BigFloat(y))
+        }
+        (Expr::BigFloat(x), Expr::Rational(y)) => {
+            let y = BigFloat::from_rational(&y, x.precision());
+            (Expr::BigFloat(x), Expr::BigFloat(y))
         }
+        (Expr::Rational(x), Expr::BigFloat(y)) => {
+            let x = BigFloat::from_rational(&x, y.precision());
+            (Expr::BigFloat(x), Expr::BigFloat(y))
+        }
+        (x @ Expr::Integer(_), y @ Expr::Integer(_)) => (x, y),
+        (x @ Expr::Rational(_), y @ Expr::Rational(_)) => (x, y),
+        (Expr::Integer(x), y @ Expr::Rational(_)) => {
+            (Expr::Rational(BigRational::from_integer(x)), y)
+        }
+        (x @ Expr::Rational(_), Expr::Integer(y)) => {
+            (x, Expr::Rational(BigRational::from_integer(y)))
+        }
+        (x, y) => (inexact(x), inexact(y)),
     }
 }
 
 fn validate_matrix(expr: &Expr) -> bool {
     match expr {
         Expr::Real(_) => true,
+        Expr::Integer(_) => true,
+        Expr::Rational(_) => true,
+        Expr::BigFloat(_) => true,
         Expr::Complex(_, _) => true,
-        Expr::Var(_) => true,
+        Expr::Quantity(_) => true,
+        Expr::Var(_) | Expr::Unit(_) => true,
         Expr::Lambda(_, ref expr) => validate_matrix(expr),
         Expr::Call(_, ref args) => args.iter().all(|expr| validate_matrix(expr)),
-        Expr::Matrix(ref x) => {
-            let len = x[0].len();
-
-            x.iter().skip(1).all(|v| v.len() == len)
+        Expr::Vector(_) => true,
+        Expr::Matrix(ref x) => match x.first() {
+            Some(first) => x.iter().skip(1).all(|v| v.len() == first.len()),
+            None => true,
+        },
+        Expr::Tuple(ref items) => items.iter().all(|expr| validate_matrix(expr)),
+        Expr::Plot(_) => true,
+        Expr::Index(ref x, ref slices) => {
+            validate_matrix(x)
+                && slices.iter().all(|slice| match slice {
+                    Slice::At(ref x) => validate_matrix(x),
+                    Slice::Range(ref from, ref to) => {
+                        from.iter().chain(to).all(|expr| validate_matrix(expr))
+                    }
+                })
         }
         Expr::Neg(ref x) => validate_matrix(x),
+        Expr::Factorial(ref x) => validate_matrix(x),
         Expr::Add(ref x, ref y) => validate_matrix(x) && validate_matrix(y),
         Expr::Mul(ref x, ref y) => validate_matrix(x) && validate_matrix(y),
         Expr::Div(ref x, ref y) => validate_matrix(x) && validate_matrix(y),
         Expr::Rem(ref x, ref y) => validate_matrix(x) && validate_matrix(y),
         Expr::Pow(ref x, ref y) => validate_matrix(x) && validate_matrix(y),
         Expr::MatrixMul(ref x, ref y) => validate_matrix(x) && validate_matrix(y),
+        Expr::Solve(ref x, ref y) => validate_matrix(x) && validate_matrix(y),
+        Expr::Convert(ref x, ref unit) => validate_matrix(x) && validate_matrix(unit),
         Expr::AssignVar(_, ref val) => validate_matrix(val),
         Expr::AssignFunc(_, ref body) => validate_matrix(body),
     }
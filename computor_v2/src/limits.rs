@@ -0,0 +1,137 @@
+use crate::{Expr, ExprError};
+
+use num_bigint::BigInt;
+use num_traits::One;
+use std::time::{Duration, Instant};
+
+/// Digits of exact numbers when no other limit is set, enough for `25000!`
+pub const DEFAULT_MAX_DIGITS: usize = 100_000;
+
+/// Resources one evaluation may use, for running untrusted input. `None`
+/// means no limit, which is the default for all of them but `max_digits`
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// Expressions evaluated, counting the ones inside function calls
+    pub max_steps: Option<u64>,
+    /// Items of a vector or a matrix
+    pub max_matrix_size: Option<usize>,
+    /// Decimal digits of exact numbers and `BigFloat`s
+    pub max_digits: Option<usize>,
+    /// Wall-clock time
+    pub timeout: Option<Duration>,
+    /// Variables and functions defined
+    pub max_context_size: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_steps: None,
+            max_matrix_size: None,
+            max_digits: Some(DEFAULT_MAX_DIGITS),
+            timeout: None,
+            max_context_size: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Fails for a vector or matrix of `size` items, `None` meaning it is too
+    /// big to even count
+    pub(crate) fn check_size(&self, size: Option<usize>) -> Result<(), ExprError> {
+        match (size, self.max_matrix_size) {
+            (Some(size), Some(limit)) if size > limit => Err(ExprError::MatrixTooBig { limit }),
+            (None, Some(limit)) => Err(ExprError::MatrixTooBig { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails for a number of about `digits` decimal digits
+    pub(crate) fn check_digits(&self, digits: f64) -> Result<(), ExprError> {
+        match self.max_digits {
+            Some(limit) if digits > limit as f64 => Err(ExprError::NumberTooBig { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails for values over the size limits
+    pub(crate) fn check(&self, e: &Expr) -> Result<(), ExprError> {
+        match e {
+            Expr::Integer(ref x) => self.check_digits(log10(x) + 1.0),
+            Expr::Rational(ref x) => {
+                self.check_digits(log10(x.numer()).max(log10(x.denom())) + 1.0)
+            }
+            Expr::BigFloat(ref x) => self.check_digits(x.precision() as f64),
+            Expr::Vector(ref x) => self.check_size(Some(x.len())),
+            Expr::Matrix(ref x) => self.check_size(Some(x.iter().map(Vec::len).sum())),
+            Expr::Tuple(ref items) => items.iter().try_for_each(|x| self.check(x)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// When the evaluation going on has to stop, for loops that run long between
+/// two steps. The default one never passes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Deadline(Option<(Instant, Duration)>);
+
+impl Deadline {
+    /// `timeout` from now
+    pub(crate) fn after(timeout: Option<Duration>) -> Deadline {
+        Deadline(timeout.map(|timeout| (Instant::now() + timeout, timeout)))
+    }
+
+    /// Fails once past the deadline
+    pub fn check(self) -> Result<(), ExprError> {
+        match self.0 {
+            Some((at, timeout)) if Instant::now() > at => Err(ExprError::Timeout {
+                ms: timeout.as_millis() as u64,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `x^n` by squaring, checking `deadline` between the multiplications
+pub(crate) fn pow(x: &BigInt, mut n: u32, deadline: Deadline) -> Result<BigInt, ExprError> {
+    let mut base = x.clone();
+    let mut res = BigInt::one();
+
+    while n > 0 {
+        deadline.check()?;
+        if n & 1 == 1 {
+            res *= &base;
+        }
+        n >>= 1;
+        if n > 0 {
+            base = &base * &base;
+        }
+    }
+
+    Ok(res)
+}
+
+/// `x^e mod m` for `e >= 0`, checking `deadline` between the multiplications
+pub(crate) fn modpow(
+    x: &BigInt,
+    e: &BigInt,
+    m: &BigInt,
+    deadline: Deadline,
+) -> Result<BigInt, ExprError> {
+    let mut res = BigInt::one() % m;
+
+    for i in (0..e.bits()).rev() {
+        deadline.check()?;
+        res = &res * &res % m;
+        if e.bit(i) {
+            res = res * x % m;
+        }
+    }
+
+    Ok(res)
+}
+
+/// `log10(|x|)` rounded down to a bit, 0 for 0
+pub(crate) fn log10(x: &BigInt) -> f64 {
+    x.bits().saturating_sub(1) as f64 * std::f64::consts::LOG10_2
+}
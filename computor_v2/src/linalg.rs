@@ -0,0 +1,850 @@
+use crate::{Deadline, ExprError};
+
+use std::cmp::Ordering;
+
+pub type Matrix = Vec<Vec<f64>>;
+
+/// Complex number as `(re, im)`, only needed for eigenvectors
+type Complex = (f64, f64);
+
+const MAX_SWEEPS: usize = 100;
+
+pub fn identity(n: usize) -> Matrix {
+    (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect()
+}
+
+pub fn transpose(a: &[Vec<f64>]) -> Matrix {
+    let columns = a.first().map_or(0, |row| row.len());
+
+    (0..columns)
+        .map(|j| a.iter().map(|row| row[j]).collect())
+        .collect()
+}
+
+/// `P A = L U` with partial pivoting, as `(L, U, P)`
+pub fn lu(a: &[Vec<f64>]) -> Result<(Matrix, Matrix, Matrix), ExprError> {
+    let n = square(a, "lu")?;
+    let mut l = vec![vec![0.0; n]; n];
+    let mut u = a.to_vec();
+    let mut perm: Vec<usize> = (0..n).collect();
+
+    for k in 0..n {
+        let p = pivot(&u, k);
+
+        u.swap(k, p);
+        l.swap(k, p);
+        perm.swap(k, p);
+
+        // Singular, nothing to eliminate with
+        if u[k][k] == 0.0 {
+            continue;
+        }
+
+        for i in k + 1..n {
+            let f = u[i][k] / u[k][k];
+
+            l[i][k] = f;
+            for j in k..n {
+                u[i][j] -= f * u[k][j];
+            }
+        }
+    }
+
+    for (i, row) in l.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+
+    let p = perm
+        .iter()
+        .map(|&j| (0..n).map(|k| if k == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+
+    Ok((l, u, p))
+}
+
+/// `A = Q R` with Householder reflections, `R` gets a non-negative diagonal
+pub fn qr(a: &[Vec<f64>]) -> Result<(Matrix, Matrix), ExprError> {
+    let (m, n) = size(a, "qr")?;
+    let mut q = identity(m);
+    let mut r = a.to_vec();
+
+    for k in 0..std::cmp::min(m - 1, n) {
+        let v = match reflector(r[k..].iter().map(|row| row[k])) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        for j in 0..n {
+            let s: f64 = v.iter().enumerate().map(|(i, v)| v * r[k + i][j]).sum();
+
+            for (i, v) in v.iter().enumerate() {
+                r[k + i][j] -= 2.0 * s * v;
+            }
+        }
+
+        for row in q.iter_mut() {
+            let s: f64 = v.iter().enumerate().map(|(j, v)| row[k + j] * v).sum();
+
+            for (j, v) in v.iter().enumerate() {
+                row[k + j] -= 2.0 * s * v;
+            }
+        }
+    }
+
+    for (i, row) in r.iter_mut().enumerate() {
+        for x in row.iter_mut().take(i) {
+            *x = 0.0;
+        }
+    }
+
+    for k in 0..std::cmp::min(m, n) {
+        if r[k][k] < 0.0 {
+            for x in r[k].iter_mut() {
+                *x = -*x;
+            }
+            for row in q.iter_mut() {
+                row[k] = -row[k];
+            }
+        }
+    }
+
+    Ok((q, r))
+}
+
+/// `A x = b` by Gaussian elimination with partial pivoting, least squares
+/// when there are more equations than unknowns
+pub fn solve(a: &[Vec<f64>], b: &[Vec<f64>]) -> Result<Matrix, ExprError> {
+    let (m, n) = size(a, "solve")?;
+    let (rows, k) = size(b, "solve")?;
+
+    if rows != m {
+        return Err(ExprError::CalcError {
+            err: "right side must have as many rows as the matrix".into(),
+        });
+    }
+
+    if m > n {
+        return least_squares(a, b);
+    }
+
+    let tol = tolerance(a);
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    let mut rank = 0;
+
+    // Row echelon form, columns without a pivot are skipped
+    for col in 0..n {
+        if rank == m {
+            break;
+        }
+
+        let p = (rank..m).fold(rank, |p, i| if a[i][col].abs() > a[p][col].abs() { i } else { p });
+
+        if a[p][col].abs() <= tol {
+            continue;
+        }
+
+        a.swap(rank, p);
+        b.swap(rank, p);
+
+        for i in rank + 1..m {
+            let f = a[i][col] / a[rank][col];
+
+            for j in col..n {
+                a[i][j] -= f * a[rank][j];
+            }
+            for j in 0..k {
+                b[i][j] -= f * b[rank][j];
+            }
+        }
+
+        rank += 1;
+    }
+
+    // Rows left are `0 = b`
+    let b_tol = 1e-10 * b.iter().flatten().fold(1.0f64, |m, x| m.max(x.abs()));
+    if b[rank..].iter().flatten().any(|x| x.abs() > b_tol) {
+        return Err(ExprError::InconsistentSystem);
+    }
+
+    if rank < n {
+        return Err(ExprError::SingularMatrix);
+    }
+
+    Ok(back_substitute(&a, &b, n))
+}
+
+fn least_squares(a: &[Vec<f64>], b: &[Vec<f64>]) -> Result<Matrix, ExprError> {
+    let n = a[0].len();
+    let (q, r) = qr(a)?;
+    let tol = tolerance(a);
+
+    if (0..n).any(|i| r[i][i].abs() <= tol) {
+        return Err(ExprError::SingularMatrix);
+    }
+
+    Ok(back_substitute(&r, &product(&transpose(&q), b), n))
+}
+
+/// Solution of the upper triangular top `n` rows
+fn back_substitute(u: &[Vec<f64>], b: &[Vec<f64>], n: usize) -> Matrix {
+    let k = b[0].len();
+    let mut x = vec![vec![0.0; k]; n];
+
+    for i in (0..n).rev() {
+        for j in 0..k {
+            let s: f64 = (i + 1..n).map(|c| u[i][c] * x[c][j]).sum();
+
+            x[i][j] = (b[i][j] - s) / u[i][i];
+        }
+    }
+
+    x
+}
+
+/// `A = L L^T` for symmetric positive definite `A`
+pub fn chol(a: &[Vec<f64>]) -> Result<Matrix, ExprError> {
+    let n = square(a, "chol")?;
+    let not_spd = || ExprError::CalcError {
+        err: "chol works only on symmetric positive definite matrices".into(),
+    };
+
+    if !symmetric(a) {
+        return Err(not_spd());
+    }
+
+    let mut l = vec![vec![0.0; n]; n];
+
+    for j in 0..n {
+        let d = a[j][j] - (0..j).map(|k| l[j][k] * l[j][k]).sum::<f64>();
+
+        if d <= 0.0 {
+            return Err(not_spd());
+        }
+
+        l[j][j] = d.sqrt();
+
+        for i in j + 1..n {
+            let s: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+
+            l[i][j] = (a[i][j] - s) / l[j][j];
+        }
+    }
+
+    Ok(l)
+}
+
+/// Thin `A = U S V^T` with one-sided Jacobi rotations, singular values in decreasing order
+pub fn svd(a: &[Vec<f64>], deadline: Deadline) -> Result<(Matrix, Matrix, Matrix), ExprError> {
+    let (m, n) = size(a, "svd")?;
+
+    if m < n {
+        let (u, s, v) = svd(&transpose(a), deadline)?;
+
+        return Ok((v, s, u));
+    }
+
+    let mut u = a.to_vec();
+    let mut v = identity(n);
+    let mut converged = false;
+
+    for _ in 0..MAX_SWEEPS {
+        let mut rotated = false;
+
+        for p in 0..n {
+            deadline.check()?;
+
+            for q in p + 1..n {
+                let alpha: f64 = u.iter().map(|row| row[p] * row[p]).sum();
+                let beta: f64 = u.iter().map(|row| row[q] * row[q]).sum();
+                let gamma: f64 = u.iter().map(|row| row[p] * row[q]).sum();
+
+                if gamma == 0.0 || gamma.abs() <= 1e-15 * (alpha * beta).sqrt() {
+                    continue;
+                }
+
+                rotated = true;
+
+                let (c, s) = rotation(alpha, beta, gamma);
+                rotate_columns(&mut u, p, q, c, s);
+                rotate_columns(&mut v, p, q, c, s);
+            }
+        }
+
+        if !rotated {
+            converged = true;
+            break;
+        }
+    }
+
+    if !converged {
+        return Err(no_convergence("svd"));
+    }
+
+    let sigma: Vec<f64> = (0..n)
+        .map(|j| u.iter().map(|row| row[j] * row[j]).sum::<f64>().sqrt())
+        .collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| sigma[j].partial_cmp(&sigma[i]).unwrap_or(Ordering::Equal));
+
+    let tiny = sigma.iter().cloned().fold(0.0, f64::max) * 1e-14 * m as f64;
+    let mut columns: Vec<Option<Vec<f64>>> = order
+        .iter()
+        .map(|&j| {
+            if sigma[j] > tiny {
+                Some(u.iter().map(|row| row[j] / sigma[j]).collect())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    complete_basis(&mut columns, m);
+
+    let columns: Vec<Vec<f64>> = columns.into_iter().map(|c| c.unwrap_or_default()).collect();
+    let s = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| if i == j && sigma[order[i]] > tiny { sigma[order[i]] } else { 0.0 })
+                .collect()
+        })
+        .collect();
+    let v = v.iter().map(|row| order.iter().map(|&j| row[j]).collect()).collect();
+
+    Ok((transpose(&columns), s, v))
+}
+
+/// Eigenvalues in decreasing order and eigenvectors as columns. For a complex
+/// pair `a ± bi` the two columns hold the real and imaginary parts of the
+/// vector for `a + bi`, the other one is its conjugate
+pub fn eig(a: &[Vec<f64>], deadline: Deadline) -> Result<(Vec<Complex>, Matrix), ExprError> {
+    let n = square(a, "eig")?;
+
+    if symmetric(a) {
+        return jacobi(a, deadline);
+    }
+
+    let mut values = hqr(hessenberg(a, deadline)?, deadline)?;
+    values.sort_by(|x, y| {
+        (y.0, y.1)
+            .partial_cmp(&(x.0, x.1))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let mut columns = vec![];
+
+    for &(re, im) in values.iter() {
+        if im < 0.0 {
+            continue;
+        }
+
+        deadline.check()?;
+        let x = eigenvector(a, (re, im));
+
+        columns.push(x.iter().map(|x| x.0).collect());
+        if im > 0.0 {
+            columns.push(x.iter().map(|x| x.1).collect());
+        }
+    }
+
+    if columns.len() != n {
+        return Err(no_convergence("eig"));
+    }
+
+    Ok((values, transpose(&columns)))
+}
+
+/// Rotates pairs of symmetric off-diagonal elements to zero
+fn jacobi(a: &[Vec<f64>], deadline: Deadline) -> Result<(Vec<Complex>, Matrix), ExprError> {
+    let n = a.len();
+    let mut a = a.to_vec();
+    let mut v = identity(n);
+    let scale: f64 = a.iter().flatten().map(|x| x * x).sum();
+    let mut converged = false;
+
+    for _ in 0..MAX_SWEEPS {
+        let off: f64 = (0..n)
+            .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+
+        if off <= 1e-30 * scale {
+            converged = true;
+            break;
+        }
+
+        for p in 0..n {
+            deadline.check()?;
+
+            for q in p + 1..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+
+                let (c, s) = rotation(a[p][p], a[q][q], a[p][q]);
+                rotate_columns(&mut a, p, q, c, s);
+                rotate_rows(&mut a, p, q, c, s);
+                rotate_columns(&mut v, p, q, c, s);
+            }
+        }
+    }
+
+    if !converged {
+        return Err(no_convergence("eig"));
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[j][j].partial_cmp(&a[i][i]).unwrap_or(Ordering::Equal));
+
+    let values = order.iter().map(|&i| (a[i][i], 0.0)).collect();
+    let vectors = v.iter().map(|row| order.iter().map(|&j| row[j]).collect()).collect();
+
+    Ok((values, vectors))
+}
+
+/// Householder reduction to upper Hessenberg form, similar to `a`
+fn hessenberg(a: &[Vec<f64>], deadline: Deadline) -> Result<Matrix, ExprError> {
+    let n = a.len();
+    let mut h = a.to_vec();
+
+    for k in 0..n.saturating_sub(2) {
+        deadline.check()?;
+        let v = match reflector(h[k + 1..].iter().map(|row| row[k])) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        for j in 0..n {
+            let s: f64 = v.iter().enumerate().map(|(i, v)| v * h[k + 1 + i][j]).sum();
+
+            for (i, v) in v.iter().enumerate() {
+                h[k + 1 + i][j] -= 2.0 * s * v;
+            }
+        }
+
+        for row in h.iter_mut() {
+            let s: f64 = v.iter().enumerate().map(|(j, v)| row[k + 1 + j] * v).sum();
+
+            for (j, v) in v.iter().enumerate() {
+                row[k + 1 + j] -= 2.0 * s * v;
+            }
+        }
+    }
+
+    for (i, row) in h.iter_mut().enumerate() {
+        for x in row.iter_mut().take(i.saturating_sub(1)) {
+            *x = 0.0;
+        }
+    }
+
+    Ok(h)
+}
+
+/// Eigenvalues of an upper Hessenberg matrix with Francis double shift QR steps
+fn hqr(mut a: Matrix, deadline: Deadline) -> Result<Vec<Complex>, ExprError> {
+    let n = a.len();
+    let mut values = vec![(0.0, 0.0); n];
+    let mut anorm = 0.0;
+
+    for (i, row) in a.iter().enumerate() {
+        for x in row.iter().skip(i.saturating_sub(1)) {
+            anorm += x.abs();
+        }
+    }
+
+    let mut nn = n as isize - 1;
+    let mut t = 0.0;
+    let mut its = 0;
+
+    while nn >= 0 {
+        deadline.check()?;
+        let last = nn as usize;
+
+        // Look for a single small subdiagonal element
+        let mut l = last;
+        while l >= 1 {
+            let s = a[l - 1][l - 1].abs() + a[l][l].abs();
+            let s = if s == 0.0 { anorm } else { s };
+
+            if a[l][l - 1].abs() + s == s {
+                a[l][l - 1] = 0.0;
+                break;
+            }
+
+            l -= 1;
+        }
+
+        let mut x = a[last][last];
+
+        if l == last {
+            values[last] = (x + t, 0.0);
+            nn -= 1;
+            its = 0;
+            continue;
+        }
+
+        let mut y = a[last - 1][last - 1];
+        let mut w = a[last][last - 1] * a[last - 1][last];
+
+        if l == last - 1 {
+            let p = 0.5 * (y - x);
+            let q = p * p + w;
+            let z = q.abs().sqrt();
+            x += t;
+
+            if q >= 0.0 {
+                let z = p + z.copysign(p);
+                let second = if z != 0.0 { x - w / z } else { x + z };
+
+                values[last - 1] = (x + z, 0.0);
+                values[last] = (second, 0.0);
+            } else {
+                values[last - 1] = (x + p, -z);
+                values[last] = (x + p, z);
+            }
+
+            nn -= 2;
+            its = 0;
+            continue;
+        }
+
+        if its == 30 {
+            return Err(no_convergence("eig"));
+        }
+
+        // Exceptional shifts to get out of cycles
+        if its == 10 || its == 20 {
+            t += x;
+            for (i, row) in a.iter_mut().enumerate().take(last + 1) {
+                row[i] -= x;
+            }
+
+            let s = a[last][last - 1].abs() + a[last - 1][last - 2].abs();
+            x = 0.75 * s;
+            y = x;
+            w = -0.4375 * s * s;
+        }
+
+        its += 1;
+
+        // Form the shift and look for two consecutive small subdiagonal elements
+        let mut m = last - 2;
+        let (mut p, mut q, mut r);
+
+        loop {
+            let z = a[m][m];
+            let rr = x - z;
+            let ss = y - z;
+
+            p = (rr * ss - w) / a[m + 1][m] + a[m][m + 1];
+            q = a[m + 1][m + 1] - z - rr - ss;
+            r = a[m + 2][m + 1];
+
+            let s = p.abs() + q.abs() + r.abs();
+            p /= s;
+            q /= s;
+            r /= s;
+
+            if m == l {
+                break;
+            }
+
+            let u = a[m][m - 1].abs() * (q.abs() + r.abs());
+            let v = p.abs() * (a[m - 1][m - 1].abs() + z.abs() + a[m + 1][m + 1].abs());
+
+            if u + v == v {
+                break;
+            }
+
+            m -= 1;
+        }
+
+        for i in m + 2..=last {
+            a[i][i - 2] = 0.0;
+            if i != m + 2 {
+                a[i][i - 3] = 0.0;
+            }
+        }
+
+        // Double QR step on rows l..=last and columns m..=last
+        for k in m..last {
+            if k != m {
+                p = a[k][k - 1];
+                q = a[k + 1][k - 1];
+                r = if k != last - 1 { a[k + 2][k - 1] } else { 0.0 };
+                x = p.abs() + q.abs() + r.abs();
+
+                if x != 0.0 {
+                    p /= x;
+                    q /= x;
+                    r /= x;
+                }
+            }
+
+            let s = (p * p + q * q + r * r).sqrt().copysign(p);
+
+            if s == 0.0 {
+                continue;
+            }
+
+            if k == m {
+                if l != m {
+                    a[k][k - 1] = -a[k][k - 1];
+                }
+            } else {
+                a[k][k - 1] = -s * x;
+            }
+
+            p += s;
+            x = p / s;
+            y = q / s;
+            let z = r / s;
+            q /= p;
+            r /= p;
+
+            for j in k..=last {
+                let mut p = a[k][j] + q * a[k + 1][j];
+
+                if k != last - 1 {
+                    p += r * a[k + 2][j];
+                    a[k + 2][j] -= p * z;
+                }
+
+                a[k + 1][j] -= p * y;
+                a[k][j] -= p * x;
+            }
+
+            for i in l..=std::cmp::min(last, k + 3) {
+                let mut p = x * a[i][k] + y * a[i][k + 1];
+
+                if k != last - 1 {
+                    p += z * a[i][k + 2];
+                    a[i][k + 2] -= p * r;
+                }
+
+                a[i][k + 1] -= p * q;
+                a[i][k] -= p;
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+/// Inverse iteration with a shift right next to the eigenvalue
+fn eigenvector(a: &[Vec<f64>], value: Complex) -> Vec<Complex> {
+    let n = a.len();
+    let shift = (value.0 + 1e-10 * value.0.hypot(value.1).max(1.0), value.1);
+    let m: Vec<Vec<Complex>> = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| if i == j { (a[i][j] - shift.0, -shift.1) } else { (a[i][j], 0.0) })
+                .collect()
+        })
+        .collect();
+
+    let mut x: Vec<Complex> = (0..n).map(|i| (1.0 / (i as f64 + 1.0).sqrt(), 0.0)).collect();
+
+    for _ in 0..3 {
+        x = normalize(complex_solve(m.clone(), x));
+    }
+
+    x
+}
+
+/// Gaussian elimination with partial pivoting, zero pivots are nudged since
+/// the matrix is singular on purpose
+fn complex_solve(mut m: Vec<Vec<Complex>>, mut b: Vec<Complex>) -> Vec<Complex> {
+    let n = m.len();
+    let eps = 1e-300;
+
+    for k in 0..n {
+        let p = (k..n).fold(k, |p, i| if abs(m[i][k]) > abs(m[p][k]) { i } else { p });
+
+        m.swap(k, p);
+        b.swap(k, p);
+
+        if abs(m[k][k]) == 0.0 {
+            m[k][k] = (eps, 0.0);
+        }
+
+        for i in k + 1..n {
+            let f = div(m[i][k], m[k][k]);
+
+            for j in k..n {
+                m[i][j] = sub(m[i][j], mul(f, m[k][j]));
+            }
+            b[i] = sub(b[i], mul(f, b[k]));
+        }
+    }
+
+    let mut x = vec![(0.0, 0.0); n];
+
+    for i in (0..n).rev() {
+        let s = (i + 1..n).fold(b[i], |s, j| sub(s, mul(m[i][j], x[j])));
+
+        x[i] = div(s, m[i][i]);
+    }
+
+    x
+}
+
+/// Unit length, with the biggest component made real and positive
+fn normalize(x: Vec<Complex>) -> Vec<Complex> {
+    let big = x.iter().cloned().fold((0.0, 0.0), |b, x| if abs(x) > abs(b) { x } else { b });
+
+    if abs(big) == 0.0 || !abs(big).is_finite() {
+        return x;
+    }
+
+    let x: Vec<Complex> = x.into_iter().map(|x| div(x, big)).collect();
+    let len = x.iter().map(|&x| abs(x) * abs(x)).sum::<f64>().sqrt();
+
+    x.into_iter().map(|x| (x.0 / len, x.1 / len)).collect()
+}
+
+fn abs(x: Complex) -> f64 {
+    x.0.hypot(x.1)
+}
+
+fn mul(x: Complex, y: Complex) -> Complex {
+    (x.0 * y.0 - x.1 * y.1, x.0 * y.1 + x.1 * y.0)
+}
+
+fn sub(x: Complex, y: Complex) -> Complex {
+    (x.0 - y.0, x.1 - y.1)
+}
+
+fn div(x: Complex, y: Complex) -> Complex {
+    let d = y.0 * y.0 + y.1 * y.1;
+
+    ((x.0 * y.0 + x.1 * y.1) / d, (x.1 * y.0 - x.0 * y.1) / d)
+}
+
+/// `(c, s)` of the rotation that makes columns (or the off-diagonal element) orthogonal
+fn rotation(alpha: f64, beta: f64, gamma: f64) -> (f64, f64) {
+    let zeta = (beta - alpha) / (2.0 * gamma);
+    let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+    let c = 1.0 / (1.0 + t * t).sqrt();
+
+    (c, c * t)
+}
+
+fn rotate_columns(a: &mut Matrix, p: usize, q: usize, c: f64, s: f64) {
+    for row in a.iter_mut() {
+        let (x, y) = (row[p], row[q]);
+
+        row[p] = c * x - s * y;
+        row[q] = s * x + c * y;
+    }
+}
+
+fn rotate_rows(a: &mut Matrix, p: usize, q: usize, c: f64, s: f64) {
+    for k in 0..a[p].len() {
+        let (x, y) = (a[p][k], a[q][k]);
+
+        a[p][k] = c * x - s * y;
+        a[q][k] = s * x + c * y;
+    }
+}
+
+/// Unit `v` such that `I - 2 v v^T` maps `x` onto the first axis
+fn reflector<I: Iterator<Item = f64>>(x: I) -> Option<Vec<f64>> {
+    let mut v: Vec<f64> = x.collect();
+    let norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+    if norm == 0.0 {
+        return None;
+    }
+
+    v[0] += norm.copysign(v[0]);
+
+    let len = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+    if len == 0.0 {
+        return None;
+    }
+
+    Some(v.into_iter().map(|x| x / len).collect())
+}
+
+/// Fills missing columns with unit vectors orthogonal to the rest
+fn complete_basis(columns: &mut Vec<Option<Vec<f64>>>, m: usize) {
+    for j in 0..columns.len() {
+        if columns[j].is_some() {
+            continue;
+        }
+
+        for e in 0..m {
+            let mut w: Vec<f64> = (0..m).map(|i| if i == e { 1.0 } else { 0.0 }).collect();
+
+            for c in columns.iter().flatten() {
+                let d: f64 = c.iter().zip(&w).map(|(c, w)| c * w).sum();
+
+                for (w, c) in w.iter_mut().zip(c) {
+                    *w -= d * c;
+                }
+            }
+
+            let len = w.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+            if len > 0.1 {
+                columns[j] = Some(w.into_iter().map(|x| x / len).collect());
+                break;
+            }
+        }
+    }
+}
+
+fn product(a: &[Vec<f64>], b: &[Vec<f64>]) -> Matrix {
+    a.iter()
+        .map(|row| {
+            (0..b[0].len())
+                .map(|j| row.iter().zip(b).map(|(x, b)| x * b[j]).sum())
+                .collect()
+        })
+        .collect()
+}
+
+/// Pivots this small are zero
+fn tolerance(a: &[Vec<f64>]) -> f64 {
+    let scale = a.iter().flatten().fold(0.0f64, |m, x| m.max(x.abs()));
+
+    1e-12 * scale * std::cmp::max(a.len(), a[0].len()) as f64
+}
+
+fn pivot(u: &[Vec<f64>], k: usize) -> usize {
+    (k..u.len()).fold(k, |p, i| if u[i][k].abs() > u[p][k].abs() { i } else { p })
+}
+
+fn symmetric(a: &[Vec<f64>]) -> bool {
+    let scale = a.iter().flatten().fold(0.0f64, |m, x| m.max(x.abs()));
+
+    (0..a.len()).all(|i| (0..i).all(|j| (a[i][j] - a[j][i]).abs() <= 1e-12 * scale))
+}
+
+fn size(a: &[Vec<f64>], func: &str) -> Result<(usize, usize), ExprError> {
+    match a.first().map(|row| row.len()) {
+        Some(n) if n > 0 && a.iter().all(|row| row.len() == n) => Ok((a.len(), n)),
+        _ => Err(ExprError::CalcError {
+            err: format!("{} needs a non-empty matrix", func),
+        }),
+    }
+}
+
+fn square(a: &[Vec<f64>], func: &str) -> Result<usize, ExprError> {
+    match size(a, func)? {
+        (m, n) if m == n => Ok(n),
+        _ => Err(ExprError::CalcError {
+            err: format!("{} works only on square matrices", func),
+        }),
+    }
+}
+
+fn no_convergence(func: &str) -> ExprError {
+    ExprError::NoConvergence {
+        func: func.to_string(),
+    }
+}
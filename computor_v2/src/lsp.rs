@@ -0,0 +1,383 @@
+use crate::{grammar, validate_matrix, Context, Expr, ExprError, Interpreter, Limits};
+
+use lalrpop_util::ParseError;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+/// Language server for script files, one expression per line, `#` starts a
+/// comment line
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exit: bool,
+}
+
+/// Steps each line may take, documents are evaluated again on every change
+const MAX_STEPS: u64 = 1_000_000;
+/// Time each line may take
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Open file, evaluated line by line whenever it changes
+struct Document {
+    lines: Vec<String>,
+    /// Variables and functions as they are after the last line
+    calc: Interpreter,
+    /// Where each function defined by `f(x) = ...` is named
+    definitions: HashMap<String, Value>,
+    diagnostics: Vec<Value>,
+}
+
+/// Serves requests from `input` until `exit`, messages framed with
+/// `Content-Length` headers. `true` if the client asked to shut down first
+pub fn run(input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<bool> {
+    let mut server = Server::new();
+
+    while !server.exit {
+        let message = match read_message(input)? {
+            Some(message) => message,
+            None => break,
+        };
+
+        for reply in server.handle(&message) {
+            write_message(output, &reply)?;
+        }
+    }
+
+    Ok(server.shutdown)
+}
+
+fn read_message(input: &mut dyn BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(pos) = header.find(':') {
+            if header[..pos].eq_ignore_ascii_case("content-length") {
+                length = header[pos + 1..].trim().parse().ok();
+            }
+        }
+    }
+
+    let length =
+        length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn write_message(output: &mut dyn Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    /// Replies and notifications to send back for one incoming message
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "computor_v2" },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "exit" => {
+                self.exit = true;
+                return vec![];
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+
+                return vec![self.update(uri, text)];
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                let text = changes.and_then(|x| x.last()).and_then(|x| x["text"].as_str());
+
+                return vec![self.update(uri, text.unwrap_or(""))];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+
+                return vec![diagnostics(&uri, vec![])];
+            }
+            "textDocument/hover" => self.hover(&uri, &params["position"]),
+            "textDocument/completion" => self.completion(&uri),
+            "textDocument/definition" => self.definition(&uri, &params["position"]),
+            _ if message.get("id").is_some() => {
+                let text = format!("unknown method {}", method);
+                let err = json!({ "code": -32601, "message": text });
+
+                return vec![json!({ "jsonrpc": "2.0", "id": message["id"], "error": err })];
+            }
+            // Notifications we don't care about
+            _ => return vec![],
+        };
+
+        vec![json!({ "jsonrpc": "2.0", "id": message["id"], "result": result })]
+    }
+
+    fn update(&mut self, uri: String, text: &str) -> Value {
+        let document = Document::new(text);
+        let res = diagnostics(&uri, document.diagnostics.clone());
+
+        self.documents.insert(uri, document);
+
+        res
+    }
+
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return Value::Null,
+        };
+        let (name, range) = match document.word(position) {
+            Some(word) => word,
+            None => return Value::Null,
+        };
+
+        let context = document.calc.context();
+
+        match context.get(&name) {
+            Some(value) => json!({
+                "contents": {
+                    "kind": "markdown",
+                    "value": format!("```\n{}\n```", describe(&name, value, context)),
+                },
+                "range": range,
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn completion(&self, uri: &str) -> Value {
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return json!([]),
+        };
+        let mut names: Vec<&str> = document.calc.context().names().collect();
+        names.sort();
+
+        let items: Vec<Value> = names
+            .into_iter()
+            .filter_map(|name| Some((name, document.calc.context().get(name)?)))
+            .map(|(name, value)| {
+                // Function or Variable
+                let kind = if let Expr::Lambda(..) = value { 3 } else { 6 };
+
+                json!({
+                    "label": name,
+                    "kind": kind,
+                    "detail": describe(name, value, document.calc.context()),
+                })
+            })
+            .collect();
+
+        json!(items)
+    }
+
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        let found = self.documents.get(uri).and_then(|document| {
+            let (name, _) = document.word(position)?;
+
+            document.definitions.get(&name)
+        });
+
+        match found {
+            Some(range) => json!({ "uri": uri, "range": range }),
+            None => Value::Null,
+        }
+    }
+}
+
+impl Document {
+    fn new(text: &str) -> Document {
+        let mut calc = Interpreter::new();
+        calc.set_limits(Limits {
+            max_steps: Some(MAX_STEPS),
+            timeout: Some(TIMEOUT),
+            ..Limits::default()
+        });
+
+        let mut document = Document {
+            lines: text.lines().map(String::from).collect(),
+            calc,
+            definitions: HashMap::new(),
+            diagnostics: vec![],
+        };
+
+        for n in 0..document.lines.len() {
+            document.eval(n);
+        }
+
+        document
+    }
+
+    fn eval(&mut self, n: usize) {
+        let line = &self.lines[n];
+        let start = line.len() - line.trim_start().len();
+        let end = line.trim_end().len();
+
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            return;
+        }
+
+        let expr = match grammar::RootExprParser::new().parse(line) {
+            Ok(ref expr) if !validate_matrix(expr) => {
+                let message = ExprError::InvalidMatrix.to_string();
+
+                return self.diagnostics.push(diagnostic(line, n, (start, end), &message));
+            }
+            Ok(expr) => expr,
+            Err(err) => {
+                let at = match err {
+                    ParseError::InvalidToken { location } => {
+                        let len = line[location..].chars().next().map_or(0, char::len_utf8);
+
+                        (location, location + len)
+                    }
+                    ParseError::UnrecognizedEOF { location, .. } => (location, location),
+                    ParseError::UnrecognizedToken {
+                        token: (from, _, to),
+                        ..
+                    }
+                    | ParseError::ExtraToken {
+                        token: (from, _, to),
+                    } => (from, to),
+                    ParseError::User { .. } => (start, end),
+                };
+                let message = ExprError::ParseError {
+                    err: err.to_string(),
+                }
+                .to_string();
+
+                return self.diagnostics.push(diagnostic(line, n, at, &message));
+            }
+        };
+
+        if let Expr::AssignFunc(box Expr::Call(ref name, _), _) = expr {
+            let range = range(line, n, (start, start + name.len()));
+            self.definitions.insert(name.clone(), range);
+        }
+
+        if let Err(err) = self.calc.eval_expr(expr) {
+            let diagnostic = diagnostic(line, n, (start, end), &err.to_string());
+
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Name under the cursor, lowercased like the parser does, and its range
+    fn word(&self, position: &Value) -> Option<(String, Value)> {
+        let n = position["line"].as_u64()? as usize;
+        let line = self.lines.get(n)?;
+        let at = byte(line, position["character"].as_u64()? as usize);
+        let (start, end) = word(line, at);
+
+        if start == end {
+            return None;
+        }
+
+        Some((line[start..end].to_lowercase(), range(line, n, (start, end))))
+    }
+}
+
+/// Bytes where the name around byte `at` of `line` starts and ends, the same
+/// for both if there is none
+pub(crate) fn word(line: &str, at: usize) -> (usize, usize) {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '$';
+    let start = line[..at]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !is_name(c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let end = line[at..].find(|c| !is_name(c)).map_or(line.len(), |i| at + i);
+
+    (start, end)
+}
+
+/// `f(x) = body` for functions, `x = value` for the rest
+pub(crate) fn describe(name: &str, value: &Expr, context: &Context) -> String {
+    match value {
+        Expr::Lambda(ref args, ref body) => {
+            format!("{}({}) = {}", name, args.join(", "), body.formatted(&context.settings))
+        }
+        _ => format!("{} = {}", name, value.formatted(&context.settings)),
+    }
+}
+
+fn diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn diagnostic(line: &str, n: usize, (from, to): (usize, usize), message: &str) -> Value {
+    json!({
+        "range": range(line, n, (from, to)),
+        "severity": 1,
+        "source": "computor_v2",
+        "message": message,
+    })
+}
+
+/// LSP range of bytes `from..to` of line `n`, columns counted in UTF-16 units.
+/// An offset inside a character counts from the start of it
+fn range(line: &str, n: usize, (from, to): (usize, usize)) -> Value {
+    let column = |i: usize| {
+        let i = (0..=i.min(line.len())).rev().find(|&i| line.is_char_boundary(i)).unwrap_or(0);
+
+        line[..i].encode_utf16().count()
+    };
+
+    json!({
+        "start": { "line": n, "character": column(from) },
+        "end": { "line": n, "character": column(to) },
+    })
+}
+
+/// Byte offset of UTF-16 column `column`
+fn byte(line: &str, column: usize) -> usize {
+    let mut units = 0;
+
+    for (i, c) in line.char_indices() {
+        if units >= column {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+
+    line.len()
+}
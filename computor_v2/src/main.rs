@@ -2,29 +2,156 @@ extern crate color_backtrace;
 extern crate computor_v2;
 extern crate failure;
 extern crate rustyline;
+extern crate serde_json;
 
-use computor_v2::Context;
+use computor_v2::{repl, Expr, Interpreter};
 use rustyline::error::ReadlineError;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+/// `--json`: one `{"eval": "..."}` request per line in, one answer per line out
+fn json_mode() {
+    let mut calc = Interpreter::new();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let answer = computor_v2::json::answer(&line, &mut calc);
+        let _ = writeln!(stdout, "{}", answer);
+        let _ = stdout.flush();
+    }
+}
+
+/// `--kernel <connection file>`: Jupyter kernel
+#[cfg(feature = "jupyter")]
+fn kernel_mode(path: &str) {
+    if let Err(err) = computor_v2::kernel::run(path) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "jupyter"))]
+fn kernel_mode(_: &str) {
+    eprintln!("Error: built without Jupyter support, rebuild with --features jupyter");
+    process::exit(1);
+}
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(n) = args.iter().position(|arg| arg == "--kernel") {
+        match args.get(n + 1) {
+            Some(path) => return kernel_mode(path),
+            None => {
+                eprintln!("Error: usage is --kernel <connection file>");
+                process::exit(1);
+            }
+        }
+    }
+    if env::args().skip(1).any(|arg| arg == "--json") {
+        return json_mode();
+    }
+    if env::args().skip(1).any(|arg| arg == "--lsp") {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+
+        match computor_v2::lsp::run(&mut stdin.lock(), &mut stdout.lock()) {
+            Ok(true) => return,
+            Ok(false) => process::exit(1),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
     color_backtrace::install();
     let mut rl = rustyline::Editor::<()>::new();
-    let mut context = Context::new();
+    let mut calc = Interpreter::new();
+    calc.number_results();
+
+    let history = repl::history_path(env::var_os("XDG_STATE_HOME"), env::var_os("HOME"));
+    if let Some(ref path) = history {
+        // Missing on the first run, nothing to worry about
+        let _ = rl.load_history(path);
+    }
 
     loop {
         match rl.readline("> ") {
             Ok(line) => {
                 rl.add_history_entry(&*line);
 
-                if line.starts_with("root ") {
-                    computor_v2::computor_v1::computor_v1(line[5..].to_string());
+                if line.starts_with(":set") {
+                    let words: Vec<&str> = line.split_whitespace().skip(1).collect();
+
+                    match words.as_slice() {
+                        [] => println!("{}", calc.context().settings),
+                        [name, value] => {
+                            if let Err(err) = calc.set(name, value) {
+                                println!("Error: {}", err);
+                            }
+                        }
+                        _ => println!("Error: usage is :set <name> <value>"),
+                    }
+                } else if line.starts_with(":format") {
+                    let words: Vec<&str> = line.split_whitespace().skip(1).collect();
+
+                    match words.as_slice() {
+                        [] => println!("{}", calc.context().settings.format),
+                        [value] => {
+                            if let Err(err) = calc.set("format", value) {
+                                println!("Error: {}", err);
+                            }
+                        }
+                        _ => println!("Error: usage is :format <text|latex|unicode|mathml>"),
+                    }
+                } else if let Some(equation) = line.strip_prefix("root ") {
+                    computor_v2::computor_v1::computor_v1(equation.to_string());
                 } else {
-                    match computor_v2::parse(&line) {
+                    let (line, target) = match repl::redirect(&line) {
+                        Some((expr, path)) => (expr, Some(path)),
+                        None => (line.as_str(), None),
+                    };
+
+                    match computor_v2::parse(line) {
                         Ok(expr) => {
-                            println!("Parsed: {}", &expr);
+                            let parsed = expr.rendered(&calc.context().settings);
+                            if parsed.contains('\n') {
+                                println!("Parsed:\n{}", parsed);
+                            } else {
+                                println!("Parsed: {}", parsed);
+                            }
+
+                            match calc.eval_expr(expr) {
+                                Ok(result) => {
+                                    let output = result.rendered(&calc.context().settings);
 
-                            match expr.run(&mut context, 0) {
-                                Ok(result) => println!("Result: {}", &result),
+                                    if let Some(path) = target {
+                                        match result {
+                                            Expr::Plot(ref plot) => {
+                                                match computor_v2::export::save(plot, path) {
+                                                    Ok(()) => println!("Wrote {}", path),
+                                                    Err(err) => println!("Error: {}", err),
+                                                }
+                                            }
+                                            _ => println!("Error: only plots can be saved"),
+                                        }
+                                    } else if output.contains('\n') {
+                                        println!("Result:\n{}", output);
+                                    } else {
+                                        println!("Result: {}", output);
+                                    }
+                                }
                                 Err(err) => println!("Error: {}", err),
                             }
                         }
@@ -34,7 +161,20 @@ fn main() {
             }
             Err(ReadlineError::Interrupted) => {}
             Err(ReadlineError::Eof) => break,
-            Err(err) => panic!("{}", err),
+            Err(err) => {
+                println!("Error: {}", err);
+                break;
+            }
+        }
+    }
+
+    if let Some(ref path) = history {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+
+        if let Err(err) = rl.save_history(path) {
+            println!("Error: could not save history to {}: {}", path.display(), err);
         }
     }
 }
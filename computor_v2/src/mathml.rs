@@ -0,0 +1,400 @@
+use crate::format::{format_bigfloat, format_integer, format_real, Settings};
+use crate::precedence::{
+    self, Join, ADDEND, ATOM, FACTORS, LOWEST, NEG, NEG_OPERAND, POWER, PRODUCT, SUM,
+};
+use crate::unicode::greek;
+use crate::{units, Expr, Slice};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed};
+
+const MINUS: &str = "<mo>-</mo>";
+
+/// `<math>` element for `e`, with no more parentheses than precedence needs
+pub fn mathml(e: &Expr, settings: &Settings) -> String {
+    format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{}</math>",
+        row(node(e, settings).0)
+    )
+}
+
+/// Children of an `<mrow>`, a negative expression starts with `MINUS`
+fn node(e: &Expr, s: &Settings) -> (Vec<String>, u8) {
+    let (res, prec) = match e {
+        Expr::Real(ref x) => number(&format_real(*x, s)),
+        Expr::Integer(ref x) => number(&format_integer(x, s)),
+        Expr::BigFloat(ref x) => number(&format_bigfloat(x, s)),
+        Expr::Rational(ref x) if x.denom().is_one() => number(&format_integer(x.numer(), s)),
+        Expr::Rational(ref x) => {
+            let numer = mn(&format_integer(&x.numer().abs(), s));
+            let frac = format!("<mfrac>{}{}</mfrac>", numer, mn(&format_integer(x.denom(), s)));
+
+            if x.is_negative() {
+                (vec![MINUS.to_string(), frac], NEG)
+            } else {
+                (vec![frac], PRODUCT)
+            }
+        }
+        Expr::Complex(re, im) => complex(*re, *im, s),
+        Expr::Quantity(ref x) => {
+            let (value, unit) = match x.unit {
+                Some((ref name, size)) => (x.value / size, name.clone()),
+                None => (x.value, units::base_units(&x.dim)),
+            };
+            let mut res = number(&format_real(value, s)).0;
+            res.push("<mo>&#x2062;</mo>".to_string());
+            res.push(unit_name(&unit));
+
+            (res, PRODUCT)
+        }
+        Expr::Var(ref name) => (vec![ident(name)], ATOM),
+        Expr::Unit(_) => (vec![unit_name(&units::unit_name(e))], ATOM),
+        Expr::Lambda(ref args, ref body) => {
+            let args: Vec<String> = args.iter().map(|x| ident(x)).collect();
+            let mut res = if args.len() == 1 {
+                args
+            } else {
+                fenced("(", separated(args), ")")
+            };
+            res.push("<mo>&#x21a6;</mo>".to_string());
+            res.push(row(node(body, s).0));
+
+            (res, LOWEST)
+        }
+        Expr::Call(ref name, ref args) => (call(name, args, s), ATOM),
+        Expr::Vector(ref x) => (matrix(std::slice::from_ref(x), s), ATOM),
+        Expr::Matrix(ref x) => (matrix(x, s), ATOM),
+        Expr::Tuple(ref items) => (fenced("(", list(items, s), ")"), ATOM),
+        Expr::Plot(_) => (vec!["<mtext>(plot)</mtext>".to_string()], ATOM),
+        Expr::Index(ref x, ref slices) => {
+            let end = |x: &Option<Expr>| match x {
+                Some(x) => row(node(x, s).0),
+                None => String::new(),
+            };
+            let slices = slices
+                .iter()
+                .map(|slice| match slice {
+                    Slice::At(x) => row(node(x, s).0),
+                    Slice::Range(from, to) => format!("{}<mo>:</mo>{}", end(from), end(to)),
+                })
+                .collect();
+            let sub = row(separated(slices));
+
+            (vec![format!("<msub>{}{}</msub>", row(wrap(x, s, ATOM)), sub)], ATOM)
+        }
+        Expr::Neg(ref x) => {
+            let mut res = vec![MINUS.to_string()];
+            res.extend(wrap(x, s, NEG_OPERAND));
+
+            (res, NEG)
+        }
+        Expr::Factorial(ref x) => {
+            let mut res = wrap(x, s, ATOM);
+            res.push("<mo>!</mo>".to_string());
+
+            (res, POWER)
+        }
+        Expr::Add(ref x, ref y) => {
+            let mut res = wrap(x, s, ADDEND);
+            let (right, prec) = node(y, s);
+
+            // The minus of the right side becomes the operator
+            match precedence::join(prec, right[0] == MINUS) {
+                Join::Minus => res.extend(right),
+                Join::Parens => {
+                    res.push("<mo>+</mo>".to_string());
+                    res.extend(fenced("(", right, ")"));
+                }
+                Join::Plus => {
+                    res.push("<mo>+</mo>".to_string());
+                    res.extend(right);
+                }
+            }
+
+            (res, SUM)
+        }
+        Expr::Mul(ref x, ref y) => {
+            let mut res = wrap(x, s, FACTORS.0);
+            let right = wrap(y, s, FACTORS.1);
+
+            // Invisible times between a number and what it multiplies: 2x
+            let implicit = match **x {
+                Expr::Integer(_) | Expr::Real(_) | Expr::Rational(_) | Expr::BigFloat(_) => {
+                    !right[0].starts_with("<mn>")
+                }
+                _ => false,
+            };
+            if implicit {
+                res.push("<mo>&#x2062;</mo>".to_string());
+            } else {
+                res.push("<mo>&#x22c5;</mo>".to_string());
+            }
+            res.extend(right);
+
+            (res, PRODUCT)
+        }
+        Expr::Div(ref x, ref y) => {
+            let (numer, denom) = (row(node(x, s).0), row(node(y, s).0));
+
+            (vec![format!("<mfrac>{}{}</mfrac>", numer, denom)], PRODUCT)
+        }
+        Expr::Rem(ref x, ref y) => {
+            let mut res = wrap(x, s, NEG);
+            res.push("<mo>mod</mo>".to_string());
+            res.extend(wrap(y, s, POWER));
+
+            (res, PRODUCT)
+        }
+        Expr::Pow(ref x, box Expr::Rational(ref n))
+            if n.numer().is_one() && *n.denom() == BigInt::from(2) =>
+        {
+            (vec![format!("<msqrt>{}</msqrt>", row(node(x, s).0))], ATOM)
+        }
+        Expr::Pow(ref x, ref y) => {
+            let (base, exp) = (row(wrap(x, s, ATOM)), row(node(y, s).0));
+
+            (vec![format!("<msup>{}{}</msup>", base, exp)], POWER)
+        }
+        Expr::MatrixMul(ref x, ref y) => {
+            let mut res = wrap(x, s, NEG);
+            res.push("<mo>&#x2062;</mo>".to_string());
+            res.extend(wrap(y, s, PRODUCT));
+
+            (res, PRODUCT)
+        }
+        // A \ b solves A x = b
+        Expr::Solve(ref x, ref y) => {
+            let inverse = format!("<msup>{}{}</msup>", row(wrap(x, s, ATOM)), exponent("-1"));
+            let mut res = vec![inverse, "<mo>&#x2062;</mo>".to_string()];
+            res.extend(wrap(y, s, PRODUCT));
+
+            (res, PRODUCT)
+        }
+        Expr::Convert(ref x, ref unit) => {
+            let unit = match **unit {
+                Expr::Unit(_) => unit_name(&units::unit_name(unit)),
+                ref unit => row(node(unit, s).0),
+            };
+            let mut res = node(x, s).0;
+            res.push("<mo>&#x2192;</mo>".to_string());
+            res.push(unit);
+
+            (res, LOWEST)
+        }
+        Expr::AssignVar(ref name, ref val) => {
+            let mut res = vec![ident(name), "<mo>=</mo>".to_string()];
+            res.extend(node(val, s).0);
+
+            (res, LOWEST)
+        }
+        Expr::AssignFunc(ref head, ref body) => {
+            let mut res = node(head, s).0;
+            res.push("<mo>=</mo>".to_string());
+            res.extend(node(body, s).0);
+
+            (res, LOWEST)
+        }
+    };
+
+    let prec = precedence::signed(prec, res[0] == MINUS);
+
+    (res, prec)
+}
+
+/// `e`, in parentheses if it is looser than `prec`
+fn wrap(e: &Expr, s: &Settings, prec: u8) -> Vec<String> {
+    match node(e, s) {
+        (res, p) if precedence::looser(p, prec) => fenced("(", res, ")"),
+        (res, _) => res,
+    }
+}
+
+/// One element out of a list of children
+fn row(mut items: Vec<String>) -> String {
+    if items.len() == 1 {
+        items.remove(0)
+    } else {
+        format!("<mrow>{}</mrow>", items.concat())
+    }
+}
+
+fn fenced(open: &str, items: Vec<String>, close: &str) -> Vec<String> {
+    let mut res = vec![format!("<mo>{}</mo>", open)];
+    res.extend(items);
+    res.push(format!("<mo>{}</mo>", close));
+
+    res
+}
+
+fn separated(items: Vec<String>) -> Vec<String> {
+    let mut res = Vec::new();
+
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            res.push("<mo>,</mo>".to_string());
+        }
+        res.push(item);
+    }
+
+    res
+}
+
+fn list(items: &[Expr], s: &Settings) -> Vec<String> {
+    separated(items.iter().map(|x| row(node(x, s).0)).collect())
+}
+
+fn mn(x: &str) -> String {
+    format!("<mn>{}</mn>", escape(x))
+}
+
+/// Integer exponent like `-7`
+fn exponent(x: &str) -> String {
+    if x.starts_with('-') {
+        format!("<mrow>{}{}</mrow>", MINUS, mn(&x[1..]))
+    } else {
+        mn(x)
+    }
+}
+
+/// Number printed by `format`, `1.5e-7` as `1.5 × 10` to the `-7`
+fn number(x: &str) -> (Vec<String>, u8) {
+    let (sign, x) = if x.starts_with('-') {
+        (vec![MINUS.to_string()], &x[1..])
+    } else {
+        (vec![], x)
+    };
+    let (mut res, prec) = match x {
+        "inf" => (vec!["<mi>&#x221e;</mi>".to_string()], ATOM),
+        "NaN" => (vec!["<mi>NaN</mi>".to_string()], ATOM),
+        x => match x.find('e') {
+            Some(pos) => {
+                let power = format!("<msup><mn>10</mn>{}</msup>", exponent(&x[pos + 1..]));
+
+                (vec![mn(&x[..pos]), "<mo>&#xd7;</mo>".to_string(), power], PRODUCT)
+            }
+            None => (vec![mn(x)], ATOM),
+        },
+    };
+
+    if sign.is_empty() {
+        (res, prec)
+    } else {
+        res.splice(0..0, sign);
+
+        (res, NEG)
+    }
+}
+
+fn complex(re: f64, im: f64, s: &Settings) -> (Vec<String>, u8) {
+    if im == 0.0 {
+        return number(&format_real(re, s));
+    }
+
+    let imag = if im.abs() == 1.0 {
+        vec!["<mi>i</mi>".to_string()]
+    } else {
+        let mut res = number(&format_real(im.abs(), s)).0;
+        res.push("<mo>&#x2062;</mo>".to_string());
+        res.push("<mi>i</mi>".to_string());
+
+        res
+    };
+    let sign = if im < 0.0 { MINUS } else { "<mo>+</mo>" };
+
+    if re != 0.0 {
+        let mut res = number(&format_real(re, s)).0;
+        res.push(sign.to_string());
+        res.extend(imag);
+
+        (res, SUM)
+    } else if im < 0.0 {
+        let mut res = vec![MINUS.to_string()];
+        res.extend(imag);
+
+        (res, NEG)
+    } else {
+        let prec = if imag.len() == 1 { ATOM } else { PRODUCT };
+
+        (imag, prec)
+    }
+}
+
+fn matrix(rows: &[Vec<f64>], s: &Settings) -> Vec<String> {
+    let rows: Vec<String> = rows
+        .iter()
+        .map(|cells| {
+            let cells: Vec<String> = cells
+                .iter()
+                .map(|x| format!("<mtd>{}</mtd>", row(number(&format_real(*x, s)).0)))
+                .collect();
+
+            format!("<mtr>{}</mtr>", cells.concat())
+        })
+        .collect();
+
+    fenced("(", vec![format!("<mtable>{}</mtable>", rows.concat())], ")")
+}
+
+fn call(name: &str, args: &[Expr], s: &Settings) -> Vec<String> {
+    match (name, args) {
+        ("sqrt", [x]) => return vec![format!("<msqrt>{}</msqrt>", row(node(x, s).0))],
+        ("abs", [x]) => return fenced("|", node(x, s).0, "|"),
+        ("floor", [x]) => return fenced("&#x230a;", node(x, s).0, "&#x230b;"),
+        ("ceil", [x]) => return fenced("&#x2308;", node(x, s).0, "&#x2309;"),
+        _ => {}
+    }
+
+    let mut res = vec![format!("<mi>{}</mi>", escape(name)), "<mo>&#x2061;</mo>".to_string()];
+    res.extend(fenced("(", list(args, s), ")"));
+
+    res
+}
+
+/// `<mi>x</mi>`, `<mi>π</mi>` or `<mi>speed</mi>`
+fn ident(name: &str) -> String {
+    match greek(name) {
+        Some(c) => format!("<mi>{}</mi>", c),
+        None => format!("<mi>{}</mi>", escape(name)),
+    }
+}
+
+/// Upright unit names, `m*s^-2` as `m⋅s` to the `-2`
+fn unit_name(unit: &str) -> String {
+    let mut res = Vec::new();
+    let mut name = String::new();
+    let mut chars = unit.chars().peekable();
+
+    let mi = |name: &str| format!("<mi mathvariant=\"normal\">{}</mi>", escape(name));
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '/' | '(' | ')' => {
+                if !name.is_empty() {
+                    res.push(mi(&name));
+                    name.clear();
+                }
+                let op = if c == '*' { "&#x22c5;".to_string() } else { c.to_string() };
+                res.push(format!("<mo>{}</mo>", op));
+            }
+            '^' => {
+                let mut exp = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '-') {
+                    exp.push(c);
+                    chars.next();
+                }
+                res.push(format!("<msup>{}{}</msup>", mi(&name), exponent(&exp)));
+                name.clear();
+            }
+            c => name.push(c),
+        }
+    }
+    if !name.is_empty() {
+        res.push(mi(&name));
+    }
+
+    row(res)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,272 @@
+use crate::{Expr, ExprError, Limits};
+
+use num_traits::ToPrimitive;
+
+/// Evaluated `Slice`, `None` ends of a span mean the edges of the axis
+pub enum Axis {
+    At(i64),
+    Span(Option<i64>, Option<i64>),
+}
+
+/// Matrix builders, looked up after user functions. Sizes are checked against
+/// `limits` before anything is allocated
+pub fn call(name: &str, args: Vec<Expr>, limits: &Limits) -> Result<Expr, ExprError> {
+    let square = |n: usize| limits.check_size(n.checked_mul(n));
+
+    match (name, args.as_slice()) {
+        ("zeros", [r, c]) => fill(count(name, r)?, count(name, c)?, 0.0, limits),
+        ("ones", [r, c]) => fill(count(name, r)?, count(name, c)?, 1.0, limits),
+        ("eye", [n]) => {
+            let n = count(name, n)?;
+            square(n)?;
+
+            Ok(Expr::Matrix(crate::linalg::identity(n)))
+        }
+        ("diag", [Expr::Matrix(m)]) if m.len() > 1 && m.iter().all(|row| row.len() == m.len()) => {
+            Ok(Expr::Vector((0..m.len()).map(|i| m[i][i]).collect()))
+        }
+        // Rows and columns are vectors, any other matrix has to be square
+        ("diag", [Expr::Matrix(m)]) if m.len() > 1 && m.iter().any(|row| row.len() > 1) => {
+            Err(ExprError::CalcError {
+                err: "diag works only on vectors and square matrices".into(),
+            })
+        }
+        ("diag", [x]) => {
+            let items = items(name, x)?;
+            let n = items.len();
+            square(n)?;
+
+            Ok(Expr::Matrix(
+                (0..n)
+                    .map(|i| (0..n).map(|j| if i == j { items[i] } else { 0.0 }).collect())
+                    .collect(),
+            ))
+        }
+        ("range", [a, b]) => range(real(name, a)?, real(name, b)?, 1.0, limits),
+        ("range", [a, b, step]) => {
+            range(real(name, a)?, real(name, b)?, real(name, step)?, limits)
+        }
+        ("reshape", [x, r, c]) => {
+            let items = items(name, x)?;
+            let (r, c) = (count(name, r)?, count(name, c)?);
+
+            if r.checked_mul(c) != Some(items.len()) {
+                return Err(ExprError::CalcError {
+                    err: format!("can't reshape {} items into {}x{}", items.len(), r, c),
+                });
+            }
+
+            Ok(Expr::Matrix(items.chunks(c.max(1)).map(|row| row.to_vec()).collect()))
+        }
+        ("hcat", _) | ("vcat", _) if !args.is_empty() => concat(name, args),
+        _ => Err(ExprError::BadArgsCount {
+            func: name.to_string(),
+        }),
+    }
+}
+
+/// `m[1, 2]`, `m[:, 0]`, `m[0]`, `v[1:3]`, `t[0]`
+pub fn index(x: Expr, axes: Vec<Axis>) -> Result<Expr, ExprError> {
+    match (x, axes.as_slice()) {
+        (Expr::Vector(v), [axis]) => match select(axis, v.len())? {
+            Selection::One(i) => Ok(Expr::Real(v[i])),
+            Selection::Many(from, to) => Ok(Expr::Vector(v[from..to].to_vec())),
+        },
+        (Expr::Tuple(items), [axis]) => match select(axis, items.len())? {
+            Selection::One(i) => Ok(items[i].clone()),
+            Selection::Many(from, to) => Ok(Expr::Tuple(items[from..to].to_vec())),
+        },
+        (Expr::Matrix(m), [rows]) => {
+            let (from, to) = select(rows, m.len())?.span();
+
+            Ok(Expr::Matrix(m[from..to].to_vec()))
+        }
+        (Expr::Matrix(m), [rows, columns]) => {
+            let width = m.first().map_or(0, |row| row.len());
+
+            match (select(rows, m.len())?, select(columns, width)?) {
+                (Selection::One(i), Selection::One(j)) => Ok(Expr::Real(m[i][j])),
+                (rows, columns) => {
+                    let (top, bottom) = rows.span();
+                    let (left, right) = columns.span();
+
+                    Ok(Expr::Matrix(
+                        m[top..bottom]
+                            .iter()
+                            .map(|row| row[left..right].to_vec())
+                            .collect(),
+                    ))
+                }
+            }
+        }
+        (Expr::Matrix(_), _) => Err(ExprError::CalcError {
+            err: "matrices take one or two indexes".into(),
+        }),
+        (Expr::Vector(_), _) | (Expr::Tuple(_), _) => Err(ExprError::CalcError {
+            err: "vectors and tuples take one index".into(),
+        }),
+        _ => Err(ExprError::CalcError {
+            err: "only matrices, vectors and tuples can be indexed".into(),
+        }),
+    }
+}
+
+enum Selection {
+    One(usize),
+    Many(usize, usize),
+}
+
+impl Selection {
+    fn span(self) -> (usize, usize) {
+        match self {
+            Selection::One(i) => (i, i + 1),
+            Selection::Many(from, to) => (from, to),
+        }
+    }
+}
+
+fn select(axis: &Axis, len: usize) -> Result<Selection, ExprError> {
+    let check = |index: i64| {
+        if index < 0 || index as u64 > len as u64 {
+            Err(ExprError::IndexOutOfBounds { index, len })
+        } else {
+            Ok(index as usize)
+        }
+    };
+
+    match *axis {
+        Axis::At(i) if i >= 0 && (i as u64) < len as u64 => Ok(Selection::One(i as usize)),
+        Axis::At(i) => Err(ExprError::IndexOutOfBounds { index: i, len }),
+        Axis::Span(from, to) => {
+            let from = check(from.unwrap_or(0))?;
+            let to = check(to.unwrap_or(len as i64))?;
+
+            if from > to {
+                return Err(ExprError::CalcError {
+                    err: "slice starts after its end".into(),
+                });
+            }
+
+            Ok(Selection::Many(from, to))
+        }
+    }
+}
+
+/// Index value, whole numbers only
+pub fn position(x: &Expr) -> Result<i64, ExprError> {
+    let res = match x {
+        Expr::Integer(x) => x.to_i64(),
+        Expr::Real(x) if x.fract() == 0.0 => x.to_i64(),
+        _ => None,
+    };
+
+    res.ok_or_else(|| ExprError::CalcError {
+        err: format!("index must be a whole number, not {}", x),
+    })
+}
+
+fn count(func: &str, x: &Expr) -> Result<usize, ExprError> {
+    match position(x) {
+        Ok(n) if n >= 0 => Ok(n as usize),
+        _ => Err(ExprError::CalcError {
+            err: format!("{} takes sizes as natural numbers", func),
+        }),
+    }
+}
+
+pub fn real(func: &str, x: &Expr) -> Result<f64, ExprError> {
+    match x {
+        Expr::Real(x) => Ok(*x),
+        Expr::Integer(x) => Ok(x.to_f64().unwrap_or(std::f64::NAN)),
+        Expr::Rational(x) => Ok(crate::to_f64(x)),
+        Expr::BigFloat(x) => Ok(x.to_f64()),
+        _ => Err(ExprError::CalcError {
+            err: format!("{} works only on real numbers", func),
+        }),
+    }
+}
+
+/// Items of a vector or of a matrix row by row
+fn items(func: &str, x: &Expr) -> Result<Vec<f64>, ExprError> {
+    match x {
+        Expr::Vector(v) => Ok(v.clone()),
+        Expr::Matrix(m) => Ok(m.iter().flatten().cloned().collect()),
+        _ => Err(ExprError::CalcError {
+            err: format!("{} works only on vectors and matrices", func),
+        }),
+    }
+}
+
+fn fill(rows: usize, columns: usize, x: f64, limits: &Limits) -> Result<Expr, ExprError> {
+    limits.check_size(rows.checked_mul(columns))?;
+
+    Ok(Expr::Matrix(vec![vec![x; columns]; rows]))
+}
+
+/// `a`, `a + step`, ... up to `b` excluded, as a row
+fn range(a: f64, b: f64, step: f64, limits: &Limits) -> Result<Expr, ExprError> {
+    if step == 0.0 || !step.is_finite() {
+        return Err(ExprError::CalcError {
+            err: "range step must be a finite non-zero number".into(),
+        });
+    }
+
+    // Slightly less than the exact count so that rounding doesn't add `b` itself
+    let len = ((b - a) / step - 1e-10).ceil().max(0.0);
+
+    if !len.is_finite() {
+        return Err(ExprError::CalcError {
+            err: "range ends must be finite".into(),
+        });
+    }
+
+    limits.check_size(len.to_usize())?;
+    let row = (0..len as usize).map(|i| a + step * i as f64).collect();
+
+    Ok(Expr::Matrix(vec![row]))
+}
+
+/// `hcat` puts matrices side by side, `vcat` one under another
+fn concat(name: &str, args: Vec<Expr>) -> Result<Expr, ExprError> {
+    let mut matrices = vec![];
+
+    for arg in args {
+        match arg {
+            Expr::Matrix(m) => matrices.push(m),
+            _ => {
+                return Err(ExprError::CalcError {
+                    err: format!("{} works only on matrices", name),
+                })
+            }
+        }
+    }
+
+    let mismatch = || ExprError::CalcError {
+        err: match name {
+            "hcat" => "hcat needs matrices with the same number of rows".into(),
+            _ => "vcat needs matrices with the same number of columns".into(),
+        },
+    };
+
+    if name == "vcat" {
+        let width = matrices[0].first().map_or(0, |row| row.len());
+
+        if matrices.iter().flatten().any(|row| row.len() != width) {
+            return Err(mismatch());
+        }
+
+        return Ok(Expr::Matrix(matrices.into_iter().flatten().collect()));
+    }
+
+    let height = matrices[0].len();
+
+    if matrices.iter().any(|m| m.len() != height) {
+        return Err(mismatch());
+    }
+
+    Ok(Expr::Matrix(
+        (0..height)
+            .map(|i| matrices.iter().flat_map(|m| m[i].iter().cloned()).collect())
+            .collect(),
+    ))
+}
@@ -0,0 +1,151 @@
+use crate::{Context, Expr, ExprError};
+
+use std::fmt;
+
+/// How many arguments a native function takes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    /// Any number, the function checks them itself
+    Variadic,
+}
+
+/// Function implemented in Rust, registered on a `Context` and called from
+/// expressions like builtins and user functions
+pub trait NativeFunction: Send + Sync {
+    /// Name in expressions, lowercase like every name the parser gives
+    fn name(&self) -> &str;
+
+    fn arity(&self) -> Arity;
+
+    /// Gets its arguments evaluated, already checked against `arity`
+    fn call(&self, args: &[Expr]) -> Result<Expr, ExprError>;
+
+    /// Same arguments always give the same result, so a call on constants
+    /// can be computed once when a function using it is defined
+    fn is_pure(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for dyn NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeFunction({}, {:?})", self.name(), self.arity())
+    }
+}
+
+/// `NativeFunction` made of a closure
+pub(crate) struct Closure<F> {
+    pub name: String,
+    pub arity: Arity,
+    pub func: F,
+}
+
+impl<F> NativeFunction for Closure<F>
+where
+    F: Fn(&[Expr]) -> Result<Expr, ExprError> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    fn call(&self, args: &[Expr]) -> Result<Expr, ExprError> {
+        (self.func)(args)
+    }
+}
+
+/// Calls `func` after checking the number of arguments
+pub(crate) fn call(func: &dyn NativeFunction, args: &[Expr]) -> Result<Expr, ExprError> {
+    match func.arity() {
+        Arity::Exact(n) if n != args.len() => Err(ExprError::BadArgsCount {
+            func: func.name().to_string(),
+        }),
+        _ => func.call(args),
+    }
+}
+
+/// Replaces calls of pure native functions on constants by their values,
+/// calls that fail or go over the limits of `context` are left for the error
+/// to show up when they are run
+pub(crate) fn fold(e: Expr, context: &Context) -> Expr {
+    let fold_box = |x: Box<Expr>| box fold(*x, context);
+
+    match e {
+        Expr::Call(name, args) => {
+            let args: Vec<Expr> = args.into_iter().map(|arg| fold(arg, context)).collect();
+
+            match context.native(&name) {
+                Some(func) if func.is_pure() && is_native(&name, context) => {
+                    let value = values(&args, context)
+                        .and_then(|values| call(&**func, &values).ok())
+                        .filter(|value| context.limits.check(value).is_ok());
+
+                    match value {
+                        Some(value) => value,
+                        None => Expr::Call(name, args),
+                    }
+                }
+                _ => Expr::Call(name, args),
+            }
+        }
+        Expr::Tuple(items) => Expr::Tuple(items.into_iter().map(|x| fold(x, context)).collect()),
+        Expr::Neg(x) => Expr::Neg(fold_box(x)),
+        Expr::Factorial(x) => Expr::Factorial(fold_box(x)),
+        Expr::Add(x, y) => Expr::Add(fold_box(x), fold_box(y)),
+        Expr::Mul(x, y) => Expr::Mul(fold_box(x), fold_box(y)),
+        Expr::Div(x, y) => Expr::Div(fold_box(x), fold_box(y)),
+        Expr::Rem(x, y) => Expr::Rem(fold_box(x), fold_box(y)),
+        Expr::Pow(x, y) => Expr::Pow(fold_box(x), fold_box(y)),
+        Expr::MatrixMul(x, y) => Expr::MatrixMul(fold_box(x), fold_box(y)),
+        Expr::Solve(x, y) => Expr::Solve(fold_box(x), fold_box(y)),
+        Expr::Convert(x, unit) => Expr::Convert(fold_box(x), unit),
+        e => e,
+    }
+}
+
+/// Not shadowed by a user function of the same name
+fn is_native(name: &str, context: &Context) -> bool {
+    !matches!(context.get(name), Some(Expr::Lambda(..)))
+}
+
+/// Arguments as the call would get them at run time, if they are all constants
+fn values(args: &[Expr], context: &Context) -> Option<Vec<Expr>> {
+    // Constants only depend on the settings, and count against the limits of
+    // the evaluation going on
+    let mut scratch = Context {
+        settings: context.settings.clone(),
+        limits: context.limits.clone(),
+        steps: context.steps.clone(),
+        deadline: context.deadline,
+        ..Context::default()
+    };
+
+    args.iter()
+        .map(|arg| {
+            if is_constant(arg) {
+                arg.clone().run(&mut scratch, 0).ok()
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn is_constant(e: &Expr) -> bool {
+    match e {
+        Expr::Real(_)
+        | Expr::Integer(_)
+        | Expr::Rational(_)
+        | Expr::BigFloat(_)
+        | Expr::Complex(..)
+        | Expr::Quantity(_)
+        | Expr::Vector(_)
+        | Expr::Matrix(_) => true,
+        Expr::Neg(ref x) => is_constant(x),
+        _ => false,
+    }
+}
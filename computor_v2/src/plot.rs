@@ -0,0 +1,320 @@
+use std::fmt::Write;
+
+/// Points taken from each function, whatever the output size
+pub const SAMPLES: usize = 1000;
+
+/// Markers that tell curves apart when there are several of them
+const GLYPHS: [char; 6] = ['•', '+', 'x', 'o', '*', '#'];
+
+/// Sampled functions, rendered when displayed
+#[derive(Clone, Debug)]
+pub struct Plot {
+    pub kind: Kind,
+    pub curves: Vec<Curve>,
+    /// Shown range of `x`, the one asked for or the one the points cover
+    pub x: (f64, f64),
+}
+
+/// `y = f(x)`, `(x, y) = (f(t), g(t))` or `r = f(t)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Function,
+    Parametric,
+    Polar,
+}
+
+impl Plot {
+    /// Function plots show `range`, the other kinds whatever their points cover
+    pub fn new(kind: Kind, curves: Vec<Curve>, range: (f64, f64)) -> Plot {
+        let x = match kind {
+            Kind::Function => range,
+            _ => {
+                let xs = curves.iter().flat_map(|curve| curve.points.iter().flatten());
+                let (lo, hi) = xs.fold((std::f64::INFINITY, -std::f64::INFINITY), |acc, p| {
+                    (acc.0.min(p.0), acc.1.max(p.0))
+                });
+
+                if lo < hi {
+                    (lo, hi)
+                } else if lo.is_finite() {
+                    (lo - 1.0, lo + 1.0)
+                } else {
+                    (-1.0, 1.0)
+                }
+            }
+        };
+
+        Plot { kind, curves, x }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Curve {
+    pub label: String,
+    /// `None` where the function could not be evaluated
+    pub points: Vec<Option<(f64, f64)>>,
+}
+
+impl Curve {
+    /// `SAMPLES` evenly spaced values of `f` from `a` to `b`
+    pub fn sample(label: String, f: &mut dyn FnMut(f64) -> Option<f64>, a: f64, b: f64) -> Curve {
+        Curve::trace(label, &mut |x| f(x).map(|y| (x, y)), a, b)
+    }
+
+    /// Points `f(t)` for `SAMPLES` evenly spaced `t` from `a` to `b`
+    pub fn trace(
+        label: String,
+        f: &mut dyn FnMut(f64) -> Option<(f64, f64)>,
+        a: f64,
+        b: f64,
+    ) -> Curve {
+        let points = (0..SAMPLES)
+            .map(|i| {
+                let t = a + (b - a) * i as f64 / (SAMPLES - 1) as f64;
+
+                f(t).filter(|p| p.0.is_finite() && p.1.is_finite())
+            })
+            .collect();
+
+        Curve { label, points }
+    }
+}
+
+/// Columns and rows of the terminal, 80x24 when it can't be told
+pub fn terminal() -> (usize, usize) {
+    match terminal_size::terminal_size() {
+        Some((terminal_size::Width(w), terminal_size::Height(h))) => (w as usize, h as usize),
+        None => (80, 24),
+    }
+}
+
+/// Shown range of `y`: everything, unless a few points (near poles) would
+/// squash the rest into a line
+pub fn y_range(plot: &Plot) -> (f64, f64) {
+    let mut ys: Vec<f64> = plot
+        .curves
+        .iter()
+        .flat_map(|curve| curve.points.iter().flatten().map(|p| p.1))
+        .collect();
+
+    if ys.is_empty() {
+        return (-1.0, 1.0);
+    }
+
+    ys.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let quantile = |q: f64| ys[((ys.len() - 1) as f64 * q).round() as usize];
+    let (min, max) = (ys[0], ys[ys.len() - 1]);
+    let (lo, hi) = (quantile(0.02), quantile(0.98));
+
+    let (lo, hi) = if hi > lo && max - min > 10.0 * (hi - lo) {
+        (lo - (hi - lo) * 0.1, hi + (hi - lo) * 0.1)
+    } else {
+        (min, max)
+    };
+
+    if hi - lo <= 1e-12 * lo.abs().max(1.0) {
+        (lo - 1.0, hi + 1.0)
+    } else {
+        (lo, hi)
+    }
+}
+
+/// Round values about a quarter of the range apart: 1, 2 or 5 times a power of ten
+pub fn ticks(a: f64, b: f64) -> (Vec<f64>, f64) {
+    let rough = (b - a) / 4.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * magnitude);
+
+    let first = (a / step).ceil() as i64;
+    let last = (b / step).floor() as i64;
+
+    ((first..=last).map(|k| k as f64 * step).collect(), step)
+}
+
+/// Tick label with as many decimals as the distance between ticks needs
+pub fn tick_label(x: f64, step: f64) -> String {
+    if x != 0.0 && (x.abs() >= 1e6 || step < 1e-4) {
+        return format!("{:.2e}", x);
+    }
+
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let res = format!("{:.*}", decimals, x);
+
+    // No "-0.00"
+    if res.starts_with('-') && res.chars().all(|c| c == '-' || c == '0' || c == '.') {
+        res[1..].to_string()
+    } else {
+        res
+    }
+}
+
+/// Which of `cells` cells of `dots` dots each from `a` to `b` has `x` in it
+fn cell(a: f64, b: f64, cells: usize, dots: usize, x: f64) -> usize {
+    let dot = ((x - a) / (b - a) * (cells * dots - 1) as f64).round().max(0.0) as usize;
+
+    (dot / dots).min(cells - 1)
+}
+
+/// Character cell chart, braille dots for one curve and a glyph per curve for more
+pub fn render(plot: &Plot, (width, height): (usize, usize)) -> String {
+    let (x0, x1) = plot.x;
+    let (lo, hi) = y_range(plot);
+
+    let rows = height.saturating_sub(4).max(5);
+    let (values, step) = ticks(lo, hi);
+    let y_ticks: Vec<(usize, String)> = values
+        .into_iter()
+        .map(|y| (cell(hi, lo, rows, 4, y), tick_label(y, step)))
+        .collect();
+    let margin = y_ticks.iter().map(|(_, label)| label.chars().count()).max().unwrap_or(0);
+    let columns = width.saturating_sub(margin + 3).max(10);
+
+    // Dots of braille cells, 2 wide and 4 high, and the curve that drew last in each cell
+    let (px, py) = (2 * columns, 4 * rows);
+    let mut dots = vec![vec![0u8; columns]; rows];
+    let mut owner = vec![vec![None; columns]; rows];
+
+    let to_pixel = |(x, y): (f64, f64)| {
+        (
+            (x - x0) / (x1 - x0) * (px - 1) as f64,
+            (hi - y) / (hi - lo) * (py - 1) as f64,
+        )
+    };
+
+    for (k, curve) in plot.curves.iter().enumerate() {
+        let mut set = |x: f64, y: f64| {
+            let (x, y) = (x.round(), y.round());
+
+            if x >= 0.0 && y >= 0.0 && (x as usize) < px && (y as usize) < py {
+                let (x, y) = (x as usize, y as usize);
+                let bit = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]][x % 2][y % 4];
+
+                dots[y / 4][x / 2] |= bit;
+                owner[y / 4][x / 2] = Some(k);
+            }
+        };
+
+        for pair in curve.points.windows(2) {
+            match (pair[0], pair[1]) {
+                (Some(a), Some(b)) => {
+                    let ((ax, ay), (bx, by)) = (to_pixel(a), to_pixel(b));
+
+                    // A jump over the whole height is a pole, not a steep line
+                    if (ay - by).abs() > py as f64 {
+                        set(ax, ay);
+                        continue;
+                    }
+
+                    let steps = (ax - bx).abs().max((ay - by).abs()).ceil().max(1.0) as usize;
+
+                    for i in 0..=steps {
+                        let t = i as f64 / steps as f64;
+                        set(ax + (bx - ax) * t, ay + (by - ay) * t);
+                    }
+                }
+                (Some(a), None) => {
+                    let (x, y) = to_pixel(a);
+                    set(x, y);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(Some(last)) = curve.points.last() {
+            let (x, y) = to_pixel(*last);
+            set(x, y);
+        }
+    }
+
+    let zero_column = if x0 <= 0.0 && 0.0 <= x1 {
+        Some(cell(x0, x1, columns, 2, 0.0))
+    } else {
+        None
+    };
+    let zero_row = if lo <= 0.0 && 0.0 <= hi {
+        Some(cell(hi, lo, rows, 4, 0.0))
+    } else {
+        None
+    };
+
+    let mut res = String::new();
+
+    for row in 0..rows {
+        let label = y_ticks.iter().find(|tick| tick.0 == row);
+        let (label, frame) = match label {
+            Some((_, label)) => (label.as_str(), '┤'),
+            None => ("", '│'),
+        };
+
+        let _ = write!(res, "{:>width$} {}", label, frame, width = margin);
+
+        for column in 0..columns {
+            let c = match owner[row][column] {
+                Some(_) if plot.curves.len() == 1 => {
+                    std::char::from_u32(0x2800 + dots[row][column] as u32).unwrap_or('?')
+                }
+                Some(k) => GLYPHS[k % GLYPHS.len()],
+                None if Some(row) == zero_row && Some(column) == zero_column => '┼',
+                None if Some(row) == zero_row => '─',
+                None if Some(column) == zero_column => '│',
+                None => ' ',
+            };
+
+            res.push(c);
+        }
+
+        res.push('\n');
+    }
+
+    let (values, step) = ticks(x0, x1);
+    let x_ticks: Vec<(usize, String)> = values
+        .into_iter()
+        .map(|x| (cell(x0, x1, columns, 2, x), tick_label(x, step)))
+        .collect();
+
+    res += &" ".repeat(margin + 1);
+    res.push('└');
+    for column in 0..columns {
+        res.push(if x_ticks.iter().any(|tick| tick.0 == column) {
+            '┬'
+        } else {
+            '─'
+        });
+    }
+    res.push('\n');
+
+    // Labels centered under their ticks when there's room
+    let mut labels = String::new();
+    for (column, label) in x_ticks {
+        let len = label.chars().count();
+        let start = (margin + 2 + column).saturating_sub(len / 2);
+        let start = start.min((margin + 2 + columns).saturating_sub(len));
+        let used = labels.chars().count();
+
+        if used == 0 || start > used {
+            labels += &" ".repeat(start - used);
+            labels += &label;
+        }
+    }
+    res += &labels;
+
+    if plot.curves.len() > 1 {
+        let legend: Vec<String> = plot
+            .curves
+            .iter()
+            .enumerate()
+            .map(|(k, curve)| format!("{} {}", GLYPHS[k % GLYPHS.len()], curve.label))
+            .collect();
+
+        res.push('\n');
+        res += &" ".repeat(margin + 2);
+        res += &legend.join("   ");
+    }
+
+    res
+}
@@ -0,0 +1,51 @@
+// How tightly a rendered expression holds together, shared by the LaTeX,
+// Unicode and MathML printers. Parents put anything looser than they need
+// in parentheses
+pub const LOWEST: u8 = 0;
+pub const SUM: u8 = 1;
+/// Starts with a minus sign
+pub const NEG: u8 = 2;
+pub const PRODUCT: u8 = 3;
+pub const POWER: u8 = 4;
+pub const ATOM: u8 = 5;
+
+/// What `-x` needs of `x`
+pub const NEG_OPERAND: u8 = PRODUCT;
+/// What `x * y` needs of `x` and `y`, a minus is fine on the left only
+pub const FACTORS: (u8, u8) = (NEG, PRODUCT);
+/// What `x + y` needs of `x`
+pub const ADDEND: u8 = SUM;
+
+/// How the right side of a sum is joined to the left one
+pub enum Join {
+    /// `x + -1` is `x - 1`, the right side loses its minus
+    Minus,
+    /// `x + (a = 1)`, or a minus that does not start a negation
+    Parens,
+    Plus,
+}
+
+/// Join for a right side of precedence `prec`, which starts with a minus if `minus`
+pub fn join(prec: u8, minus: bool) -> Join {
+    if prec == NEG {
+        Join::Minus
+    } else if prec < SUM || minus {
+        Join::Parens
+    } else {
+        Join::Plus
+    }
+}
+
+/// Whether something of precedence `prec` needs parentheses where `needed` is needed
+pub fn looser(prec: u8, needed: u8) -> bool {
+    prec < needed
+}
+
+/// Precedence of a rendered node, no tighter than a negation if it starts with a minus
+pub fn signed(prec: u8, minus: bool) -> u8 {
+    if minus {
+        prec.min(NEG)
+    } else {
+        prec
+    }
+}
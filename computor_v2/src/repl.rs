@@ -0,0 +1,23 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// `$XDG_STATE_HOME/computor_v2/history`, falling back to `~/.local/state`
+/// when the variable is unset or not an absolute path
+pub fn history_path(state_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    let state_home = match state_home.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => PathBuf::from(home?).join(".local").join("state"),
+    };
+
+    Some(state_home.join("computor_v2").join("history"))
+}
+
+/// `expr > "path"` writes the value of `expr`, a plot, to a file
+pub fn redirect(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_end();
+    let end = line.len().checked_sub(1).filter(|_| line.ends_with('"'))?;
+    let start = line[..end].rfind('"')?;
+    let expr = line[..start].trim_end();
+
+    Some((expr.strip_suffix('>')?, &line[start + 1..end]))
+}
@@ -0,0 +1,279 @@
+use crate::computor_v1::{Factors, Poly};
+use crate::{rational, Expr, ExprError};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, ToPrimitive, Zero};
+
+/// Antiderivative of a function body with respect to `x`, other names are constants
+pub fn antideriv(e: &Expr, x: &str) -> Result<Expr, ExprError> {
+    if !depends(e, x) {
+        return Ok(mul(e.clone(), var(x)));
+    }
+
+    if let Some(p) = polynomial(e, x) {
+        return Ok(from_poly(&p.integral(), x));
+    }
+
+    let fail = || ExprError::NoAntiderivative {
+        expr: e.to_string(),
+    };
+
+    match e {
+        Expr::Add(box a, box b) => Ok(add(antideriv(a, x)?, antideriv(b, x)?)),
+        Expr::Neg(box a) => Ok(neg(antideriv(a, x)?)),
+        Expr::Mul(box c, box f) if !depends(c, x) => Ok(mul(c.clone(), antideriv(f, x)?)),
+        Expr::Mul(box f, box c) if !depends(c, x) => Ok(mul(c.clone(), antideriv(f, x)?)),
+        Expr::Div(box f, box c) if !depends(c, x) => Ok(div(antideriv(f, x)?, c.clone())),
+        Expr::Div(box c, box u) if !depends(c, x) => {
+            let inverse = Expr::Pow(box u.clone(), box Expr::Integer(-BigInt::one()));
+
+            Ok(mul(c.clone(), antideriv(&inverse, x)?))
+        }
+        // u^n and c^u, `u` linear in `x`
+        Expr::Pow(box u, box n) if !depends(n, x) => {
+            let a = linear(u, x).ok_or_else(fail)?;
+
+            if number(n) == Some(-BigRational::one()) {
+                Ok(div(call("ln", call("abs", u.clone())), a))
+            } else {
+                let n = add(n.clone(), one());
+
+                Ok(div(pow(u.clone(), n.clone()), mul(n, a)))
+            }
+        }
+        Expr::Pow(box c, box u) if !depends(c, x) => {
+            let a = linear(u, x).ok_or_else(fail)?;
+
+            match c {
+                Expr::Var(ref name) if name == "e" => Ok(div(pow(c.clone(), u.clone()), a)),
+                _ => Ok(div(pow(c.clone(), u.clone()), mul(a, call("ln", c.clone())))),
+            }
+        }
+        Expr::Call(ref name, ref args) if args.len() == 1 => {
+            let u = &args[0];
+            let a = linear(u, x).ok_or_else(fail)?;
+
+            match name.as_str() {
+                "exp" => Ok(div(call("exp", u.clone()), a)),
+                "sin" => Ok(neg(div(call("cos", u.clone()), a))),
+                "cos" => Ok(div(call("sin", u.clone()), a)),
+                "sqrt" => {
+                    let three_halves = ratio(3, 2);
+
+                    Ok(div(mul(ratio(2, 3), pow(u.clone(), three_halves)), a))
+                }
+                _ => Err(fail()),
+            }
+        }
+        _ => Err(fail()),
+    }
+}
+
+fn depends(e: &Expr, x: &str) -> bool {
+    match e {
+        // A unit named like the argument is the argument
+        Expr::Var(ref name) | Expr::Unit(ref name) => name == x,
+        Expr::Call(_, ref args) => args.iter().any(|arg| depends(arg, x)),
+        Expr::Lambda(..) => false,
+        Expr::Neg(ref a) | Expr::Factorial(ref a) => depends(a, x),
+        Expr::Add(ref a, ref b)
+        | Expr::Mul(ref a, ref b)
+        | Expr::Div(ref a, ref b)
+        | Expr::Rem(ref a, ref b)
+        | Expr::Pow(ref a, ref b)
+        | Expr::MatrixMul(ref a, ref b)
+        | Expr::Solve(ref a, ref b)
+        | Expr::Convert(ref a, ref b) => depends(a, x) || depends(b, x),
+        Expr::Index(ref a, _) => depends(a, x),
+        _ => false,
+    }
+}
+
+/// Degree past which a polynomial is left alone, expanding `((x^64)^64)^64`
+/// would never end
+const MAX_DEGREE: usize = 1000;
+
+/// `e` as a polynomial in `x` with exact coefficients, if it is one of degree
+/// up to `MAX_DEGREE`
+pub fn polynomial(e: &Expr, x: &str) -> Option<Poly> {
+    match e {
+        Expr::Integer(_) | Expr::Rational(_) => Some(Poly::constant(number(e)?)),
+        Expr::BigFloat(ref c) => Some(Poly::constant(c.to_rational())),
+        Expr::Var(ref name) | Expr::Unit(ref name) if name == x => Some(Poly::x()),
+        Expr::Neg(box a) => Some(polynomial(a, x)?.neg()),
+        Expr::Add(box a, box b) => Some(polynomial(a, x)?.add(&polynomial(b, x)?)),
+        Expr::Mul(box a, box b) => {
+            let p = polynomial(a, x)?.mul(&polynomial(b, x)?);
+
+            Some(p).filter(|p| p.degree() <= MAX_DEGREE)
+        }
+        Expr::Div(box a, box b) => {
+            let b = polynomial(b, x).filter(|b| b.degree() == 0 && !b.is_zero())?;
+
+            Some(polynomial(a, x)?.scale(&b.leading().recip()))
+        }
+        Expr::Pow(box a, box Expr::Integer(ref n)) => {
+            let n = n.to_u32().filter(|&n| n <= 64)?;
+            let a = polynomial(a, x).filter(|a| a.degree() * n as usize <= MAX_DEGREE)?;
+
+            Some(a.pow(n))
+        }
+        _ => None,
+    }
+}
+
+/// Highest power first
+pub fn from_poly(p: &Poly, x: &str) -> Expr {
+    p.coefs()
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, c)| !c.is_zero())
+        .map(|(n, c)| mul(rational(c.clone()), pow(var(x), Expr::Integer(BigInt::from(n)))))
+        .fold(Expr::Integer(BigInt::zero()), add)
+}
+
+/// `c * (x - 1) * (2 * x + 1)^2 * (x - 1.414)`, exact factors before numeric ones
+pub fn from_factors(factors: &Factors, x: &str) -> Expr {
+    let exact = factors.exact.iter().map(|(p, n)| (from_poly(p, x), *n));
+    let numeric = factors.numeric.iter().map(|&((re, im), n)| {
+        let root = if im == 0.0 {
+            Expr::Real(-re)
+        } else {
+            Expr::Complex(-re, -im)
+        };
+
+        (Expr::Add(box var(x), box root), n)
+    });
+
+    let product = exact
+        .chain(numeric)
+        .map(|(e, n)| pow(e, Expr::Integer(BigInt::from(n))))
+        .fold(None, |acc, e| match acc {
+            Some(acc) => Some(Expr::Mul(box acc, box e)),
+            None => Some(e),
+        });
+
+    match product {
+        Some(e) if (-factors.constant.clone()).is_one() => Expr::Neg(box e),
+        Some(e) => mul(rational(factors.constant.clone()), e),
+        None => rational(factors.constant.clone()),
+    }
+}
+
+/// Argument name and polynomial body of a one argument function
+pub fn function_poly(func: &str, f: &Expr) -> Result<(String, Poly), ExprError> {
+    match f {
+        Expr::Lambda(ref names, ref body) if names.len() == 1 => match polynomial(body, &names[0]) {
+            Some(p) => Ok((names[0].clone(), p)),
+            None => Err(ExprError::CalcError {
+                err: format!("{} is not a polynomial in {}", body, names[0]),
+            }),
+        },
+        _ => Err(ExprError::CalcError {
+            err: format!("{} works only on one argument functions", func),
+        }),
+    }
+}
+
+/// `a` of `u = a x + b`, `None` if `u` isn't like that
+fn linear(u: &Expr, x: &str) -> Option<Expr> {
+    fn coefficient(u: &Expr, x: &str) -> Option<Expr> {
+        match u {
+            _ if !depends(u, x) => Some(Expr::Integer(BigInt::zero())),
+            Expr::Var(_) | Expr::Unit(_) => Some(one()),
+            Expr::Add(box p, box q) => Some(add(coefficient(p, x)?, coefficient(q, x)?)),
+            Expr::Neg(box p) => Some(neg(coefficient(p, x)?)),
+            Expr::Mul(box c, box p) | Expr::Mul(box p, box c) if !depends(c, x) => {
+                Some(mul(c.clone(), coefficient(p, x)?))
+            }
+            Expr::Div(box p, box c) if !depends(c, x) => Some(div(coefficient(p, x)?, c.clone())),
+            _ => None,
+        }
+    }
+
+    coefficient(u, x).filter(|a| !number(a).map_or(false, |a| a.is_zero()))
+}
+
+fn number(e: &Expr) -> Option<BigRational> {
+    match e {
+        Expr::Integer(ref x) => Some(BigRational::from_integer(x.clone())),
+        Expr::Rational(ref x) => Some(x.clone()),
+        // `x^-1` is parsed as `x^(-1)`
+        Expr::Neg(box x) => number(x).map(|x| -x),
+        _ => None,
+    }
+}
+
+fn one() -> Expr {
+    Expr::Integer(BigInt::one())
+}
+
+fn ratio(numer: i32, denom: i32) -> Expr {
+    Expr::Rational(BigRational::new(BigInt::from(numer), BigInt::from(denom)))
+}
+
+fn var(x: &str) -> Expr {
+    Expr::Var(x.to_string())
+}
+
+fn call(name: &str, arg: Expr) -> Expr {
+    Expr::Call(name.to_string(), vec![arg])
+}
+
+// Node builders that fold numbers and drop zeros and ones as they go
+
+fn add(a: Expr, b: Expr) -> Expr {
+    match (number(&a), number(&b)) {
+        (Some(x), Some(y)) => rational(x + y),
+        (Some(ref x), _) if x.is_zero() => b,
+        (_, Some(ref y)) if y.is_zero() => a,
+        _ => Expr::Add(box a, box b),
+    }
+}
+
+fn neg(a: Expr) -> Expr {
+    match (number(&a), a) {
+        (Some(x), _) => rational(-x),
+        (_, Expr::Neg(box a)) => a,
+        (_, a) => Expr::Neg(box a),
+    }
+}
+
+fn mul(a: Expr, b: Expr) -> Expr {
+    match (number(&a), number(&b)) {
+        (Some(x), Some(y)) => return rational(x * y),
+        (Some(ref x), _) | (_, Some(ref x)) if x.is_zero() => return Expr::Integer(BigInt::zero()),
+        (Some(ref x), _) if x.is_one() => return b,
+        (_, Some(ref y)) if y.is_one() => return a,
+        (Some(ref x), _) if (-x).is_one() => return neg(b),
+        (None, Some(_)) => return mul(b, a),
+        _ => {}
+    }
+
+    // Numbers gather in front: 3 * (1/3 * x^3) is x^3
+    match (number(&a), b) {
+        (Some(x), Expr::Mul(box c, box e)) => match number(&c) {
+            Some(y) => mul(rational(x * y), e),
+            None => Expr::Mul(box a, box Expr::Mul(box c, box e)),
+        },
+        (_, b) => Expr::Mul(box a, box b),
+    }
+}
+
+fn div(a: Expr, b: Expr) -> Expr {
+    match number(&b) {
+        Some(ref y) if y.is_one() => a,
+        Some(ref y) if !y.is_zero() => mul(rational(y.recip()), a),
+        _ => Expr::Div(box a, box b),
+    }
+}
+
+fn pow(a: Expr, n: Expr) -> Expr {
+    match number(&n) {
+        Some(ref n) if n.is_one() => a,
+        Some(ref n) if n.is_zero() => one(),
+        _ => Expr::Pow(box a, box n),
+    }
+}
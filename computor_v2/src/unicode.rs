@@ -0,0 +1,504 @@
+use crate::format::{format_bigfloat, format_integer, format_real, Settings};
+use crate::precedence::{
+    self, Join, ADDEND, ATOM, FACTORS, LOWEST, NEG, NEG_OPERAND, POWER, PRODUCT, SUM,
+};
+use crate::{units, Expr, Slice};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed};
+
+const GREEK: [(&str, char); 24] = [
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("epsilon", 'ε'),
+    ("zeta", 'ζ'),
+    ("eta", 'η'),
+    ("theta", 'θ'),
+    ("iota", 'ι'),
+    ("kappa", 'κ'),
+    ("lambda", 'λ'),
+    ("mu", 'μ'),
+    ("nu", 'ν'),
+    ("xi", 'ξ'),
+    ("pi", 'π'),
+    ("rho", 'ρ'),
+    ("sigma", 'σ'),
+    ("tau", 'τ'),
+    ("upsilon", 'υ'),
+    ("phi", 'φ'),
+    ("chi", 'χ'),
+    ("psi", 'ψ'),
+    ("omega", 'ω'),
+    ("varepsilon", 'ϵ'),
+];
+
+/// Letter for a spelled out greek name like `pi`
+pub(crate) fn greek(name: &str) -> Option<char> {
+    GREEK.iter().find(|&&(x, _)| x == name).map(|&(_, c)| c)
+}
+
+/// Rectangle of text, lines padded to the same width, `baseline` is the row
+/// operators around it line up with
+struct Block {
+    lines: Vec<String>,
+    baseline: usize,
+}
+
+impl Block {
+    fn text(s: &str) -> Block {
+        Block {
+            lines: vec![s.to_string()],
+            baseline: 0,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.lines[0].chars().count()
+    }
+
+    fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Side by side, baselines lined up
+    fn row(blocks: Vec<Block>) -> Block {
+        let above = blocks.iter().map(|b| b.baseline).max().unwrap_or(0);
+        let below = blocks.iter().map(|b| b.height() - b.baseline - 1).max().unwrap_or(0);
+        let mut lines = vec![String::new(); above + below + 1];
+
+        for b in &blocks {
+            let blank = " ".repeat(b.width());
+            let top = above - b.baseline;
+
+            for (i, line) in lines.iter_mut().enumerate() {
+                match i.checked_sub(top).and_then(|i| b.lines.get(i)) {
+                    Some(part) => *line += part,
+                    None => *line += &blank,
+                }
+            }
+        }
+
+        Block {
+            lines,
+            baseline: above,
+        }
+    }
+
+    /// Stacked fraction, the bar on the baseline
+    fn over(numer: Block, denom: Block) -> Block {
+        let width = numer.width().max(denom.width()) + 2;
+        let center = |b: Block| {
+            let left = (width - b.width()) / 2;
+            let right = width - b.width() - left;
+
+            b.lines
+                .into_iter()
+                .map(move |line| " ".repeat(left) + &line + &" ".repeat(right))
+        };
+        let baseline = numer.height();
+
+        let mut lines: Vec<String> = center(numer).collect();
+        lines.push("─".repeat(width));
+        lines.extend(center(denom));
+
+        Block { lines, baseline }
+    }
+
+    /// Between `open` and `close`, `(top, middle, bottom)` pieces when it is
+    /// more than one line high
+    fn fence(self, open: (char, char, char, char), close: (char, char, char, char)) -> Block {
+        let column = |(single, top, middle, bottom): (char, char, char, char)| {
+            let height = self.height();
+            let lines = (0..height)
+                .map(|i| match i {
+                    _ if height == 1 => single,
+                    0 => top,
+                    i if i == height - 1 => bottom,
+                    _ => middle,
+                })
+                .map(|c| c.to_string())
+                .collect();
+
+            Block {
+                lines,
+                baseline: self.baseline,
+            }
+        };
+        let (left, right) = (column(open), column(close));
+
+        Block::row(vec![left, self, right])
+    }
+
+    fn parens(self) -> Block {
+        self.fence(('(', '⎛', '⎜', '⎝'), (')', '⎞', '⎟', '⎠'))
+    }
+
+    fn starts_with_minus(&self) -> bool {
+        self.lines[self.baseline].starts_with('-')
+    }
+
+    /// Without the first column, the minus sign of a negative block
+    fn strip(self) -> Block {
+        let lines = self.lines.iter().map(|line| line.chars().skip(1).collect()).collect();
+
+        Block {
+            lines,
+            baseline: self.baseline,
+        }
+    }
+}
+
+/// Terminal picture of `e`, fractions stacked and exponents raised
+pub fn unicode(e: &Expr, settings: &Settings) -> String {
+    let lines: Vec<String> = node(e, settings)
+        .0
+        .lines
+        .into_iter()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+
+    lines.join("\n")
+}
+
+fn node(e: &Expr, s: &Settings) -> (Block, u8) {
+    let text = |x: &str| Block::text(x);
+    let (res, prec) = match e {
+        Expr::Real(ref x) => number(&format_real(*x, s)),
+        Expr::Integer(ref x) => number(&format_integer(x, s)),
+        Expr::BigFloat(ref x) => number(&format_bigfloat(x, s)),
+        Expr::Rational(ref x) if x.denom().is_one() => number(&format_integer(x.numer(), s)),
+        Expr::Rational(ref x) => {
+            let numer = text(&format_integer(&x.numer().abs(), s));
+            let frac = Block::over(numer, text(&format_integer(x.denom(), s)));
+
+            if x.is_negative() {
+                (Block::row(vec![text("-"), frac]), NEG)
+            } else {
+                (frac, PRODUCT)
+            }
+        }
+        Expr::Complex(re, im) => complex(*re, *im, s),
+        Expr::Quantity(ref x) => {
+            let (value, unit) = match x.unit {
+                Some((ref name, size)) => (x.value / size, name.clone()),
+                None => (x.value, units::base_units(&x.dim)),
+            };
+            let value = number(&format_real(value, s)).0;
+
+            (Block::row(vec![value, text(" "), text(&unit_name(&unit))]), PRODUCT)
+        }
+        Expr::Var(ref name) => (text(&ident(name)), ATOM),
+        Expr::Unit(_) => (text(&unit_name(&units::unit_name(e))), ATOM),
+        Expr::Lambda(ref args, ref body) => {
+            let args: Vec<String> = args.iter().map(|x| ident(x)).collect();
+            let args = if args.len() == 1 {
+                args[0].clone()
+            } else {
+                format!("({})", args.join(", "))
+            };
+
+            (Block::row(vec![text(&args), text(" ↦ "), node(body, s).0]), LOWEST)
+        }
+        Expr::Call(ref name, ref args) => (call(name, args, s), ATOM),
+        Expr::Vector(ref x) => (matrix(std::slice::from_ref(x), s), ATOM),
+        Expr::Matrix(ref x) => (matrix(x, s), ATOM),
+        Expr::Tuple(ref items) => (list(items, s).parens(), ATOM),
+        Expr::Plot(_) => (text("(plot)"), ATOM),
+        Expr::Index(ref x, ref slices) => {
+            let end = |x: &Option<Expr>| match x {
+                Some(x) => node(x, s).0,
+                None => text(""),
+            };
+            let mut parts = vec![wrap(x, s, ATOM), text("[")];
+
+            for (i, slice) in slices.iter().enumerate() {
+                if i > 0 {
+                    parts.push(text(", "));
+                }
+                match slice {
+                    Slice::At(x) => parts.push(node(x, s).0),
+                    Slice::Range(from, to) => parts.extend(vec![end(from), text(":"), end(to)]),
+                }
+            }
+            parts.push(text("]"));
+
+            (Block::row(parts), ATOM)
+        }
+        Expr::Neg(ref x) => (Block::row(vec![text("-"), wrap(x, s, NEG_OPERAND)]), NEG),
+        Expr::Factorial(ref x) => (Block::row(vec![wrap(x, s, ATOM), text("!")]), POWER),
+        Expr::Add(ref x, ref y) => {
+            let left = wrap(x, s, ADDEND);
+            let (right, prec) = node(y, s);
+
+            match precedence::join(prec, right.starts_with_minus()) {
+                Join::Minus => (Block::row(vec![left, text(" - "), right.strip()]), SUM),
+                Join::Parens => (Block::row(vec![left, text(" + "), right.parens()]), SUM),
+                Join::Plus => (Block::row(vec![left, text(" + "), right]), SUM),
+            }
+        }
+        Expr::Mul(ref x, ref y) => {
+            let left = wrap(x, s, FACTORS.0);
+            let right = wrap(y, s, FACTORS.1);
+
+            // 2x, but 2·3
+            let implicit = match **x {
+                Expr::Integer(_) | Expr::Real(_) | Expr::BigFloat(_) => {
+                    right.height() == 1 && right.lines[0].starts_with(char::is_alphabetic)
+                }
+                _ => false,
+            };
+            let op = if implicit { "" } else { "·" };
+
+            (Block::row(vec![left, text(op), right]), PRODUCT)
+        }
+        Expr::Div(ref x, ref y) => (Block::over(node(x, s).0, node(y, s).0), PRODUCT),
+        Expr::Rem(ref x, ref y) => {
+            (Block::row(vec![wrap(x, s, NEG), text(" mod "), wrap(y, s, POWER)]), PRODUCT)
+        }
+        Expr::Pow(ref x, box Expr::Rational(ref n))
+            if n.numer().is_one() && *n.denom() == BigInt::from(2) =>
+        {
+            (root(node(x, s).0), ATOM)
+        }
+        Expr::Pow(ref x, ref y) => (power(wrap(x, s, ATOM), node(y, s).0), POWER),
+        Expr::MatrixMul(ref x, ref y) => {
+            (Block::row(vec![wrap(x, s, NEG), text(" "), wrap(y, s, PRODUCT)]), PRODUCT)
+        }
+        Expr::Solve(ref x, ref y) => {
+            let inverse = power(wrap(x, s, ATOM), text("-1"));
+
+            (Block::row(vec![inverse, text(" "), wrap(y, s, PRODUCT)]), PRODUCT)
+        }
+        Expr::Convert(ref x, ref unit) => {
+            (Block::row(vec![node(x, s).0, text(" → "), node(unit, s).0]), LOWEST)
+        }
+        Expr::AssignVar(ref name, ref val) => {
+            (Block::row(vec![text(&ident(name)), text(" = "), node(val, s).0]), LOWEST)
+        }
+        Expr::AssignFunc(ref head, ref body) => {
+            (Block::row(vec![node(head, s).0, text(" = "), node(body, s).0]), LOWEST)
+        }
+    };
+
+    let prec = precedence::signed(prec, res.starts_with_minus());
+
+    (res, prec)
+}
+
+/// `e`, in parentheses if it is looser than `prec`
+fn wrap(e: &Expr, s: &Settings, prec: u8) -> Block {
+    match node(e, s) {
+        (res, p) if precedence::looser(p, prec) => res.parens(),
+        (res, _) => res,
+    }
+}
+
+fn list(items: &[Expr], s: &Settings) -> Block {
+    let mut parts = Vec::new();
+
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            parts.push(Block::text(", "));
+        }
+        parts.push(node(item, s).0);
+    }
+
+    Block::row(parts)
+}
+
+/// `¹²³`, if every char of `s` has a superscript form
+fn superscript(s: &str) -> Option<String> {
+    s.chars()
+        .map(|c| match c {
+            '0' => Some('⁰'),
+            '1' => Some('¹'),
+            '2' => Some('²'),
+            '3' => Some('³'),
+            '4' => Some('⁴'),
+            '5' => Some('⁵'),
+            '6' => Some('⁶'),
+            '7' => Some('⁷'),
+            '8' => Some('⁸'),
+            '9' => Some('⁹'),
+            '-' => Some('⁻'),
+            '+' => Some('⁺'),
+            '(' => Some('⁽'),
+            ')' => Some('⁾'),
+            'i' => Some('ⁱ'),
+            'n' => Some('ⁿ'),
+            ' ' => Some(' '),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Exponent in superscript chars when it can be written that way, raised above
+/// the base when not
+fn power(base: Block, exp: Block) -> Block {
+    if exp.height() == 1 {
+        if let Some(sup) = superscript(&exp.lines[0]) {
+            let blank = " ".repeat(sup.chars().count());
+            let lines = base
+                .lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| if i == 0 { line.clone() + &sup } else { line.clone() + &blank })
+                .collect();
+
+            return Block {
+                lines,
+                baseline: base.baseline,
+            };
+        }
+    }
+
+    let (base_width, exp_width) = (base.width(), exp.width());
+    let baseline = exp.height() + base.baseline;
+    let mut lines: Vec<String> =
+        exp.lines.into_iter().map(|line| " ".repeat(base_width) + &line).collect();
+    lines.extend(base.lines.into_iter().map(|line| line + &" ".repeat(exp_width)));
+
+    Block { lines, baseline }
+}
+
+/// Square root sign with a bar over `b`
+fn root(b: Block) -> Block {
+    let mut lines = vec![" ".to_string() + &"_".repeat(b.width())];
+    let baseline = b.baseline + 1;
+
+    for (i, line) in b.lines.into_iter().enumerate() {
+        let sign = if i == b.baseline { "√" } else { " " };
+        lines.push(sign.to_string() + &line);
+    }
+
+    Block { lines, baseline }
+}
+
+/// Number printed by `format`, `1.5e-7` as `1.5×10⁻⁷`
+fn number(x: &str) -> (Block, u8) {
+    let x = match x {
+        "inf" => "∞",
+        "-inf" => "-∞",
+        x => x,
+    };
+
+    match x.find('e').filter(|_| x != "NaN") {
+        Some(pos) => {
+            let exp = superscript(&x[pos + 1..]).unwrap_or_default();
+
+            (Block::text(&format!("{}×10{}", &x[..pos], exp)), PRODUCT)
+        }
+        None => (Block::text(x), ATOM),
+    }
+}
+
+fn complex(re: f64, im: f64, s: &Settings) -> (Block, u8) {
+    if im == 0.0 {
+        return number(&format_real(re, s));
+    }
+
+    let abs = if im.abs() == 1.0 {
+        "i".to_string()
+    } else {
+        format_real(im.abs(), s) + "i"
+    };
+    let sign = if im < 0.0 { "-" } else { "+" };
+
+    if re != 0.0 {
+        let text = format!("{} {} {}", format_real(re, s), sign, abs);
+
+        (Block::text(&text), SUM)
+    } else if im < 0.0 {
+        (Block::text(&format!("-{}", abs)), NEG)
+    } else {
+        (Block::text(&abs), PRODUCT)
+    }
+}
+
+/// Columns aligned to the right between tall brackets
+fn matrix(rows: &[Vec<f64>], s: &Settings) -> Block {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|x| format_real(*x, s)).collect())
+        .collect();
+    let columns = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|j| {
+            cells
+                .iter()
+                .filter_map(|row| row.get(j))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let lines: Vec<String> = cells
+        .iter()
+        .map(|row| {
+            let row: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(j, width)| format!("{:>1$}", row.get(j).map_or("", |x| x.as_str()), width))
+                .collect();
+
+            format!(" {} ", row.join("  "))
+        })
+        .collect();
+
+    let body = if lines.is_empty() {
+        Block::text(" ")
+    } else {
+        Block {
+            baseline: (lines.len() - 1) / 2,
+            lines,
+        }
+    };
+
+    body.fence(('[', '⎡', '⎢', '⎣'), (']', '⎤', '⎥', '⎦'))
+}
+
+fn call(name: &str, args: &[Expr], s: &Settings) -> Block {
+    match (name, args) {
+        ("sqrt", [x]) => return root(node(x, s).0),
+        ("abs", [x]) => return node(x, s).0.fence(('|', '│', '│', '│'), ('|', '│', '│', '│')),
+        ("floor", [x]) => return node(x, s).0.fence(('⌊', '│', '│', '⌊'), ('⌋', '│', '│', '⌋')),
+        ("ceil", [x]) => return node(x, s).0.fence(('⌈', '⌈', '│', '│'), ('⌉', '⌉', '│', '│')),
+        _ => {}
+    }
+
+    Block::row(vec![Block::text(name), list(args, s).parens()])
+}
+
+/// `x`, `π` or `speed`
+fn ident(name: &str) -> String {
+    match greek(name) {
+        Some(c) => c.to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// `m*s^-2` as `m·s⁻²`
+fn unit_name(unit: &str) -> String {
+    let mut res = String::new();
+    let mut chars = unit.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => res.push('·'),
+            '^' => {
+                let mut exp = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '-') {
+                    exp.push(c);
+                    chars.next();
+                }
+                res += &superscript(&exp).unwrap_or(exp);
+            }
+            c => res.push(c),
+        }
+    }
+
+    res
+}
@@ -0,0 +1,320 @@
+use crate::{Expr, ExprError};
+
+/// Exponents of the SI base units, in the order of `BASE`
+pub type Dimension = [i32; 7];
+
+const BASE: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+/// Largest power of a base unit, in either direction
+const MAX_EXPONENT: i32 = 1000;
+
+const NONE: Dimension = [0, 0, 0, 0, 0, 0, 0];
+const LENGTH: Dimension = [1, 0, 0, 0, 0, 0, 0];
+const AREA: Dimension = [2, 0, 0, 0, 0, 0, 0];
+const VOLUME: Dimension = [3, 0, 0, 0, 0, 0, 0];
+const MASS: Dimension = [0, 1, 0, 0, 0, 0, 0];
+const TIME: Dimension = [0, 0, 1, 0, 0, 0, 0];
+const SPEED: Dimension = [1, 0, -1, 0, 0, 0, 0];
+const CURRENT: Dimension = [0, 0, 0, 1, 0, 0, 0];
+const TEMPERATURE: Dimension = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: Dimension = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: Dimension = [0, 0, 0, 0, 0, 0, 1];
+const FREQUENCY: Dimension = [0, 0, -1, 0, 0, 0, 0];
+const FORCE: Dimension = [1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dimension = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dimension = [2, 1, -3, 0, 0, 0, 0];
+const PRESSURE: Dimension = [-1, 1, -2, 0, 0, 0, 0];
+const CHARGE: Dimension = [0, 0, 1, 1, 0, 0, 0];
+const VOLTAGE: Dimension = [2, 1, -3, -1, 0, 0, 0];
+const RESISTANCE: Dimension = [2, 1, -3, -2, 0, 0, 0];
+
+/// Name as typed (names are lowercased by the parser), symbol, size in SI base units
+const UNITS: &[(&str, &str, f64, Dimension)] = &[
+    ("m", "m", 1.0, LENGTH),
+    ("km", "km", 1e3, LENGTH),
+    ("cm", "cm", 1e-2, LENGTH),
+    ("mm", "mm", 1e-3, LENGTH),
+    ("um", "um", 1e-6, LENGTH),
+    ("nm", "nm", 1e-9, LENGTH),
+    ("in", "in", 0.0254, LENGTH),
+    ("ft", "ft", 0.3048, LENGTH),
+    ("yd", "yd", 0.9144, LENGTH),
+    ("mi", "mi", 1609.344, LENGTH),
+    ("nmi", "nmi", 1852.0, LENGTH),
+    ("ha", "ha", 1e4, AREA),
+    ("acre", "acre", 4046.856_422_4, AREA),
+    ("l", "L", 1e-3, VOLUME),
+    ("ml", "mL", 1e-6, VOLUME),
+    ("gal", "gal", 3.785_411_784e-3, VOLUME),
+    ("kg", "kg", 1.0, MASS),
+    ("g", "g", 1e-3, MASS),
+    ("mg", "mg", 1e-6, MASS),
+    ("t", "t", 1e3, MASS),
+    ("lb", "lb", 0.453_592_37, MASS),
+    ("oz", "oz", 0.028_349_523_125, MASS),
+    ("s", "s", 1.0, TIME),
+    ("ms", "ms", 1e-3, TIME),
+    ("min", "min", 60.0, TIME),
+    ("h", "h", 3600.0, TIME),
+    ("day", "day", 86400.0, TIME),
+    ("week", "week", 604_800.0, TIME),
+    ("mph", "mph", 0.447_04, SPEED),
+    ("knot", "knot", 1852.0 / 3600.0, SPEED),
+    ("a", "A", 1.0, CURRENT),
+    ("ma", "mA", 1e-3, CURRENT),
+    ("k", "K", 1.0, TEMPERATURE),
+    ("mol", "mol", 1.0, AMOUNT),
+    ("cd", "cd", 1.0, LUMINOSITY),
+    ("hz", "Hz", 1.0, FREQUENCY),
+    ("khz", "kHz", 1e3, FREQUENCY),
+    ("n", "N", 1.0, FORCE),
+    ("kn", "kN", 1e3, FORCE),
+    ("lbf", "lbf", 4.448_221_615_260_5, FORCE),
+    ("j", "J", 1.0, ENERGY),
+    ("kj", "kJ", 1e3, ENERGY),
+    ("cal", "cal", 4.184, ENERGY),
+    ("kcal", "kcal", 4184.0, ENERGY),
+    ("wh", "Wh", 3600.0, ENERGY),
+    ("kwh", "kWh", 3.6e6, ENERGY),
+    ("ev", "eV", 1.602_176_634e-19, ENERGY),
+    ("w", "W", 1.0, POWER),
+    ("kw", "kW", 1e3, POWER),
+    ("hp", "hp", 745.699_871_582_270_2, POWER),
+    ("pa", "Pa", 1.0, PRESSURE),
+    ("kpa", "kPa", 1e3, PRESSURE),
+    ("bar", "bar", 1e5, PRESSURE),
+    ("atm", "atm", 101_325.0, PRESSURE),
+    ("psi", "psi", 6894.757_293_168, PRESSURE),
+    ("c", "C", 1.0, CHARGE),
+    ("v", "V", 1.0, VOLTAGE),
+    ("kv", "kV", 1e3, VOLTAGE),
+    ("ohm", "ohm", 1.0, RESISTANCE),
+];
+
+/// A number with a physical dimension, always kept in SI base units
+#[derive(Clone, Debug)]
+pub struct Quantity {
+    pub value: f64,
+    pub dim: Dimension,
+    /// Unit picked with `to` and its size in SI base units, only used for printing
+    pub unit: Option<(String, f64)>,
+}
+
+/// Looked up for names read as units that are neither a variable nor a constant
+pub fn lookup(name: &str) -> Option<Expr> {
+    UNITS
+        .iter()
+        .find(|unit| unit.0 == name)
+        .map(|&(_, _, value, dim)| Expr::Quantity(Quantity::new(value, dim)))
+}
+
+/// Right side of `to`, where every name is a unit
+pub fn expected(e: Expr) -> Expr {
+    match e {
+        Expr::Var(name) => Expr::Unit(name),
+        Expr::Mul(x, y) => Expr::Mul(box expected(*x), box expected(*y)),
+        Expr::Div(x, y) => Expr::Div(box expected(*x), box expected(*y)),
+        Expr::Pow(x, n) => Expr::Pow(box expected(*x), n),
+        e => e,
+    }
+}
+
+/// `y` of `x * y` or `x / y`, a name is a unit if `x` ends with one: `9.81 m/s^2`
+pub fn after(x: &Expr, y: Expr) -> Expr {
+    fn ends_with_unit(x: &Expr) -> bool {
+        match x {
+            Expr::Unit(_) => true,
+            Expr::Neg(x) | Expr::Mul(_, x) | Expr::Div(_, x) | Expr::Pow(x, _) => {
+                ends_with_unit(x)
+            }
+            _ => false,
+        }
+    }
+
+    match y {
+        Expr::Var(_) | Expr::Pow(box Expr::Var(_), _) if ends_with_unit(x) => expected(y),
+        y => y,
+    }
+}
+
+/// `5 km to mi`
+pub fn convert(x: Expr, unit: Expr, name: String) -> Result<Expr, ExprError> {
+    match (x, unit) {
+        (Expr::Quantity(x), Expr::Quantity(unit)) => {
+            if x.dim != unit.dim {
+                return Err(mismatch(&x.dim, &unit.dim));
+            }
+
+            Ok(Expr::Quantity(Quantity {
+                unit: Some((name, unit.value)),
+                ..x
+            }))
+        }
+        (_, Expr::Quantity(unit)) => Err(mismatch(&NONE, &unit.dim)),
+        _ => Err(ExprError::CalcError {
+            err: "conversion works only to units".into(),
+        }),
+    }
+}
+
+/// Pretty name for the right side of `to`, from the expression as typed
+pub fn unit_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Unit(ref name) => match UNITS.iter().find(|unit| unit.0 == name) {
+            Some(unit) => unit.1.to_string(),
+            None => name.clone(),
+        },
+        Expr::Mul(ref x, ref y) => format!("{}*{}", unit_name(x), unit_name(y)),
+        Expr::Div(ref x, ref y @ box Expr::Mul(..)) => {
+            format!("{}/({})", unit_name(x), unit_name(y))
+        }
+        Expr::Div(ref x, ref y) => format!("{}/{}", unit_name(x), unit_name(y)),
+        Expr::Pow(ref x, ref y) => format!("{}^{}", unit_name(x), y),
+        expr => expr.to_string(),
+    }
+}
+
+/// `kg*m^2/s^2` style name of a dimension
+pub fn base_units(dim: &Dimension) -> String {
+    let part = |name: &str, exp: i32| {
+        if exp == 1 {
+            name.to_string()
+        } else {
+            format!("{}^{}", name, exp)
+        }
+    };
+
+    let top: Vec<String> = BASE
+        .iter()
+        .zip(dim)
+        .filter(|&(_, &exp)| exp > 0)
+        .map(|(name, &exp)| part(name, exp))
+        .collect();
+
+    // `s^-1` reads better than `1/s`
+    if top.is_empty() {
+        let parts: Vec<String> = BASE
+            .iter()
+            .zip(dim)
+            .filter(|&(_, &exp)| exp != 0)
+            .map(|(name, &exp)| part(name, exp))
+            .collect();
+
+        return if parts.is_empty() {
+            "1".to_string()
+        } else {
+            parts.join("*")
+        };
+    }
+
+    let bottom: Vec<String> = BASE
+        .iter()
+        .zip(dim)
+        .filter(|&(_, &exp)| exp < 0)
+        .map(|(name, &exp)| part(name, -exp))
+        .collect();
+
+    match bottom.len() {
+        0 => top.join("*"),
+        1 => format!("{}/{}", top.join("*"), bottom[0]),
+        _ => format!("{}/({})", top.join("*"), bottom.join("*")),
+    }
+}
+
+pub fn mismatch(left: &Dimension, right: &Dimension) -> ExprError {
+    ExprError::UnitMismatch {
+        left: base_units(left),
+        right: base_units(right),
+    }
+}
+
+impl Quantity {
+    pub fn new(value: f64, dim: Dimension) -> Quantity {
+        Quantity {
+            value,
+            dim,
+            unit: None,
+        }
+    }
+
+    /// Dimensionless results are plain numbers again
+    fn into_expr(self) -> Expr {
+        if self.dim == NONE {
+            Expr::Real(self.value)
+        } else {
+            Expr::Quantity(self)
+        }
+    }
+
+    pub fn add(self, other: Quantity) -> Result<Expr, ExprError> {
+        if self.dim != other.dim {
+            return Err(mismatch(&self.dim, &other.dim));
+        }
+
+        Ok(Quantity::new(self.value + other.value, self.dim).into_expr())
+    }
+
+    pub fn rem(self, other: Quantity) -> Result<Expr, ExprError> {
+        if self.dim != other.dim {
+            return Err(mismatch(&self.dim, &other.dim));
+        }
+
+        Ok(Quantity::new(self.value % other.value, self.dim).into_expr())
+    }
+
+    pub fn mul(self, other: Quantity) -> Result<Expr, ExprError> {
+        let mut dim = self.dim;
+        for (d, o) in dim.iter_mut().zip(&other.dim) {
+            *d = match d.checked_add(*o) {
+                Some(sum) if sum.abs() <= MAX_EXPONENT => sum,
+                _ => {
+                    return Err(ExprError::CalcError {
+                        err: format!(
+                            "can't multiply {} by {}",
+                            base_units(&self.dim),
+                            base_units(&other.dim)
+                        ),
+                    })
+                }
+            };
+        }
+
+        Ok(Quantity::new(self.value * other.value, dim).into_expr())
+    }
+
+    pub fn div(self, other: Quantity) -> Result<Expr, ExprError> {
+        self.mul(other.recip())
+    }
+
+    pub fn scale(self, k: f64) -> Expr {
+        Quantity::new(self.value * k, self.dim).into_expr()
+    }
+
+    pub fn recip(self) -> Quantity {
+        let mut dim = self.dim;
+        for d in dim.iter_mut() {
+            *d = -*d;
+        }
+
+        Quantity::new(1.0 / self.value, dim)
+    }
+
+    /// Only when every exponent stays whole: `(4 m^2)^0.5` is fine, `(4 m)^0.5` is not
+    pub fn pow(self, y: f64) -> Result<Expr, ExprError> {
+        let mut dim = self.dim;
+
+        for d in dim.iter_mut() {
+            let exp = *d as f64 * y;
+
+            if exp.fract() != 0.0 || exp.abs() > f64::from(MAX_EXPONENT) {
+                return Err(ExprError::CalcError {
+                    err: format!("can't raise {} to the power of {}", base_units(&self.dim), y),
+                });
+            }
+
+            *d = exp as i32;
+        }
+
+        Ok(Quantity::new(self.value.powf(y), dim).into_expr())
+    }
+}
@@ -0,0 +1,94 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+/// `antideriv` of `body` printed, and checked against the numeric integral on [a, b]
+fn antideriv(calc: &mut Interpreter, body: &str, a: f64, b: f64) -> String {
+    calc.eval(&format!("f(x) = {}", body)).unwrap();
+    let res = eval(calc, "big = antideriv(f)");
+
+    let exact: f64 = calc.eval_as(&format!("big({}) - big({})", b, a)).unwrap();
+    let numeric: f64 = calc.eval_as(&format!("integrate(f, {}, {})[0]", a, b)).unwrap();
+    assert!((exact - numeric).abs() < 1e-9, "{}: {} vs {}", body, exact, numeric);
+
+    // And the printed text reads back as the same function
+    let text = res.split_once(" => ").unwrap().1;
+    calc.eval(&format!("back(x) = {}", text)).unwrap();
+    let back: f64 = calc.eval_as(&format!("back({}) - back({})", b, a)).unwrap();
+    assert!((back - exact).abs() < 1e-9, "{}: {} vs {}", text, back, exact);
+
+    res
+}
+
+#[test]
+fn polynomials() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(antideriv(&mut calc, "5", 0.0, 1.0), "(x) => (5 * x)");
+    assert_eq!(antideriv(&mut calc, "x", 0.0, 1.0), "(x) => ((1/2) * (x ^ 2))");
+    assert_eq!(
+        antideriv(&mut calc, "3 * x^2 + 2 * x + 1", 0.0, 2.0),
+        "(x) => (((x ^ 3) + (x ^ 2)) + x)"
+    );
+    assert_eq!(antideriv(&mut calc, "x^3 / 4", -1.0, 2.0), "(x) => ((1/16) * (x ^ 4))");
+}
+
+#[test]
+fn functions_and_powers() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(antideriv(&mut calc, "exp(x)", 0.0, 1.0), "(x) => exp(x)");
+    assert_eq!(antideriv(&mut calc, "sin(x)", 0.0, 3.0), "(x) => -cos(x)");
+    assert_eq!(antideriv(&mut calc, "cos(x)", 0.0, 3.0), "(x) => sin(x)");
+    assert_eq!(antideriv(&mut calc, "sqrt(x)", 0.0, 4.0), "(x) => ((2/3) * (x ^ (3/2)))");
+    assert_eq!(antideriv(&mut calc, "1 / x", 1.0, 3.0), "(x) => ln(abs(x))");
+    // ln|x| holds on the negative side too
+    antideriv(&mut calc, "1 / x", -3.0, -1.0);
+    assert_eq!(antideriv(&mut calc, "x^-1", 1.0, 3.0), "(x) => ln(abs(x))");
+    antideriv(&mut calc, "x^-2", 1.0, 3.0);
+    antideriv(&mut calc, "2^x", 0.0, 2.0);
+}
+
+#[test]
+fn linearity_and_substitution() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(
+        antideriv(&mut calc, "3 * x^2 + cos(2 * x) + 1 / x", 1.0, 2.0),
+        "(x) => (((x ^ 3) + ((1/2) * sin((2 * x)))) + ln(abs(x)))"
+    );
+    assert_eq!(
+        antideriv(&mut calc, "2 * exp(x) - sin(x)", 0.0, 1.0),
+        "(x) => ((2 * exp(x)) + cos(x))"
+    );
+    antideriv(&mut calc, "exp(3 * x + 1)", 0.0, 1.0);
+    antideriv(&mut calc, "sin(1 - x) / 2", 0.0, 2.0);
+    antideriv(&mut calc, "1 / (2 * x + 1)", 0.0, 2.0);
+    antideriv(&mut calc, "(x / 2 + 1)^3", 0.0, 2.0);
+
+    // Other names of the variable, given explicitly for several arguments
+    calc.eval("p(t, k) = k * t^2").unwrap();
+    assert_eq!(eval(&mut calc, "antideriv(p, t)"), "(t, k) => (k * ((1/3) * (t ^ 3)))");
+}
+
+#[test]
+fn not_integrable() {
+    let mut calc = Interpreter::new();
+
+    for body in &["sin(x) * cos(x)", "exp(x^2)", "1 / (x^2 + 1)", "sin(x)^2"] {
+        calc.eval(&format!("f(x) = {}", body)).unwrap();
+
+        match calc.eval("antideriv(f)") {
+            Err(ExprError::NoAntiderivative { .. }) => {}
+            res => panic!("{}: {:?}", body, res),
+        }
+    }
+
+    calc.eval("p(t, k) = k * t").unwrap();
+    assert!(calc.eval("antideriv(p)").is_err());
+    assert!(calc.eval("antideriv(5)").is_err());
+}
@@ -0,0 +1,65 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+#[test]
+fn known_constants() {
+    let mut calc = Interpreter::new();
+    calc.set("precision", "50").unwrap();
+
+    assert_eq!(eval(&mut calc, "pi"), "3.1415926535897932384626433832795028841971693993751");
+    assert_eq!(eval(&mut calc, "sqrt(2)"), "1.4142135623730950488016887242096980785696718753769");
+    assert_eq!(eval(&mut calc, "exp(1)"), "2.7182818284590452353602874713526624977572470937");
+    assert_eq!(eval(&mut calc, "e"), "2.7182818284590452353602874713526624977572470937");
+    assert_eq!(eval(&mut calc, "ln(2)"), "0.69314718055994530941723212145817656807550013436026");
+    assert_eq!(eval(&mut calc, "sin(pi / 6)"), "0.5");
+    assert_eq!(eval(&mut calc, "4 * atan(1) - pi"), "0");
+
+    calc.set("precision", "10").unwrap();
+    assert_eq!(eval(&mut calc, "pi"), "3.141592654");
+    assert_eq!(eval(&mut calc, "1 / 3"), "1/3");
+    assert_eq!(eval(&mut calc, "sqrt(1 / 3)"), "0.5773502692");
+    assert_eq!(eval(&mut calc, "sqrt(16)"), "4");
+}
+
+#[test]
+fn precision_setting() {
+    let mut calc = Interpreter::new();
+
+    calc.set("precision", "1").unwrap();
+    assert_eq!(eval(&mut calc, "pi"), "3");
+    calc.set("precision", "10000").unwrap();
+    let pi = eval(&mut calc, "pi");
+    assert_eq!(pi.len(), 10001);
+    assert!(pi.starts_with("3.14159265358979323846"));
+
+    for value in &["0", "10001", "-1", "x", ""] {
+        match calc.set("precision", value) {
+            Err(ExprError::BadSetting { .. }) => {}
+            res => panic!("{}: {:?}", value, res),
+        }
+    }
+    // Rejected values leave the old one
+    assert_eq!(calc.context().settings.precision, Some(10000));
+
+    calc.set("precision", "off").unwrap();
+    assert_eq!(eval(&mut calc, "pi"), "3.141592653589793");
+}
+
+#[test]
+fn huge_arguments() {
+    let mut calc = Interpreter::new();
+    calc.set("precision", "30").unwrap();
+
+    assert_eq!(eval(&mut calc, "sin(10^100)"), "-0.372376123661276688262086695553");
+    for line in &["sin(10^20000)", "cos(10^20000)", "tan(10^20000)"] {
+        match calc.eval(line) {
+            Err(ExprError::CalcError { .. }) => {}
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+}
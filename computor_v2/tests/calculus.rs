@@ -0,0 +1,123 @@
+extern crate computor_v2;
+
+use computor_v2::{Expr, ExprError, Interpreter};
+use std::f64::consts::{E, PI};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+/// Value and error estimate of `integrate`, `nderiv` or `limit`
+fn estimate(calc: &mut Interpreter, line: &str) -> (f64, f64) {
+    match calc.eval(line) {
+        Ok(Expr::Tuple(ref items)) => match items.as_slice() {
+            [Expr::Real(value), Expr::Real(error)] => (*value, *error),
+            _ => panic!("{}: {:?}", line, items),
+        },
+        res => panic!("{}: {:?}", line, res),
+    }
+}
+
+/// The estimate bounds the real error, up to the rounding of `exact` itself
+fn assert_bounded(calc: &mut Interpreter, line: &str, exact: f64) {
+    let (value, error) = estimate(calc, line);
+
+    assert!(error >= 0.0, "{}: {}", line, error);
+    assert!(
+        (value - exact).abs() <= error + 4.0 * std::f64::EPSILON * exact.abs(),
+        "{}: {} +- {}, not {}",
+        line,
+        value,
+        error,
+        exact
+    );
+}
+
+fn no_convergence(calc: &mut Interpreter, line: &str, func: &str) {
+    match calc.eval(line) {
+        Err(ExprError::NoConvergence { func: f }) => assert_eq!(f, func),
+        res => panic!("{}: {:?}", line, res),
+    }
+}
+
+fn session() -> Interpreter {
+    let mut calc = Interpreter::new();
+
+    for line in &[
+        "sq(x) = x^2",
+        "s(x) = sin(x)",
+        "ex(x) = exp(x)",
+        "g(x) = 1 / exp(x^2)",
+        "r(x) = 1 / x",
+        "rs(x) = 1 / sqrt(x)",
+        "sinc(x) = sin(x) / x",
+        "q(n) = 1 / n",
+        "id(n) = n",
+        "half(n) = n / 2",
+    ] {
+        calc.eval(line).unwrap();
+    }
+
+    calc
+}
+
+#[test]
+fn integrals() {
+    let mut calc = session();
+
+    assert_bounded(&mut calc, "integrate(sq, 0, 3)", 9.0);
+    assert_bounded(&mut calc, "integrate(s, 0, pi)", 2.0);
+    assert_bounded(&mut calc, "integrate(ex, 0, 1)", E - 1.0);
+    assert_bounded(&mut calc, "integrate(r, 1, e)", 1.0);
+    assert_bounded(&mut calc, "integrate(g, -10, 10)", PI.sqrt());
+    assert_bounded(&mut calc, "integrate(rs, 0, 1)", 2.0);
+    assert_bounded(&mut calc, "integrate(sq, 3, 0)", -9.0);
+    assert_eq!(estimate(&mut calc, "integrate(sq, 1, 1)"), (0.0, 0.0));
+
+    no_convergence(&mut calc, "integrate(r, 0, 1)", "integrate");
+}
+
+#[test]
+fn derivatives() {
+    let mut calc = session();
+
+    assert_bounded(&mut calc, "nderiv(sq, 3)", 6.0);
+    assert_bounded(&mut calc, "nderiv(s, 0)", 1.0);
+    assert_bounded(&mut calc, "nderiv(s, pi / 3)", 0.5);
+    assert_bounded(&mut calc, "nderiv(ex, 1)", E);
+    assert_bounded(&mut calc, "nderiv(r, 2)", -0.25);
+}
+
+#[test]
+fn limits() {
+    let mut calc = session();
+
+    assert_bounded(&mut calc, "limit(sinc, 0)", 1.0);
+    assert_bounded(&mut calc, "limit(sq, 2)", 4.0);
+    // Rounding inside the function itself is beyond the estimate, no cancellation here
+    calc.eval("c(x) = 2 * sin(x / 2)^2 / x^2").unwrap();
+    assert_bounded(&mut calc, "limit(c, 0)", 0.5);
+    calc.eval("d(x) = (2 + x) / (1 - x)").unwrap();
+    assert_bounded(&mut calc, "limit(d, 1 / 2)", 5.0);
+
+    no_convergence(&mut calc, "limit(r, 0)", "limit");
+    calc.eval("osc(x) = sin(1 / x)").unwrap();
+    no_convergence(&mut calc, "limit(osc, 0)", "limit");
+}
+
+#[test]
+fn sums_and_products() {
+    let mut calc = session();
+
+    assert_eq!(eval(&mut calc, "sum(q, 1, 10)"), "7381/2520");
+    assert_eq!(eval(&mut calc, "sum(id, 1, 100)"), "5050");
+    assert_eq!(eval(&mut calc, "sum(sq, -2, 2)"), "10");
+    assert_eq!(eval(&mut calc, "prod(id, 1, 20)"), "2432902008176640000");
+    assert_eq!(eval(&mut calc, "prod(half, 1, 4)"), "3/2");
+    assert_eq!(eval(&mut calc, "sum(id, 5, 4)"), "0");
+    assert_eq!(eval(&mut calc, "prod(id, 5, 4)"), "1");
+
+    assert!(calc.eval("sum(id, 1, 2.5)").is_err());
+    assert!(calc.eval("sum(id, 1)").is_err());
+    assert!(calc.eval("prod(5, 1, 2)").is_err());
+}
@@ -0,0 +1,78 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter, Limits};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+/// Functions checked the same compiled or left to the tree walker
+fn functions(walk: bool) -> Interpreter {
+    let mut calc = Interpreter::new();
+    if walk {
+        calc.walk_functions();
+    }
+
+    calc.eval("v = [10, 20, 30, 40]").unwrap();
+    calc.eval("at(k) = v[k] + v[k:][0] + v[:k + 1][k]").unwrap();
+    calc.eval("m = [[1, 2]; [3, 4]]").unwrap();
+    calc.eval("pick(k) = (m ** m)[k, :] * k").unwrap();
+    calc.eval("sq(x) = x^2").unwrap();
+    calc.eval("twice(f, x) = f(f(x))").unwrap();
+    calc.eval("tri(n) = sum(sq, 1, n) / n").unwrap();
+    assert_eq!(eval(&mut calc, "sum(at, 0, 3)"), "300");
+    assert_eq!(eval(&mut calc, "pick(1)"), "[[15, 22]]");
+    assert_eq!(eval(&mut calc, "twice(sq, 3)"), "81");
+    assert_eq!(eval(&mut calc, "sum(tri, 1, 3)"), "49/6");
+    let integral = calc.eval_as::<f64>("integrate(sq, 0, 3)[0]").unwrap();
+    assert!((integral - 9.0).abs() < 1e-12);
+
+    // Calls see the arguments of their callers
+    calc.eval("g(x) = x + n").unwrap();
+    calc.eval("f(n) = g(1) + sum(g, 1, 2)").unwrap();
+    calc.eval("n = 100").unwrap();
+    assert_eq!(eval(&mut calc, "g(1)"), "101");
+    assert_eq!(eval(&mut calc, "f(5)"), "19");
+
+    calc
+}
+
+#[test]
+fn same_as_tree_walking() {
+    functions(true);
+    let mut calc = functions(false);
+
+    calc.eval("loop(x) = loop(x) + 1").unwrap();
+    match calc.eval("loop(1)") {
+        Err(ExprError::RecursiveRecursion) => {}
+        res => panic!("{:?}", res),
+    }
+    calc.eval("bad(x) = sq(x, x)").unwrap();
+    match calc.eval("bad(1)") {
+        Err(ExprError::BadArgsCount { func }) => assert_eq!(func, "sq"),
+        res => panic!("{:?}", res),
+    }
+    calc.eval("later(x) = x + undefinedyet").unwrap();
+    match calc.eval("later(1)") {
+        Err(ExprError::UndefinedVariable { name }) => assert_eq!(name, "undefinedyet"),
+        res => panic!("{:?}", res),
+    }
+    calc.eval("undefinedyet = 1").unwrap();
+    assert_eq!(eval(&mut calc, "later(1)"), "2");
+}
+
+#[test]
+fn steps_are_counted() {
+    let mut calc = Interpreter::new();
+    calc.set_limits(Limits {
+        max_steps: Some(10_000),
+        ..Limits::default()
+    });
+
+    calc.eval("f(x) = x * 2 + 1").unwrap();
+    calc.eval("sum(f, 1, 1000)").unwrap();
+    match calc.eval("sum(f, 1, 2000)") {
+        Err(ExprError::StepLimit { limit: 10_000 }) => {}
+        res => panic!("{:?}", res),
+    }
+}
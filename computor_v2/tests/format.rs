@@ -0,0 +1,27 @@
+extern crate computor_v2;
+
+use computor_v2::Interpreter;
+
+fn show(calc: &mut Interpreter, line: &str) -> String {
+    let x = calc.eval(line).unwrap();
+
+    x.formatted(&calc.context().settings).to_string()
+}
+
+#[test]
+fn rationals_and_bigfloats_follow_settings() {
+    let mut calc = Interpreter::new();
+
+    calc.set("separator", ",").unwrap();
+    assert_eq!(show(&mut calc, "1000000 / 3"), "1,000,000/3");
+    assert_eq!(show(&mut calc, "1 / 1234567"), "1/1,234,567");
+
+    calc.set("precision", "30").unwrap();
+    assert_eq!(show(&mut calc, "sqrt(2) * 1000"), "1,414.21356237309504880168872421");
+    calc.set("digits", "5").unwrap();
+    assert_eq!(show(&mut calc, "sqrt(2) * 1000"), "1,414.2");
+    calc.set("notation", "sci").unwrap();
+    assert_eq!(show(&mut calc, "sqrt(2) * 1000"), "1.4142e3");
+    calc.set("notation", "eng").unwrap();
+    assert_eq!(show(&mut calc, "sqrt(2) / 100"), "14.142e-3");
+}
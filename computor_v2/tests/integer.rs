@@ -0,0 +1,77 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter, Limits};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+#[test]
+fn integer_functions() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(eval(&mut calc, "gcd(12, 18)"), "6");
+    assert_eq!(eval(&mut calc, "gcd(-12, 18)"), "6");
+    assert_eq!(eval(&mut calc, "gcd(0, 5)"), "5");
+    assert_eq!(eval(&mut calc, "lcm(4, 6)"), "12");
+    assert_eq!(eval(&mut calc, "lcm(0, 6)"), "0");
+    assert_eq!(eval(&mut calc, "lcm(2^64, 3)"), "55340232221128654848");
+
+    assert_eq!(eval(&mut calc, "choose(52, 5)"), "2598960");
+    assert_eq!(eval(&mut calc, "choose(5, 0)"), "1");
+    assert_eq!(eval(&mut calc, "choose(5, 6)"), "0");
+    assert_eq!(eval(&mut calc, "choose(100, 50)"), "100891344545564193334812497256");
+
+    for n in &["2", "3", "97", "7919", "2^61 - 1", "2^89 - 1"] {
+        assert_eq!(eval(&mut calc, &format!("isprime({})", n)), "1", "{}", n);
+    }
+    for n in &["-7", "0", "1", "4", "561", "3215031751", "2^64 + 1"] {
+        assert_eq!(eval(&mut calc, &format!("isprime({})", n)), "0", "{}", n);
+    }
+
+    for line in &["gcd(1.5, 2)", "choose(5)", "isprime(1/2)"] {
+        assert!(calc.eval(line).is_err(), "{}", line);
+    }
+}
+
+#[test]
+fn factorial() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(eval(&mut calc, "0!"), "1");
+    assert_eq!(eval(&mut calc, "5!"), "120");
+    assert_eq!(eval(&mut calc, "25!"), "15511210043330985984000000");
+    assert_eq!(eval(&mut calc, "3!!"), "720");
+    assert_eq!(eval(&mut calc, "2 * 3!"), "12");
+    assert_eq!(eval(&mut calc, "-3!"), "-6");
+    assert_eq!(eval(&mut calc, "1000! / 999!"), "1000");
+
+    for line in &["(-1)!", "(1/2)!", "1.5!"] {
+        match calc.eval(line) {
+            Err(ExprError::CalcError { .. }) => {}
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+}
+
+#[test]
+fn sizes_are_capped_by_default() {
+    let mut calc = Interpreter::new();
+    let limit = Limits::default().max_digits.unwrap();
+
+    calc.eval("20000!").unwrap();
+    calc.eval("10^99999").unwrap();
+    for line in &["99999999!", "10^(10^9)", "(2/3)^(10^9)"] {
+        match calc.eval(line) {
+            Err(ExprError::NumberTooBig { limit: l }) => assert_eq!(l, limit),
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+
+    // Lifted when asked to
+    calc.set_limits(Limits {
+        max_digits: None,
+        ..Limits::default()
+    });
+    calc.eval("30000!").unwrap();
+}
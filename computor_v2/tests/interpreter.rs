@@ -0,0 +1,61 @@
+extern crate computor_v2;
+
+use computor_v2::{Expr, ExprError, Interpreter};
+use std::convert::TryFrom;
+
+#[test]
+fn variables_go_both_ways() {
+    let mut calc = Interpreter::new();
+
+    calc.set_var("Rate", 0.25).unwrap();
+    calc.set_var("m", vec![vec![1.0, 2.0], vec![3.0, 4.0]]).unwrap();
+    calc.eval("total = rate * 8").unwrap();
+
+    assert_eq!(calc.get_var::<f64>("total").unwrap(), 2.0);
+    assert_eq!(
+        calc.eval_as::<Vec<Vec<f64>>>("m ** m").unwrap(),
+        [[7.0, 10.0], [15.0, 22.0]]
+    );
+    assert_eq!(calc.eval_as::<i64>("ans[1, 0] + 1").unwrap(), 16);
+
+    match calc.get_var::<f64>("nope") {
+        Err(ExprError::UndefinedVariable { name }) => assert_eq!(name, "nope"),
+        res => panic!("{:?}", res),
+    }
+    match calc.eval_as::<Vec<f64>>("1 / 3") {
+        Err(ExprError::TypeMismatch { expected, found }) => {
+            assert_eq!((expected.as_str(), found.as_str()), ("vector", "rational"))
+        }
+        res => panic!("{:?}", res),
+    }
+    assert!(calc.set_var("2x", 1).is_err());
+
+    for rows in &[vec![vec![1.0, 2.0], vec![3.0]], vec![], vec![vec![]]] {
+        match calc.set_var("m", rows.clone()) {
+            Err(ExprError::InvalidMatrix) => {}
+            res => panic!("{:?}", res),
+        }
+    }
+}
+
+#[test]
+fn functions_defined_from_rust() {
+    let mut calc = Interpreter::new();
+
+    calc.define_fn("hyp", &["a", "b"], "sqrt(a^2 + b^2)").unwrap();
+    calc.register_native_fn("clamp", 1, |args| {
+        let x = f64::try_from(args[0].clone())?;
+
+        Ok(Expr::from(x.clamp(0.0, 1.0)))
+    })
+    .unwrap();
+
+    assert_eq!(calc.eval_as::<f64>("hyp(3, 4)").unwrap(), 5.0);
+    assert_eq!(calc.eval_as::<f64>("clamp(hyp(3, 4)) + clamp(-2)").unwrap(), 1.0);
+    assert!(calc.context().names().any(|name| name == "clamp"));
+
+    match calc.eval("clamp(1, 2)") {
+        Err(ExprError::BadArgsCount { func }) => assert_eq!(func, "clamp"),
+        res => panic!("{:?}", res),
+    }
+}
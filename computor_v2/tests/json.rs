@@ -0,0 +1,53 @@
+extern crate computor_v2;
+#[macro_use]
+extern crate serde_json;
+
+use computor_v2::json::answer;
+use computor_v2::Interpreter;
+
+#[test]
+fn values_with_their_types() {
+    let mut calc = Interpreter::new();
+
+    answer(r#"{"eval": "f(x, y) = x * y"}"#, &mut calc);
+    let res = answer(r#"{"eval": "f(1, 5)", "id": 7}"#, &mut calc);
+    assert_eq!(res["id"], json!(7));
+    assert_eq!(res["result"], json!({ "type": "integer", "value": 5 }));
+    assert_eq!(res["ast"]["type"], json!("call"));
+
+    let res = answer(r#"{"eval": "[[1, 2]; [3, 4]]"}"#, &mut calc);
+    assert_eq!(res["result"]["value"], json!([[1.0, 2.0], [3.0, 4.0]]));
+
+    let res = answer(r#"{"eval": "2 - 3i"}"#, &mut calc);
+    assert_eq!(res["result"], json!({ "type": "complex", "re": 2.0, "im": -3.0 }));
+}
+
+#[test]
+fn syntax_tree() {
+    let res = answer(r#"{"eval": "-x + 2"}"#, &mut Interpreter::new());
+
+    assert_eq!(
+        res["ast"],
+        json!({
+            "type": "add",
+            "left": { "type": "neg", "arg": { "type": "var", "name": "x" } },
+            "right": { "type": "integer", "value": 2 },
+        })
+    );
+}
+
+#[test]
+fn typed_errors() {
+    let mut calc = Interpreter::new();
+
+    let res = answer(r#"{"eval": "1 / 0"}"#, &mut calc);
+    assert_eq!(res["error"]["type"], json!("division_by_zero"));
+    assert_eq!(res["error"]["message"], json!("division by zero"));
+
+    let res = answer(r#"{"eval": "nope"}"#, &mut calc);
+    assert_eq!(res["error"]["type"], json!("undefined_variable"));
+    assert_eq!(res["error"]["name"], json!("nope"));
+
+    assert_eq!(answer("1 + 1", &mut calc)["error"]["type"], json!("bad_request"));
+    assert_eq!(answer(r#"{"eval": "1 +"}"#, &mut calc)["error"]["type"], json!("parse_error"));
+}
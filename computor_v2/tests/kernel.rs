@@ -0,0 +1,224 @@
+extern crate computor_v2;
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "jupyter")]
+extern crate zmq;
+
+use computor_v2::kernel::{decode, encode, hmac, Channel, Kernel, Message};
+use serde_json::Value;
+
+const KEY: &[u8] = b"f0e1d2c3-secret";
+
+/// Stand-in for the notebook: sends one signed request through the wire
+/// format and reads back what the kernel sends on each socket
+fn send(kernel: &mut Kernel, msg_type: &str, content: Value) -> Vec<(Channel, Message)> {
+    let request = Message {
+        identities: vec![b"client".to_vec()],
+        header: json!({ "msg_id": "1", "session": "test", "msg_type": msg_type }),
+        parent_header: json!({}),
+        metadata: json!({}),
+        content,
+    };
+    let request = decode(encode(&request, KEY), KEY).unwrap();
+
+    kernel
+        .handle(Channel::Shell, &request)
+        .into_iter()
+        .map(|(channel, reply)| (channel, decode(encode(&reply, KEY), KEY).unwrap()))
+        .collect()
+}
+
+fn find<'a>(replies: &'a [(Channel, Message)], msg_type: &str) -> Option<&'a Message> {
+    replies
+        .iter()
+        .map(|(_, msg)| msg)
+        .find(|msg| msg.msg_type() == msg_type)
+}
+
+#[test]
+fn signatures_are_checked() {
+    let msg = Message {
+        identities: vec![],
+        header: json!({ "msg_type": "kernel_info_request" }),
+        parent_header: json!({}),
+        metadata: json!({}),
+        content: json!({}),
+    };
+
+    let mut frames = encode(&msg, KEY);
+    assert_eq!(frames[0], b"<IDS|MSG>");
+    assert_eq!(frames[1].len(), 64);
+    assert!(decode(frames.clone(), b"other key").is_none());
+
+    frames[5] = b"{\"code\": \"rm -rf /\"}".to_vec();
+    assert!(decode(frames, KEY).is_none());
+
+    // Jupyter runs without signatures when the key is empty
+    let frames = encode(&msg, b"");
+    assert!(frames[1].is_empty());
+    assert!(decode(frames, b"").is_some());
+}
+
+#[test]
+fn hmac_sha256_matches_rfc_4231() {
+    let mac = hmac::hmac_sha256(b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+    assert_eq!(
+        hmac::hex(&mac),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+
+    // Keys longer than a block are hashed first
+    let key = [0xaa; 131];
+    let mac = hmac::hmac_sha256(&key, &[b"Test Using Larger Than Block-Size Key - Hash Key First"]);
+    assert_eq!(
+        hmac::hex(&mac),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+
+    assert!(hmac::same(&mac, &mac));
+    assert!(!hmac::same(&mac, &mac[1..]));
+    assert!(!hmac::same(b"abc", b"abd"));
+}
+
+#[test]
+fn cells_share_variables() {
+    let mut kernel = Kernel::new();
+
+    send(
+        &mut kernel,
+        "execute_request",
+        json!({ "code": "x = 3\nf(y) = y * x" }),
+    );
+    let replies = send(
+        &mut kernel,
+        "execute_request",
+        json!({ "code": "f(2) / 4" }),
+    );
+
+    let statuses: Vec<&Value> = replies
+        .iter()
+        .filter(|(_, msg)| msg.msg_type() == "status")
+        .map(|(_, msg)| &msg.content["execution_state"])
+        .collect();
+    assert_eq!(statuses, ["busy", "idle"]);
+
+    let result = find(&replies, "execute_result").unwrap();
+    assert_eq!(result.content["execution_count"], 2);
+    assert_eq!(result.content["data"]["text/plain"], "3/2");
+    assert_eq!(result.content["data"]["text/latex"], "$\\frac{3}{2}$");
+    assert_eq!(result.parent_header["msg_type"], "execute_request");
+
+    let (channel, reply) = replies
+        .iter()
+        .find(|(_, msg)| msg.msg_type() == "execute_reply")
+        .unwrap();
+    assert_eq!(*channel, Channel::Shell);
+    assert_eq!(reply.identities, [b"client".to_vec()]);
+    assert_eq!(reply.content["status"], "ok");
+}
+
+#[test]
+fn errors_are_reported() {
+    let mut kernel = Kernel::new();
+    let replies = send(&mut kernel, "execute_request", json!({ "code": "1 / 0" }));
+
+    let error = find(&replies, "error").unwrap();
+    assert_eq!(error.content["ename"], "division_by_zero");
+    assert_eq!(error.content["evalue"], "division by zero");
+    assert!(find(&replies, "execute_result").is_none());
+
+    let reply = find(&replies, "execute_reply").unwrap();
+    assert_eq!(reply.content["status"], "error");
+
+    let content = json!({ "code": "2 * vari", "cursor_pos": 8 });
+    send(
+        &mut kernel,
+        "execute_request",
+        json!({ "code": "variable = 1" }),
+    );
+    let replies = send(&mut kernel, "complete_request", content);
+    let reply = find(&replies, "complete_reply").unwrap();
+    assert_eq!(reply.content["matches"], json!(["variable"]));
+    assert_eq!(reply.content["cursor_start"], 4);
+
+    // Cursors count chars, not bytes
+    let content = json!({ "code": "π·vari + 1", "cursor_pos": 4 });
+    let replies = send(&mut kernel, "complete_request", content);
+    let reply = find(&replies, "complete_reply").unwrap();
+    assert_eq!(reply.content["matches"], json!(["variable"]));
+    assert_eq!(reply.content["cursor_start"], 2);
+    assert_eq!(reply.content["cursor_end"], 6);
+
+    let replies = send(&mut kernel, "shutdown_request", json!({ "restart": false }));
+    assert!(find(&replies, "shutdown_reply").is_some());
+    assert!(kernel.shutdown);
+}
+
+#[cfg(feature = "jupyter")]
+#[test]
+fn serves_over_sockets() {
+    use std::net::TcpListener;
+    use std::{env, fs, thread};
+
+    let port = || {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    };
+    let (shell_port, iopub_port, hb_port) = (port(), port(), port());
+    let info = json!({
+        "transport": "tcp",
+        "ip": "127.0.0.1",
+        "key": "f0e1d2c3-secret",
+        "signature_scheme": "hmac-sha256",
+        "shell_port": shell_port,
+        "iopub_port": iopub_port,
+        "control_port": port(),
+        "stdin_port": port(),
+        "hb_port": hb_port,
+    });
+    let path = env::temp_dir().join(format!("computor_v2-kernel-{}.json", shell_port));
+    fs::write(&path, info.to_string()).unwrap();
+
+    let file = path.to_str().unwrap().to_string();
+    let kernel = thread::spawn(move || computor_v2::kernel::run(&file).unwrap());
+
+    let zmq = zmq::Context::new();
+    let connect = |kind, port| {
+        let socket = zmq.socket(kind).unwrap();
+        socket
+            .connect(&format!("tcp://127.0.0.1:{}", port))
+            .unwrap();
+        socket
+    };
+    let shell = connect(zmq::DEALER, shell_port);
+    let heartbeat = connect(zmq::REQ, hb_port);
+
+    heartbeat.send("ping", 0).unwrap();
+    assert_eq!(heartbeat.recv_bytes(0).unwrap(), b"ping");
+
+    let request = |msg_type: &str, content: Value| {
+        let msg = Message {
+            identities: vec![],
+            header: json!({ "msg_id": msg_type, "session": "test", "msg_type": msg_type }),
+            parent_header: json!({}),
+            metadata: json!({}),
+            content,
+        };
+        shell.send_multipart(encode(&msg, KEY), 0).unwrap();
+
+        decode(shell.recv_multipart(0).unwrap(), KEY).unwrap()
+    };
+
+    let reply = request("execute_request", json!({ "code": "2 + 2" }));
+    assert_eq!(reply.msg_type(), "execute_reply");
+    assert_eq!(reply.content["status"], "ok");
+
+    let reply = request("shutdown_request", json!({ "restart": false }));
+    assert_eq!(reply.msg_type(), "shutdown_reply");
+
+    kernel.join().unwrap();
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,54 @@
+extern crate computor_v2;
+
+use computor_v2::format::Settings;
+use computor_v2::latex::latex;
+use computor_v2::Context;
+
+fn tex(line: &str) -> String {
+    latex(&computor_v2::parse(line).unwrap(), &Settings::default())
+}
+
+#[test]
+fn fractions_and_powers() {
+    assert_eq!(tex("(x + 1) / (x - 1)^2"), "\\frac{x + 1}{\\left(x - 1\\right)^{2}}");
+    assert_eq!(tex("(x^2)^3"), "\\left(x^{2}\\right)^{3}");
+    assert_eq!(tex("2^(x + 1)"), "2^{x + 1}");
+    assert_eq!(tex("2 * (1/3)"), "2 \\cdot \\frac{1}{3}");
+    assert_eq!(tex("2 * (x/3)"), "2 \\cdot \\frac{x}{3}");
+    assert_eq!(tex("1.5 * (x/3)^2"), "1.5 \\left(\\frac{x}{3}\\right)^{2}");
+}
+
+#[test]
+fn minimal_parentheses() {
+    assert_eq!(tex("x + 2*y*z - 1"), "x + 2 y \\cdot z - 1");
+    assert_eq!(tex("x - (y - z)"), "x - \\left(y - z\\right)");
+    assert_eq!(tex("(x + y) * z"), "\\left(x + y\\right) \\cdot z");
+    assert_eq!(tex("x * -y"), "x \\cdot \\left(-y\\right)");
+    assert_eq!(tex("x + -y"), "x - y");
+}
+
+#[test]
+fn names_and_functions() {
+    assert_eq!(tex("sin(pi * t)"), "\\sin\\left(\\pi \\cdot t\\right)");
+    assert_eq!(tex("sqrt(speed)"), "\\sqrt{\\mathrm{speed}}");
+    assert_eq!(tex("f(x) = abs(x)"), "f\\left(x\\right) = \\left|x\\right|");
+}
+
+#[test]
+fn values() {
+    let mut context = Context::new();
+    let mut run = |line: &str| {
+        let result = computor_v2::parse(line).unwrap().eval(&mut context).unwrap();
+
+        latex(&result, &context.settings)
+    };
+
+    assert_eq!(
+        run("[[1, 2]; [3, 4.5]]"),
+        "\\begin{pmatrix} 1 & 2 \\\\ 3 & 4.5 \\end{pmatrix}"
+    );
+    assert_eq!(run("2 - 3i"), "2 - 3i");
+    assert_eq!(run("-1 / 3"), "-\\frac{1}{3}");
+    assert_eq!(run("1 / 10^20"), "\\frac{1}{100000000000000000000}");
+    assert_eq!(run("0.5 ^ 70"), "8.470329472543003 \\times 10^{-22}");
+}
@@ -0,0 +1,150 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter, Limits};
+use std::time::{Duration, Instant};
+
+fn sandbox(limits: Limits) -> Interpreter {
+    let mut calc = Interpreter::new();
+    calc.set_limits(limits);
+
+    calc
+}
+
+#[test]
+fn steps_and_time() {
+    let mut calc = sandbox(Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    });
+
+    calc.eval("f(x) = x + 1").unwrap();
+    calc.eval("f(f(f(1)))").unwrap();
+    match calc.eval("sum(f, 1, 1000)") {
+        Err(ExprError::StepLimit { limit: 1000 }) => {}
+        res => panic!("{:?}", res),
+    }
+    // Counted per evaluation
+    calc.eval("f(f(f(1)))").unwrap();
+
+    let mut calc = sandbox(Limits {
+        timeout: Some(Duration::from_millis(50)),
+        ..Limits::default()
+    });
+    calc.eval("f(x) = sum(g, 1, 1000) + x").unwrap();
+    calc.eval("g(x) = x^2").unwrap();
+    match calc.eval("sum(f, 1, 1000000)") {
+        Err(ExprError::Timeout { ms: 50 }) => {}
+        res => panic!("{:?}", res),
+    }
+}
+
+#[test]
+fn sizes() {
+    let mut calc = sandbox(Limits {
+        max_matrix_size: Some(10_000),
+        max_digits: Some(1000),
+        max_context_size: Some(3),
+        ..Limits::default()
+    });
+
+    calc.eval("zeros(100, 100)").unwrap();
+    let lines = [
+        "zeros(100000, 100000)",
+        "eye(1000)",
+        "range(0, 10^300)",
+        "ones(100, 1) ** ones(1, 101)",
+    ];
+    for line in &lines {
+        match calc.eval(line) {
+            Err(ExprError::MatrixTooBig { limit: 10_000 }) => {}
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+
+    calc.eval("2^3000").unwrap();
+    calc.eval("choose(3000, 1500)").unwrap();
+    let lines = ["2^4000", "9999999^9999999", "(1/3)^5000", "100000!", "choose(10^15, 10^9)"];
+    for line in &lines {
+        match calc.eval(line) {
+            Err(ExprError::NumberTooBig { limit: 1000 }) => {}
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+
+    calc.eval("a = 1").unwrap();
+    calc.eval("f(x) = x").unwrap();
+    calc.eval("g(x) = x").unwrap();
+    calc.eval("a = 2").unwrap();
+    match calc.eval("b = 1") {
+        Err(ExprError::ContextFull { limit: 3 }) => {}
+        res => panic!("{:?}", res),
+    }
+    assert!(calc.set_var("c", 1).is_err());
+
+    // Results are kept whatever the room left, and don't take any
+    let mut calc = sandbox(Limits {
+        max_context_size: Some(1),
+        ..Limits::default()
+    });
+    calc.number_results();
+    calc.set_var("a", 1).unwrap();
+    assert_eq!(calc.eval_as::<i64>("1 + 1").unwrap(), 2);
+    assert_eq!(calc.eval_as::<i64>("ans + $1").unwrap(), 4);
+    calc.set_var("a", 2).unwrap();
+    assert!(calc.set_var("b", 1).is_err());
+
+    // Checked along a `sum` or a `prod`, not only on what comes out
+    let mut calc = sandbox(Limits {
+        max_digits: Some(1000),
+        ..Limits::default()
+    });
+    calc.eval("f(x) = x").unwrap();
+    match calc.eval("prod(f, 1, 100000)") {
+        Err(ExprError::NumberTooBig { limit: 1000 }) => {}
+        res => panic!("{:?}", res),
+    }
+}
+
+#[test]
+fn long_loops_watch_the_time() {
+    // Nothing but the timeout to stop them
+    let mut calc = sandbox(Limits {
+        max_digits: None,
+        timeout: Some(Duration::from_millis(10)),
+        ..Limits::default()
+    });
+    calc.eval("f(x) = 963761198400 * x^5 + 7 * x + 963761198400").unwrap();
+    calc.eval("m = reshape(range(0, 90000), 300, 300)").unwrap();
+
+    let mut lines = vec!["99999999!", "7^(10^9)", "(2/3)^(10^9)", "choose(10^15, 10^9)"];
+    lines.extend(&["isprime(2^33331 - 1)", "factor(f)", "eig(m)", "svd(m)"]);
+    lines.extend(&[":precision", "pi", "exp(1/3)", "ln(2)", "sin(1)", "atan(1/3)", "2^(1/3)"]);
+    for line in lines {
+        if line == ":precision" {
+            calc.set("precision", "10000").unwrap();
+            continue;
+        }
+
+        let start = Instant::now();
+        match calc.eval(line) {
+            Err(ExprError::Timeout { ms: 10 }) => {}
+            res => panic!("{}: {:?}", line, res),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "{}", line);
+    }
+}
+
+#[test]
+fn no_panics() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(calc.eval_as::<(f64, f64)>("(1 + 2i) / (3 - 4i)").unwrap(), (-0.2, 0.4));
+    match calc.eval("(1 + 2i) / (0 * 1i)") {
+        Err(ExprError::DivisionByZero) => {}
+        res => panic!("{:?}", res),
+    }
+
+    calc.eval("empty = zeros(0, 0)").unwrap();
+    assert!(calc.eval("empty ** empty").is_err());
+    assert!(calc.eval("[[1, 2]] ** empty").is_err());
+}
@@ -0,0 +1,204 @@
+extern crate computor_v2;
+
+use computor_v2::linalg::{self, Matrix};
+use computor_v2::{Context, Deadline, Expr, ExprError};
+
+fn mul(a: &[Vec<f64>], b: &[Vec<f64>]) -> Matrix {
+    a.iter()
+        .map(|row| {
+            (0..b[0].len())
+                .map(|j| row.iter().zip(b).map(|(x, b)| x * b[j]).sum())
+                .collect()
+        })
+        .collect()
+}
+
+fn assert_close(a: &[Vec<f64>], b: &[Vec<f64>]) {
+    assert_eq!(a.len(), b.len(), "{:?} vs {:?}", a, b);
+
+    for (x, y) in a.iter().flatten().zip(b.iter().flatten()) {
+        assert!((x - y).abs() < 1e-9, "{:?} vs {:?}", a, b);
+    }
+}
+
+fn assert_orthonormal_columns(q: &[Vec<f64>]) {
+    let n = q[0].len();
+
+    assert_close(&mul(&linalg::transpose(q), q), &linalg::identity(n));
+}
+
+fn m(rows: &[&[f64]]) -> Matrix {
+    rows.iter().map(|row| row.to_vec()).collect()
+}
+
+#[test]
+fn lu_pivots_and_reconstructs() {
+    let a = m(&[&[2.0, 1.0, 1.0], &[4.0, -6.0, 0.0], &[-2.0, 7.0, 2.0]]);
+    let (l, u, p) = linalg::lu(&a).unwrap();
+
+    assert_close(&mul(&p, &a), &mul(&l, &u));
+    assert_eq!(u[0], vec![4.0, -6.0, 0.0]);
+
+    for i in 0..3 {
+        assert_eq!(l[i][i], 1.0);
+        for j in i + 1..3 {
+            assert_eq!(l[i][j], 0.0);
+            assert_eq!(u[j][i], 0.0);
+        }
+    }
+}
+
+#[test]
+fn lu_of_singular_matrix() {
+    let a = m(&[&[1.0, 2.0], &[2.0, 4.0]]);
+    let (l, u, p) = linalg::lu(&a).unwrap();
+
+    assert_close(&mul(&p, &a), &mul(&l, &u));
+    assert_eq!(u[1][1], 0.0);
+}
+
+#[test]
+fn qr_matches_known_factors() {
+    let a = m(&[&[12.0, -51.0, 4.0], &[6.0, 167.0, -68.0], &[-4.0, 24.0, -41.0]]);
+    let (q, r) = linalg::qr(&a).unwrap();
+
+    assert_close(&r, &m(&[&[14.0, 21.0, -14.0], &[0.0, 175.0, -70.0], &[0.0, 0.0, 35.0]]));
+    assert_close(&mul(&q, &r), &a);
+    assert_orthonormal_columns(&q);
+}
+
+#[test]
+fn qr_of_tall_matrix() {
+    let a = m(&[&[1.0, 2.0], &[3.0, 4.0], &[5.0, 6.0]]);
+    let (q, r) = linalg::qr(&a).unwrap();
+
+    assert_eq!((q.len(), q[0].len()), (3, 3));
+    assert_eq!(r[2], vec![0.0, 0.0]);
+    assert_close(&mul(&q, &r), &a);
+    assert_orthonormal_columns(&q);
+}
+
+#[test]
+fn solve_square_and_least_squares() {
+    let x = linalg::solve(&m(&[&[0.0, 2.0], &[1.0, 1.0]]), &m(&[&[4.0], &[3.0]])).unwrap();
+    assert_close(&x, &m(&[&[1.0], &[2.0]]));
+
+    // Best line through (1, 1), (2, 2), (3, 2)
+    let a = m(&[&[1.0, 1.0], &[1.0, 2.0], &[1.0, 3.0]]);
+    let x = linalg::solve(&a, &m(&[&[1.0], &[2.0], &[2.0]])).unwrap();
+    assert_close(&x, &m(&[&[2.0 / 3.0], &[0.5]]));
+}
+
+#[test]
+fn solve_tells_singular_from_inconsistent() {
+    let a = m(&[&[1.0, 2.0], &[2.0, 4.0]]);
+
+    match linalg::solve(&a, &m(&[&[1.0], &[2.0]])) {
+        Err(ExprError::SingularMatrix) => {}
+        other => panic!("{:?}", other),
+    }
+    match linalg::solve(&a, &m(&[&[1.0], &[3.0]])) {
+        Err(ExprError::InconsistentSystem) => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn chol_matches_known_factor() {
+    let a = m(&[&[4.0, 12.0, -16.0], &[12.0, 37.0, -43.0], &[-16.0, -43.0, 98.0]]);
+    let l = linalg::chol(&a).unwrap();
+
+    assert_close(&l, &m(&[&[2.0, 0.0, 0.0], &[6.0, 1.0, 0.0], &[-8.0, 5.0, 3.0]]));
+}
+
+#[test]
+fn chol_rejects_indefinite_and_asymmetric() {
+    assert!(linalg::chol(&m(&[&[1.0, 2.0], &[2.0, 1.0]])).is_err());
+    assert!(linalg::chol(&m(&[&[1.0, 2.0], &[0.0, 1.0]])).is_err());
+}
+
+#[test]
+fn svd_of_wide_matrix() {
+    let a = m(&[&[3.0, 2.0, 2.0], &[2.0, 3.0, -2.0]]);
+    let (u, s, v) = linalg::svd(&a, Deadline::default()).unwrap();
+
+    assert_close(&s, &m(&[&[5.0, 0.0], &[0.0, 3.0]]));
+    assert_close(&mul(&mul(&u, &s), &linalg::transpose(&v)), &a);
+    assert_orthonormal_columns(&u);
+    assert_orthonormal_columns(&v);
+}
+
+#[test]
+fn svd_of_rank_deficient_matrix() {
+    let a = m(&[&[1.0, 1.0], &[1.0, 1.0], &[0.0, 0.0]]);
+    let (u, s, v) = linalg::svd(&a, Deadline::default()).unwrap();
+
+    assert_close(&s, &m(&[&[2.0, 0.0], &[0.0, 0.0]]));
+    assert_close(&mul(&mul(&u, &s), &linalg::transpose(&v)), &a);
+    assert_orthonormal_columns(&u);
+    assert_orthonormal_columns(&v);
+}
+
+#[test]
+fn eig_of_symmetric_matrix() {
+    let a = m(&[&[2.0, 0.0, 0.0], &[0.0, 3.0, 4.0], &[0.0, 4.0, 9.0]]);
+    let (values, vectors) = linalg::eig(&a, Deadline::default()).unwrap();
+
+    assert_eq!(values.len(), 3);
+    for (&(re, im), expected) in values.iter().zip(&[11.0, 2.0, 1.0]) {
+        assert!((re - expected).abs() < 1e-9 && im == 0.0, "{:?}", values);
+    }
+
+    let lambda = m(&[&[11.0, 0.0, 0.0], &[0.0, 2.0, 0.0], &[0.0, 0.0, 1.0]]);
+    assert_close(&mul(&a, &vectors), &mul(&vectors, &lambda));
+    assert_orthonormal_columns(&vectors);
+}
+
+#[test]
+fn eig_of_nonsymmetric_matrix() {
+    let a = m(&[&[4.0, 1.0], &[2.0, 3.0]]);
+    let (values, vectors) = linalg::eig(&a, Deadline::default()).unwrap();
+
+    assert!((values[0].0 - 5.0).abs() < 1e-9 && (values[1].0 - 2.0).abs() < 1e-9);
+
+    let lambda = m(&[&[5.0, 0.0], &[0.0, 2.0]]);
+    assert_close(&mul(&a, &vectors), &mul(&vectors, &lambda));
+}
+
+#[test]
+fn eig_with_complex_pair() {
+    let a = m(&[&[1.0, 2.0, 0.0], &[-2.0, 1.0, 0.0], &[0.0, 0.0, 3.0]]);
+    let (values, vectors) = linalg::eig(&a, Deadline::default()).unwrap();
+
+    let expected = [(3.0, 0.0), (1.0, 2.0), (1.0, -2.0)];
+    for (x, y) in values.iter().zip(&expected) {
+        assert!((x.0 - y.0).abs() < 1e-9 && (x.1 - y.1).abs() < 1e-9, "{:?}", values);
+    }
+
+    // Columns 1 and 2 are the real and imaginary parts of the vector for 1 + 2i,
+    // so A (x + iy) = (1 + 2i)(x + iy) splits into A x = x - 2y and A y = 2x + y
+    let lambda = m(&[&[3.0, 0.0, 0.0], &[0.0, 1.0, 2.0], &[0.0, -2.0, 1.0]]);
+    assert_close(&mul(&a, &vectors), &mul(&vectors, &lambda));
+}
+
+#[test]
+fn decompositions_from_the_calculator() {
+    let mut context = Context::new();
+    let run = |line: &str, context: &mut Context| {
+        computor_v2::parse(line).unwrap().eval(context).unwrap()
+    };
+
+    match run("lu([[1, 2]; [3, 4]])", &mut context) {
+        Expr::Tuple(ref items) => assert_eq!(items.len(), 3),
+        other => panic!("{}", other),
+    }
+
+    assert_eq!(
+        run("eig([[2, 0]; [0, 3]])", &mut context).to_string(),
+        "([3, 2], [[0, 1]; [1, 0]])"
+    );
+    assert!(computor_v2::parse("chol([[1, 2]; [2, 1]])")
+        .unwrap()
+        .eval(&mut context)
+        .is_err());
+}
@@ -0,0 +1,111 @@
+extern crate computor_v2;
+#[macro_use]
+extern crate serde_json;
+
+use computor_v2::lsp::{self, Server};
+use serde_json::Value;
+use std::io::Cursor;
+
+const URI: &str = "file:///script.cv";
+
+fn open(server: &mut Server, text: &str) -> Value {
+    let message = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": URI, "text": text } },
+    });
+
+    server.handle(&message).remove(0)
+}
+
+fn request(server: &mut Server, method: &str, line: usize, character: usize) -> Value {
+    let message = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        },
+    });
+
+    server.handle(&message).remove(0)["result"].clone()
+}
+
+#[test]
+fn diagnostics_point_at_the_problem() {
+    let mut server = Server::new();
+    let res = open(&mut server, "# comment\nx = 1 +\ny = 1 / 0\nz = 2");
+    let diagnostics = res["params"]["diagnostics"].as_array().unwrap();
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 7 }));
+    assert_eq!(diagnostics[1]["range"]["start"]["line"], json!(2));
+    assert_eq!(diagnostics[1]["message"], json!("division by zero"));
+}
+
+#[test]
+fn diagnostics_on_non_ascii_tokens() {
+    let mut server = Server::new();
+    let res = open(&mut server, "x = é\ny = 1 + 𝑥");
+    let diagnostics = res["params"]["diagnostics"].as_array().unwrap();
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 0, "character": 4 }));
+    assert_eq!(diagnostics[0]["range"]["end"], json!({ "line": 0, "character": 5 }));
+    // Two UTF-16 units
+    assert_eq!(diagnostics[1]["range"]["start"], json!({ "line": 1, "character": 8 }));
+    assert_eq!(diagnostics[1]["range"]["end"], json!({ "line": 1, "character": 10 }));
+}
+
+#[test]
+fn lines_are_limited() {
+    let mut server = Server::new();
+    let res = open(&mut server, "f(x) = x\ns = sum(f, 1, 10^9)\nt = sum(f, 1, 10)");
+    let diagnostics = res["params"]["diagnostics"].as_array().unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], json!(1));
+    // Steps or time, whichever runs out first
+    assert!(diagnostics[0]["message"].as_str().unwrap().starts_with("evaluation takes"));
+}
+
+#[test]
+fn hover_completion_and_definition() {
+    let mut server = Server::new();
+    open(&mut server, "f(x) = x + 1\n\nv = f(2)");
+
+    let hover = request(&mut server, "textDocument/hover", 2, 0);
+    assert_eq!(hover["contents"]["value"], json!("```\nv = 3\n```"));
+    let hover = request(&mut server, "textDocument/hover", 2, 4);
+    assert_eq!(hover["contents"]["value"], json!("```\nf(x) = (x + 1)\n```"));
+
+    let items = request(&mut server, "textDocument/completion", 0, 0);
+    let labels: Vec<&Value> = items.as_array().unwrap().iter().map(|x| &x["label"]).collect();
+    assert!(labels.contains(&&json!("f")) && labels.contains(&&json!("v")));
+
+    let location = request(&mut server, "textDocument/definition", 2, 4);
+    assert_eq!(location["range"]["start"], json!({ "line": 0, "character": 0 }));
+    assert_eq!(request(&mut server, "textDocument/definition", 2, 0), Value::Null);
+}
+
+#[test]
+fn framed_messages_over_a_stream() {
+    let mut input = String::new();
+    for message in &[
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ] {
+        let body = message.to_string();
+        input += &format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    }
+
+    let mut output = Vec::new();
+    let clean = lsp::run(&mut Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(clean);
+    assert_eq!(output.matches("Content-Length").count(), 2);
+    assert!(output.contains("\"hoverProvider\":true"));
+}
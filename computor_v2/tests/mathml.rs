@@ -0,0 +1,37 @@
+extern crate computor_v2;
+
+use computor_v2::format::Settings;
+use computor_v2::mathml::mathml;
+
+fn math(line: &str) -> String {
+    let res = mathml(&computor_v2::parse(line).unwrap(), &Settings::default());
+    let inner = res
+        .trim_start_matches("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">")
+        .trim_end_matches("</math>");
+
+    inner.to_string()
+}
+
+#[test]
+fn fractions_and_powers() {
+    assert_eq!(
+        math("1 / x^2"),
+        "<mfrac><mn>1</mn><msup><mi>x</mi><mn>2</mn></msup></mfrac>"
+    );
+    assert_eq!(
+        math("(x + 1)^2"),
+        "<msup><mrow><mo>(</mo><mi>x</mi><mo>+</mo><mn>1</mn><mo>)</mo></mrow><mn>2</mn></msup>"
+    );
+}
+
+#[test]
+fn signs_and_names() {
+    assert_eq!(
+        math("2 * pi + -x"),
+        "<mrow><mn>2</mn><mo>&#x2062;</mo><mi>π</mi><mo>-</mo><mi>x</mi></mrow>"
+    );
+    assert_eq!(
+        math("sin(x)"),
+        "<mrow><mi>sin</mi><mo>&#x2061;</mo><mo>(</mo><mi>x</mi><mo>)</mo></mrow>"
+    );
+}
@@ -0,0 +1,98 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+fn calc_error(calc: &mut Interpreter, line: &str) {
+    match calc.eval(line) {
+        Err(ExprError::CalcError { .. }) => {}
+        res => panic!("{}: {:?}", line, res),
+    }
+}
+
+#[test]
+fn builders() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(eval(&mut calc, "zeros(2, 3)"), "[[0, 0, 0]; [0, 0, 0]]");
+    assert_eq!(eval(&mut calc, "ones(1, 2)"), "[[1, 1]]");
+    assert_eq!(eval(&mut calc, "eye(2)"), "[[1, 0]; [0, 1]]");
+    assert_eq!(eval(&mut calc, "diag([1, 2])"), "[[1, 0]; [0, 2]]");
+    assert_eq!(eval(&mut calc, "diag([[1, 2]; [3, 4]])"), "[1, 4]");
+    assert_eq!(eval(&mut calc, "diag([[1, 2, 3]])"), "[[1, 0, 0]; [0, 2, 0]; [0, 0, 3]]");
+    assert_eq!(eval(&mut calc, "range(0, 4)"), "[[0, 1, 2, 3]]");
+    assert_eq!(eval(&mut calc, "range(1, 2, 0.25)"), "[[1, 1.25, 1.5, 1.75]]");
+    assert_eq!(eval(&mut calc, "range(3, 0, -1)"), "[[3, 2, 1]]");
+    assert_eq!(eval(&mut calc, "range(0, 0)"), "[[]]");
+    assert_eq!(eval(&mut calc, "reshape(range(0, 6), 2, 3)"), "[[0, 1, 2]; [3, 4, 5]]");
+    assert_eq!(eval(&mut calc, "reshape([1, 2, 3, 4], 4, 1)"), "[[1]; [2]; [3]; [4]]");
+
+    calc_error(&mut calc, "diag([[1, 2, 3]; [4, 5, 6]])");
+    calc_error(&mut calc, "zeros(-1, 2)");
+    calc_error(&mut calc, "eye(1.5)");
+    calc_error(&mut calc, "range(0, 1, 0)");
+    calc_error(&mut calc, "reshape([1, 2, 3], 2, 2)");
+    calc_error(&mut calc, "reshape([1, 2], 4294967296, 4294967296)");
+    match calc.eval("zeros(2)") {
+        Err(ExprError::BadArgsCount { func }) => assert_eq!(func, "zeros"),
+        res => panic!("{:?}", res),
+    }
+}
+
+#[test]
+fn concatenation() {
+    let mut calc = Interpreter::new();
+    calc.eval("a = [[1, 2]; [3, 4]]").unwrap();
+
+    assert_eq!(eval(&mut calc, "hcat(a, eye(2))"), "[[1, 2, 1, 0]; [3, 4, 0, 1]]");
+    assert_eq!(eval(&mut calc, "vcat(a, [[5, 6]])"), "[[1, 2]; [3, 4]; [5, 6]]");
+    assert_eq!(eval(&mut calc, "hcat(a)"), "[[1, 2]; [3, 4]]");
+
+    calc_error(&mut calc, "hcat(a, [[1, 2, 3]])");
+    calc_error(&mut calc, "vcat(a, [[1, 2, 3]])");
+    calc_error(&mut calc, "vcat(a, 1)");
+    assert!(calc.eval("hcat()").is_err());
+}
+
+#[test]
+fn indexing_and_slicing() {
+    let mut calc = Interpreter::new();
+    calc.eval("m = reshape(range(0, 12), 3, 4)").unwrap();
+    calc.eval("v = [10, 20, 30]").unwrap();
+
+    assert_eq!(eval(&mut calc, "m[1, 2]"), "6");
+    assert_eq!(eval(&mut calc, "m[0]"), "[[0, 1, 2, 3]]");
+    assert_eq!(eval(&mut calc, "m[:, 0]"), "[[0]; [4]; [8]]");
+    assert_eq!(eval(&mut calc, "m[1:, 2:]"), "[[6, 7]; [10, 11]]");
+    assert_eq!(eval(&mut calc, "m[:1, :]"), "[[0, 1, 2, 3]]");
+    assert_eq!(eval(&mut calc, "m[0:0, :]"), "[]");
+    assert_eq!(eval(&mut calc, "v[2]"), "30");
+    assert_eq!(eval(&mut calc, "v[1:]"), "[20, 30]");
+    assert_eq!(eval(&mut calc, "v[:]"), "[10, 20, 30]");
+    assert_eq!(eval(&mut calc, "lu(eye(2))[2]"), "[[1, 0]; [0, 1]]");
+
+    for (line, index, len) in &[
+        ("v[3]", 3, 3),
+        ("v[-1]", -1, 3),
+        ("v[1:4]", 4, 3),
+        ("m[3, 0]", 3, 3),
+        ("m[0, 4]", 4, 4),
+        ("m[:, 5:]", 5, 4),
+    ] {
+        match calc.eval(line) {
+            Err(ExprError::IndexOutOfBounds { index: i, len: l }) => {
+                assert_eq!((i, l), (*index, *len), "{}", line)
+            }
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+
+    calc_error(&mut calc, "v[2:1]");
+    calc_error(&mut calc, "v[0.5]");
+    calc_error(&mut calc, "v[0, 0]");
+    calc_error(&mut calc, "m[0, 0, 0]");
+    calc_error(&mut calc, "(1 + 2)[0]");
+}
@@ -0,0 +1,151 @@
+extern crate computor_v2;
+
+use computor_v2::{Arity, Expr, ExprError, Interpreter, Limits, NativeFunction};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Lookup table, counts how many times it is called
+struct Table {
+    values: Vec<f64>,
+    calls: Arc<AtomicUsize>,
+}
+
+impl NativeFunction for Table {
+    fn name(&self) -> &str {
+        "table"
+    }
+
+    fn arity(&self) -> Arity {
+        Arity::Exact(1)
+    }
+
+    fn call(&self, args: &[Expr]) -> Result<Expr, ExprError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let index = i64::try_from(args[0].clone())?;
+
+        match self.values.get(index as usize) {
+            Some(x) if index >= 0 => Ok(Expr::Real(*x)),
+            _ => Err(ExprError::IndexOutOfBounds {
+                index,
+                len: self.values.len(),
+            }),
+        }
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+}
+
+struct Mean;
+
+impl NativeFunction for Mean {
+    fn name(&self) -> &str {
+        "Mean"
+    }
+
+    fn arity(&self) -> Arity {
+        Arity::Variadic
+    }
+
+    fn call(&self, args: &[Expr]) -> Result<Expr, ExprError> {
+        let mut sum = 0.0;
+
+        for arg in args {
+            sum += f64::try_from(arg.clone())?;
+        }
+
+        Ok(Expr::Real(sum / args.len() as f64))
+    }
+}
+
+/// Gives back its argument
+struct Echo;
+
+impl NativeFunction for Echo {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn arity(&self) -> Arity {
+        Arity::Exact(1)
+    }
+
+    fn call(&self, args: &[Expr]) -> Result<Expr, ExprError> {
+        Ok(args[0].clone())
+    }
+
+    fn is_pure(&self) -> bool {
+        true
+    }
+}
+
+fn calculator() -> (Interpreter, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let table = Table {
+        values: vec![1.5, 2.5, 4.0],
+        calls: calls.clone(),
+    };
+
+    let mut calc = Interpreter::new();
+    calc.register(table).unwrap();
+    calc.register(Mean).unwrap();
+
+    (calc, calls)
+}
+
+#[test]
+fn calls_dispatch_to_natives() {
+    let (mut calc, _) = calculator();
+
+    assert_eq!(calc.eval_as::<f64>("table(1) * 2").unwrap(), 5.0);
+    assert_eq!(calc.eval_as::<f64>("mean(1, 2, table(2), 5)").unwrap(), 3.0);
+
+    match calc.eval("table(1, 2)") {
+        Err(ExprError::BadArgsCount { func }) => assert_eq!(func, "table"),
+        res => panic!("{:?}", res),
+    }
+    match calc.eval("table(7)") {
+        Err(ExprError::IndexOutOfBounds { index: 7, len: 3 }) => {}
+        res => panic!("{:?}", res),
+    }
+
+    // User functions take over the name
+    calc.eval("table(x) = x + 100").unwrap();
+    assert_eq!(calc.eval_as::<i64>("table(1)").unwrap(), 101);
+}
+
+#[test]
+fn pure_calls_on_constants_are_folded() {
+    let (mut calc, calls) = calculator();
+
+    let body = calc.eval("f(x) = x * table(2) + table(x)").unwrap();
+    assert_eq!(body.to_string(), "((x * 4) + table(x))");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    for x in 0..3 {
+        calc.eval(&format!("f({})", x)).unwrap();
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // Left alone when it fails, the error comes when the function is run
+    calc.eval("g(x) = x + table(-1)").unwrap();
+    assert!(calc.eval("g(1)").is_err());
+    // Within the limits of the caller
+    calc.register(Echo).unwrap();
+    calc.set_limits(Limits {
+        max_digits: Some(10),
+        ..Limits::default()
+    });
+    let big = "1".repeat(20);
+    let body = calc.eval(&format!("h(x) = x + echo({})", big)).unwrap();
+    assert_eq!(body.to_string(), format!("(x + echo({}))", big));
+    calc.define_fn("k", &["x"], &format!("x + echo({})", big)).unwrap();
+    match calc.eval("k(1)") {
+        Err(ExprError::NumberTooBig { limit: 10 }) => {}
+        res => panic!("{:?}", res),
+    }
+    let body = calc.eval("h(x) = x + echo(12345)").unwrap();
+    assert_eq!(body.to_string(), "(x + 12345)");
+}
@@ -0,0 +1,71 @@
+extern crate computor_v2;
+extern crate proptest;
+
+use computor_v2::{Interpreter, Limits};
+use proptest::prelude::*;
+use std::time::Duration;
+
+/// Pieces of the input language, random sequences of them reach much deeper
+/// than random characters, which hardly ever get past the parser
+const TOKENS: &[&str] = &[
+    "x", "y", "f", "g", "ans", "e", "pi", "km", "s", "kg", "$1", "i", "2i", "0", "1", "2", "7",
+    "0.5", "0.0", "3.14159265358979", "99999999999999999999", "(", ")", "[", "]", "[]", "[[]]",
+    ";", ",", ":", "+", "-", "*", "/", "%", "^", "**", "\\", "!", "=", " to ", "f(x) = ",
+    "x = ", "sqrt", "exp", "ln", "sin", "abs", "choose", "expand", "factor", "polydiv",
+    "compose", "gcd", "lcm", "dot", "cross", "norm", "normalize", "row", "col", "vec", "eig",
+    "lu", "qr", "svd", "chol", "transpose", "zeros", "ones", "eye", "diag", "range", "reshape",
+    "hcat", "vcat", "integrate", "nderiv", "limit", "sum", "prod", "linsolve", "isprime",
+    "antideriv", "plot", "parametric", "polar",
+];
+
+fn sandbox() -> Interpreter {
+    let mut calc = Interpreter::new();
+    calc.set_limits(Limits {
+        max_steps: Some(100_000),
+        max_matrix_size: Some(10_000),
+        max_digits: Some(10_000),
+        timeout: Some(Duration::from_millis(500)),
+        max_context_size: Some(100),
+    });
+
+    calc
+}
+
+fn line() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(TOKENS), 0..20).prop_map(|tokens| tokens.concat())
+}
+
+#[test]
+fn known_panics() {
+    let mut calc = sandbox();
+
+    assert!(calc.eval("[]").is_ok());
+    assert!(calc.eval("[] ** []").is_err());
+    assert!(calc.eval("[[1, 2]] ** []").is_err());
+    for line in &["x^4000000000 = 1", "x^2.5 = 1", "2 * 3", "x"] {
+        computor_v2::computor_v1::computor_v1(line.to_string());
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(3000))]
+
+    #[test]
+    fn token_soup_never_panics(lines in prop::collection::vec(line(), 1..4)) {
+        let mut calc = sandbox();
+
+        for line in &lines {
+            let _ = calc.eval(line);
+        }
+    }
+
+    #[test]
+    fn any_text_never_panics(line in "\\PC{0,40}") {
+        let _ = sandbox().eval(&line);
+    }
+
+    #[test]
+    fn any_equation_never_panics(line in "[-+*/^=x0-9. ]{0,30}") {
+        computor_v2::computor_v1::computor_v1(line);
+    }
+}
@@ -0,0 +1,199 @@
+extern crate computor_v2;
+
+use computor_v2::export;
+use computor_v2::plot::{self, Curve, Kind, Plot};
+use computor_v2::{Context, Expr, ExprError};
+
+fn line(label: &str, f: &dyn Fn(f64) -> Option<f64>) -> Curve {
+    Curve::sample(label.to_string(), &mut |x| f(x), -1.0, 1.0)
+}
+
+/// Bit by bit, unlike the table driven one of `export`
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+/// Type and data of each chunk, checking the lengths and CRCs on the way
+fn chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut res = vec![];
+    let mut rest = &png[8..];
+
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        assert!(rest.len() >= len + 12, "chunk overruns the file");
+
+        let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+        let crc = &rest[8 + len..12 + len];
+        assert_eq!(crc, &crc32(&rest[4..8 + len]).to_be_bytes()[..], "{:?}", kind);
+
+        res.push((kind, data));
+        rest = &rest[12 + len..];
+    }
+
+    res
+}
+
+#[test]
+fn ticks_are_round_numbers() {
+    assert_eq!(plot::ticks(-3.0, 3.5), (vec![-2.0, 0.0, 2.0], 2.0));
+    assert_eq!(plot::ticks(0.0, 1.0).0, vec![0.0, 0.5, 1.0]);
+    assert_eq!(plot::tick_label(-0.0001, 0.5), "0.0");
+    assert_eq!(plot::tick_label(2.0, 2.0), "2");
+}
+
+#[test]
+fn render_fits_the_size() {
+    let chart = Plot {
+        curves: vec![line("f", &|x| Some(x * x))],
+        kind: Kind::Function,
+        x: (-1.0, 1.0),
+    };
+    let text = plot::render(&chart, (60, 20));
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert_eq!(lines.len(), 18);
+    assert!(lines.iter().all(|line| line.chars().count() < 60), "{}", text);
+    assert!(text.chars().any(|c| ('\u{2801}'..='\u{28ff}').contains(&c)));
+}
+
+#[test]
+fn several_curves_get_glyphs_and_a_legend() {
+    let chart = Plot {
+        curves: vec![line("f", &|x| Some(x)), line("g", &|x| Some(-x))],
+        kind: Kind::Function,
+        x: (-1.0, 1.0),
+    };
+    let text = plot::render(&chart, (60, 20));
+
+    assert!(text.contains('•') && text.contains('+'));
+    assert!(text.lines().last().unwrap().contains("• f   + g"));
+}
+
+#[test]
+fn failed_points_are_gaps() {
+    let curve = line("f", &|x| if x < 0.0 { None } else { Some(x.sqrt()) });
+
+    assert!(curve.points[..plot::SAMPLES / 2].iter().all(|p| p.is_none()));
+    assert!(curve.points[plot::SAMPLES / 2..].iter().all(|p| p.is_some()));
+}
+
+#[test]
+fn plot_from_the_calculator() {
+    let mut context = Context::new();
+
+    computor_v2::parse("f(x) = sqrt(x)").unwrap().eval(&mut context).unwrap();
+    match computor_v2::parse("plot(f, -1, 1)").unwrap().eval(&mut context) {
+        Ok(Expr::Plot(chart)) => {
+            assert_eq!(chart.curves[0].label, "f");
+            assert!(chart.curves[0].points[0].is_none());
+        }
+        other => panic!("{:?}", other),
+    }
+
+    assert!(computor_v2::parse("plot(f, 1, -1)").unwrap().eval(&mut context).is_err());
+}
+
+#[test]
+fn export_to_svg_and_png() {
+    let chart = Plot {
+        curves: vec![line("f", &|x| Some(x)), line("a < b", &|x| Some(-x))],
+        kind: Kind::Function,
+        x: (-1.0, 1.0),
+    };
+    let svg = export::svg(&chart);
+    let png = export::png(&chart);
+
+    assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains("a &lt; b") && !svg.contains("a < b"));
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert!(export::save(&chart, "out.txt").is_err());
+
+    let chunks = chunks(&png);
+    let kinds: Vec<&[u8]> = chunks.iter().map(|chunk| chunk.0).collect();
+    assert_eq!(kinds, vec![&b"IHDR"[..], b"IDAT", b"IEND"]);
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+    let header = chunks[0].1;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    assert_eq!(&header[8..], &[8, 2, 0, 0, 0]);
+
+    // Stored deflate blocks: zlib header, 5 bytes per block of up to 65535 raw bytes
+    // (RGB rows each behind a filter byte), Adler-32
+    let raw = (width * 3 + 1) * height;
+    let blocks = (0..raw).step_by(0xffff).count();
+    assert_eq!(chunks[1].1.len(), 2 + 5 * blocks + raw + 4);
+    assert!(chunks[2].1.is_empty());
+}
+
+#[test]
+fn range_of_parametric_and_polar_plots() {
+    let points = |points: &[(f64, f64)]| Curve {
+        label: "c".to_string(),
+        points: points.iter().map(|&p| Some(p)).collect(),
+    };
+
+    let chart = Plot::new(Kind::Parametric, vec![points(&[(2.0, 0.0), (5.0, 9.0)])], (0.0, 1.0));
+    assert_eq!(chart.x, (2.0, 5.0));
+    let chart = Plot::new(Kind::Polar, vec![points(&[(3.0, 1.0), (3.0, 2.0)])], (0.0, 1.0));
+    assert_eq!(chart.x, (2.0, 4.0));
+    let chart = Plot::new(Kind::Polar, vec![points(&[])], (0.0, 1.0));
+    assert_eq!(chart.x, (-1.0, 1.0));
+    // Function plots keep the range they were asked for
+    let chart = Plot::new(Kind::Function, vec![points(&[(2.0, 0.0)])], (-3.0, 3.0));
+    assert_eq!(chart.x, (-3.0, 3.0));
+}
+
+#[test]
+fn parametric_and_polar_from_the_calculator() {
+    let mut context = Context::new();
+    let mut run = |line: &str| computor_v2::parse(line).unwrap().eval(&mut context);
+
+    run("cx(t) = cos(t)").unwrap();
+    run("sy(t) = sin(t)").unwrap();
+    run("two(t) = 2").unwrap();
+
+    match run("parametric(cx, sy, 0, 2 * pi)") {
+        Ok(Expr::Plot(chart)) => {
+            assert_eq!(chart.kind, Kind::Parametric);
+            assert_eq!(chart.curves[0].label, "(cx, sy)");
+            for &(x, y) in chart.curves[0].points.iter().flatten() {
+                assert!((x.hypot(y) - 1.0).abs() < 1e-9);
+            }
+            assert!((chart.x.0 + 1.0).abs() < 1e-3 && (chart.x.1 - 1.0).abs() < 1e-3);
+        }
+        other => panic!("{:?}", other),
+    }
+
+    match run("polar(two, cx, 0, pi)") {
+        Ok(Expr::Plot(chart)) => {
+            assert_eq!(chart.kind, Kind::Polar);
+            assert_eq!(chart.curves.len(), 2);
+            for &(x, y) in chart.curves[0].points.iter().flatten() {
+                assert!((x.hypot(y) - 2.0).abs() < 1e-9);
+            }
+            assert!((chart.x.0 + 2.0).abs() < 1e-9 && (chart.x.1 - 2.0).abs() < 1e-9);
+        }
+        other => panic!("{:?}", other),
+    }
+
+    for line in &["parametric(cx, 0, 1)", "parametric(cx, sy, cx, 0, 1)"] {
+        match run(line) {
+            Err(ExprError::CalcError { err }) => assert!(err.contains("pairs"), "{}", err),
+            other => panic!("{}: {:?}", line, other),
+        }
+    }
+    match run("polar(0, 1)") {
+        Err(ExprError::BadArgsCount { func }) => assert_eq!(func, "polar"),
+        other => panic!("{:?}", other),
+    }
+}
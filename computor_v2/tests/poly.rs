@@ -0,0 +1,100 @@
+extern crate computor_v2;
+extern crate num_bigint;
+extern crate num_rational;
+
+use computor_v2::computor_v1::Poly;
+use computor_v2::{Context, Deadline};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+
+fn p(coefs: &[i64]) -> Poly {
+    Poly::new(coefs.iter().map(|&c| BigRational::from_integer(BigInt::from(c))).collect())
+}
+
+#[test]
+fn division_with_remainder() {
+    // x^3 - 2x^2 - 4 = (x - 3)(x^2 + x + 3) + 5
+    let (quot, rem) = p(&[-4, 0, -2, 1]).div_rem(&p(&[-3, 1])).unwrap();
+
+    assert_eq!(quot, p(&[3, 1, 1]));
+    assert_eq!(rem, p(&[5]));
+    assert!(p(&[1, 1]).div_rem(&Poly::zero()).is_none());
+}
+
+#[test]
+fn gcd_is_monic() {
+    let a = p(&[1, 1]).mul(&p(&[-2, 1])).scale(&BigRational::from_integer(BigInt::from(3)));
+    let b = p(&[1, 1]).mul(&p(&[5, 0, 1]));
+
+    assert_eq!(a.gcd(&b), p(&[1, 1]));
+    assert_eq!(p(&[2]).gcd(&p(&[0, 1])), p(&[1]));
+}
+
+#[test]
+fn composition_and_evaluation() {
+    let f = p(&[1, 0, 1]);
+    let g = p(&[-1, 2]);
+    let two = BigRational::from_integer(BigInt::from(2));
+
+    assert_eq!(f.compose(&g), p(&[2, -4, 4]));
+    assert_eq!(f.compose(&g).eval(&two), f.eval(&g.eval(&two)));
+}
+
+#[test]
+fn factor_over_the_rationals() {
+    // 2 (x + 1)^2 (3x - 1) (x^2 + 1)
+    let f = p(&[1, 1])
+        .pow(2)
+        .mul(&p(&[-1, 3]))
+        .mul(&p(&[1, 0, 1]))
+        .scale(&BigRational::from_integer(BigInt::from(2)));
+    let factors = f.factor(Deadline::default()).unwrap();
+
+    assert_eq!(factors.constant, BigRational::from_integer(BigInt::from(2)));
+    assert!(factors.exact.contains(&(p(&[1, 1]), 2)));
+    assert!(factors.exact.contains(&(p(&[-1, 3]), 1)));
+
+    let roots: Vec<_> = factors.numeric.iter().map(|&(root, _)| root).collect();
+    assert_eq!(roots.len(), 2);
+    for (root, im) in roots.iter().zip(&[-1.0, 1.0]) {
+        assert!(root.0.abs() < 1e-12 && (root.1 - im).abs() < 1e-12, "{:?}", roots);
+    }
+}
+
+#[test]
+fn numeric_roots() {
+    let roots = p(&[-2, 0, 1]).roots();
+
+    assert_eq!(roots.len(), 2);
+    assert!((roots[0].0 + 2f64.sqrt()).abs() < 1e-12 && roots[0].1 == 0.0);
+    assert!((roots[1].0 - 2f64.sqrt()).abs() < 1e-12 && roots[1].1 == 0.0);
+}
+
+#[test]
+fn algebra_from_the_calculator() {
+    let mut context = Context::new();
+    let mut run = |line: &str| {
+        computor_v2::parse(line)
+            .unwrap()
+            .eval(&mut context)
+            .unwrap()
+            .to_string()
+    };
+
+    run("f(x) = (x - 1) * (x + 2)");
+    assert_eq!(run("expand(f)"), "(x) => (((x ^ 2) + x) + -2)");
+    assert_eq!(run("factor(f)"), "(x) => ((x + -1) * (x + 2))");
+
+    run("g(x) = x + 2");
+    assert_eq!(run("polydiv(f, g)"), "((x) => (x + -1), (x) => 0)");
+    assert_eq!(run("gcd(f, g)"), "(x) => (x + 2)");
+
+    run("h(t) = 2 * t");
+    assert_eq!(run("compose(g, h)"), "(t) => ((2 * t) + 2)");
+    // Too big to expand
+    let line = "k(x) = ((x^64)^64)^64";
+    computor_v2::parse(line).unwrap().eval(&mut context).unwrap();
+    for line in &["expand(k)", "antideriv(k)"] {
+        assert!(computor_v2::parse(line).unwrap().eval(&mut context).is_err(), "{}", line);
+    }
+}
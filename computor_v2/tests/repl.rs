@@ -0,0 +1,74 @@
+extern crate computor_v2;
+
+use computor_v2::repl::{history_path, redirect};
+use computor_v2::{ExprError, Interpreter, Limits};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+#[test]
+fn history_follows_xdg() {
+    let home = Some(OsString::from("/home/me"));
+
+    assert_eq!(
+        history_path(Some("/state".into()), home.clone()),
+        Some(PathBuf::from("/state/computor_v2/history"))
+    );
+    // Relative or missing, the spec says to ignore it
+    for state_home in &[None, Some("state".into()), Some("".into())] {
+        assert_eq!(
+            history_path(state_home.clone(), home.clone()),
+            Some(PathBuf::from("/home/me/.local/state/computor_v2/history"))
+        );
+    }
+    assert_eq!(history_path(None, None), None);
+}
+
+#[test]
+fn redirect_to_a_file() {
+    assert_eq!(redirect(r#"plot(f, 0, 1) > "out.svg""#), Some(("plot(f, 0, 1) ", "out.svg")));
+    assert_eq!(redirect(r#"p>"a b.png"  "#), Some(("p", "a b.png")));
+    assert_eq!(redirect(r#"p > """#), Some(("p ", "")));
+    for line in &["1 + 2", r#"p "out.svg""#, r#"p > "out.svg"#, r#"p > out.svg"#, r#"""#] {
+        assert_eq!(redirect(line), None, "{}", line);
+    }
+}
+
+#[test]
+fn results_are_numbered() {
+    let mut calc = Interpreter::new();
+    calc.number_results();
+
+    calc.eval("2 + 3").unwrap();
+    calc.eval("f(x) = x * 10").unwrap();
+    calc.eval("f(ans)").unwrap();
+    assert!(calc.eval("1 / 0").is_err());
+    calc.eval("$1 + $2").unwrap();
+
+    assert_eq!(calc.get_var::<i64>("$1").unwrap(), 5);
+    assert_eq!(calc.get_var::<i64>("$2").unwrap(), 50);
+    assert_eq!(calc.get_var::<i64>("$3").unwrap(), 55);
+    assert_eq!(calc.get_var::<i64>("ans").unwrap(), 55);
+    assert!(calc.get_var::<i64>("$4").is_err());
+
+    // Not numbered unless asked
+    let mut calc = Interpreter::new();
+    calc.eval("2 + 3").unwrap();
+    assert_eq!(calc.get_var::<i64>("ans").unwrap(), 5);
+    assert!(calc.get_var::<i64>("$1").is_err());
+
+    let mut calc = Interpreter::new();
+    calc.number_results();
+    calc.set_limits(Limits {
+        max_context_size: Some(3),
+        ..Limits::default()
+    });
+    // Results take no room
+    calc.eval("a = 1").unwrap();
+    calc.eval("b = 2").unwrap();
+    calc.eval("c = 3").unwrap();
+    assert_eq!(calc.get_var::<i64>("$3").unwrap(), 3);
+    match calc.eval("d = 4") {
+        Err(ExprError::ContextFull { limit: 3 }) => {}
+        res => panic!("{:?}", res),
+    }
+}
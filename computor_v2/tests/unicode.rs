@@ -0,0 +1,32 @@
+extern crate computor_v2;
+
+use computor_v2::format::Settings;
+use computor_v2::unicode::unicode;
+use computor_v2::Context;
+
+fn pretty(line: &str) -> String {
+    unicode(&computor_v2::parse(line).unwrap(), &Settings::default())
+}
+
+#[test]
+fn stacked_fractions() {
+    let lines = [" x + 1", "─────── + y", " x - 1"];
+
+    assert_eq!(pretty("(x + 1) / (x - 1) + y"), lines.join("\n"));
+}
+
+#[test]
+fn exponents() {
+    assert_eq!(pretty("(x - 1)^2 * 3^(-4)"), "(x - 1)²·3⁻⁴");
+    assert_eq!(pretty("2^y"), [" y", "2"].join("\n"));
+    assert_eq!(pretty("sqrt(x) - -pi"), [" _", "√x - (-π)"].join("\n"));
+}
+
+#[test]
+fn matrices_in_brackets() {
+    let mut context = Context::new();
+    let result = computor_v2::parse("[[1, 2.5]; [-3, 40]]").unwrap().eval(&mut context);
+    let lines = ["⎡  1  2.5 ⎤", "⎣ -3   40 ⎦"];
+
+    assert_eq!(unicode(&result.unwrap(), &context.settings), lines.join("\n"));
+}
@@ -0,0 +1,93 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+fn mismatch(calc: &mut Interpreter, line: &str) -> (String, String) {
+    match calc.eval(line) {
+        Err(ExprError::UnitMismatch { left, right }) => (left, right),
+        res => panic!("{}: {:?}", line, res),
+    }
+}
+
+#[test]
+fn dimensions() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(eval(&mut calc, "2 m + 50 cm"), "2.5 m");
+    assert_eq!(eval(&mut calc, "9.81 m/s^2 * 3 kg"), "29.43 m*kg/s^2");
+    assert_eq!(eval(&mut calc, "3 m * 2 m"), "6 m^2");
+    assert_eq!(eval(&mut calc, "6 m^2 / 2 m"), "3 m");
+    assert_eq!(eval(&mut calc, "10 m / 5 m"), "2");
+    assert_eq!(eval(&mut calc, "-2 s * 3"), "-6 s");
+    assert_eq!(eval(&mut calc, "1 n to kg*m/s^2"), "1 kg*m/s^2");
+
+    assert_eq!(mismatch(&mut calc, "3 m + 2 s"), ("m".to_string(), "s".to_string()));
+    assert_eq!(mismatch(&mut calc, "1 kg - 1"), ("kg".to_string(), "1".to_string()));
+    // Exponents stay within 1000 either way
+    calc.eval("q = (1 m)^1000").unwrap();
+    for line in &["q * q", "q / (1 / q)", "1 / q / q"] {
+        match calc.eval(line) {
+            Err(ExprError::CalcError { .. }) => {}
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+}
+
+#[test]
+fn conversion() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(eval(&mut calc, "5 km to mi"), "3.1068559611866697 mi");
+    assert_eq!(eval(&mut calc, "100 km / 2 h to km/h"), "50 km/h");
+    assert_eq!(eval(&mut calc, "1 kwh to j"), "3600000 J");
+    assert_eq!(eval(&mut calc, "2 h to min"), "120 min");
+    assert_eq!(eval(&mut calc, "1 week to day"), "7 day");
+
+    assert_eq!(mismatch(&mut calc, "5 km to s"), ("m".to_string(), "s".to_string()));
+    assert_eq!(mismatch(&mut calc, "5 to m"), ("1".to_string(), "m".to_string()));
+    match calc.eval("5 km to 3") {
+        Err(ExprError::CalcError { .. }) => {}
+        res => panic!("{:?}", res),
+    }
+    match calc.eval("5 km to parsec") {
+        Err(ExprError::UndefinedVariable { name }) => assert_eq!(name, "parsec"),
+        res => panic!("{:?}", res),
+    }
+}
+
+#[test]
+fn only_where_a_unit_is_expected() {
+    let mut calc = Interpreter::new();
+
+    for line in &["t + 1", "a * 2", "m", "2 * s", "f(x) = x + h"] {
+        let res = if line.contains('=') {
+            calc.eval(line).unwrap();
+            calc.eval("f(1)")
+        } else {
+            calc.eval(line)
+        };
+
+        match res {
+            Err(ExprError::UndefinedVariable { .. }) => {}
+            res => panic!("{}: {:?}", line, res),
+        }
+    }
+
+    // Variables and arguments win over units
+    calc.eval("t = 4").unwrap();
+    assert_eq!(eval(&mut calc, "2 t"), "8");
+    calc.eval("g(s) = 3 s + 2 m").unwrap();
+    match calc.eval("g(1)") {
+        Err(ExprError::UnitMismatch { .. }) => {}
+        res => panic!("{:?}", res),
+    }
+    assert_eq!(eval(&mut calc, "g(1 m)"), "5 m");
+    calc.eval("k(s) = 2 s").unwrap();
+    assert_eq!(eval(&mut calc, "sum(k, 1, 3)"), "12");
+    calc.eval("w(x) = x * 1 m").unwrap();
+    assert_eq!(eval(&mut calc, "w(3)"), "3 m");
+}
@@ -0,0 +1,76 @@
+extern crate computor_v2;
+
+use computor_v2::{ExprError, Interpreter};
+
+fn eval(calc: &mut Interpreter, line: &str) -> String {
+    calc.eval(line).unwrap().to_string()
+}
+
+fn calc_error(calc: &mut Interpreter, line: &str) {
+    match calc.eval(line) {
+        Err(ExprError::CalcError { .. }) => {}
+        res => panic!("{}: {:?}", line, res),
+    }
+}
+
+#[test]
+fn arithmetic_and_broadcasting() {
+    let mut calc = Interpreter::new();
+    calc.eval("v = [1, 2, 3]").unwrap();
+
+    assert_eq!(eval(&mut calc, "v + [10, 20, 30]"), "[11, 22, 33]");
+    assert_eq!(eval(&mut calc, "v - [1, 1, 1]"), "[0, 1, 2]");
+    assert_eq!(eval(&mut calc, "-v"), "[-1, -2, -3]");
+    assert_eq!(eval(&mut calc, "v * 2"), "[2, 4, 6]");
+    assert_eq!(eval(&mut calc, "2 * v"), "[2, 4, 6]");
+    assert_eq!(eval(&mut calc, "v + 1"), "[2, 3, 4]");
+    assert_eq!(eval(&mut calc, "1 - v"), "[0, -1, -2]");
+    assert_eq!(eval(&mut calc, "v / 2"), "[0.5, 1, 1.5]");
+
+    calc_error(&mut calc, "v + [1, 2]");
+    // Which product is meant has to be said
+    calc_error(&mut calc, "v * v");
+}
+
+#[test]
+fn products_and_norms() {
+    let mut calc = Interpreter::new();
+
+    assert_eq!(eval(&mut calc, "dot([1, 2, 3], [4, 5, 6])"), "32");
+    assert_eq!(eval(&mut calc, "cross([1, 0, 0], [0, 1, 0])"), "[0, 0, 1]");
+    assert_eq!(eval(&mut calc, "cross([1, 2, 3], [0, -1, 2])"), "[7, -2, -1]");
+    assert_eq!(eval(&mut calc, "norm([3, 4])"), "5");
+    assert_eq!(eval(&mut calc, "normalize([3, 4])"), "[0.6, 0.8]");
+    assert_eq!(eval(&mut calc, "normalize([0, 0, -5])"), "[0, 0, -1]");
+
+    calc_error(&mut calc, "dot([1, 2], [1, 2, 3])");
+    calc_error(&mut calc, "cross([1, 2], [3, 4])");
+    calc_error(&mut calc, "norm(3)");
+    match calc.eval("normalize([0, 0])") {
+        Err(ExprError::DivisionByZero) => {}
+        res => panic!("{:?}", res),
+    }
+    match calc.eval("dot([1, 2])") {
+        Err(ExprError::BadArgsCount { func }) => assert_eq!(func, "dot"),
+        res => panic!("{:?}", res),
+    }
+}
+
+#[test]
+fn vectors_and_matrices() {
+    let mut calc = Interpreter::new();
+    calc.eval("v = [1, 2, 3]").unwrap();
+    calc.eval("m = [[1, 0, 0]; [0, 2, 0]; [0, 0, 3]]").unwrap();
+
+    assert_eq!(eval(&mut calc, "m ** v"), "[1, 4, 9]");
+    assert_eq!(eval(&mut calc, "v ** m"), "[1, 4, 9]");
+    assert_eq!(eval(&mut calc, "[[1, 2, 3]] ** v"), "[14]");
+    assert_eq!(eval(&mut calc, "v ** [[1]; [1]; [1]]"), "[6]");
+    assert_eq!(eval(&mut calc, "row(v)"), "[[1, 2, 3]]");
+    assert_eq!(eval(&mut calc, "col(v)"), "[[1]; [2]; [3]]");
+    assert_eq!(eval(&mut calc, "vec(col(v))"), "[1, 2, 3]");
+
+    calc_error(&mut calc, "m ** [1, 2]");
+    calc_error(&mut calc, "[1, 2] ** m");
+    calc_error(&mut calc, "vec(m)");
+}